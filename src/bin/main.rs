@@ -1,8 +1,15 @@
 extern crate game;
 extern crate glm;
+extern crate sdl2;
 
 mod mains;
 
+use std::env;
+
 fn main() {
-    mains::app::main();
+    if env::args().any(|arg| arg == "--headless") {
+        mains::headless::main();
+    } else {
+        mains::app::main();
+    }
 }
@@ -1,5 +1,11 @@
-use game::app::AppRunner;
+use game::{
+    app::AppRunner,
+    controls::Replay,
+};
+use std;
 
 pub fn main() {
-    AppRunner::new().unwrap().run().unwrap();
+    let args: Vec<String> = std::env::args().collect();
+    let replay = Replay::from_args(&args).unwrap();
+    AppRunner::new(replay).unwrap().run().unwrap();
 }
@@ -1,15 +1,21 @@
 use game::{
     app::AppRunner,
+    assets::AssetManager,
+    controls::Replay,
     file,
     entity::{
         Ball,
     },
+    render::Sprite3D,
 };
 
 pub fn _main() {
-    let _app = AppRunner::new();
+    let _app = AppRunner::new(Replay::Off);
 //    let athlete = Athlete::new().unwrap();
     let ball = Ball::new().unwrap();
-    let out = file::util::resource_path("temp", "ball");
-    ball.sprite.write_to_objs(out.as_str()).unwrap();
+    let mut assets = AssetManager::new();
+    let sprite = Sprite3D::new(ball.sprite_sheet_info(), &mut assets).unwrap();
+    let out_dir = file::util::resource_dir("temp").unwrap();
+    sprite.write_to_objs(&out_dir, "ball").unwrap();
+    sprite.write_to_gltf(&out_dir.join("ball.glb"), &ball.animation).unwrap();
 }
@@ -0,0 +1,95 @@
+use game::{
+    app::{
+        fixed_timestep,
+        FixedTimestep,
+    },
+    controls::{
+        Binding,
+        Input,
+        InputMap,
+        Replay,
+    },
+    entity::PhysicalWorld,
+};
+use sdl2::keyboard::Scancode;
+use std;
+
+const DEFAULT_NUM_TICKS: usize = 600;
+
+// Scripted input: run right, then back left, then stand still.
+fn scripted_actions(tick: usize, num_ticks: usize) -> Vec<&'static str> {
+    if tick < num_ticks / 3 {
+        vec!("move_right")
+    } else if tick < 2 * num_ticks / 3 {
+        vec!("move_left")
+    } else {
+        vec!()
+    }
+}
+
+// Presses the first key bound to each action.
+fn scripted_keys(input_map: &InputMap, actions: &[&str]) -> Vec<Scancode> {
+    actions.iter()
+        .filter_map(|action| input_map.bindings(action).iter()
+            .filter_map(|binding| match *binding {
+                Binding::Key(scancode) => Some(scancode),
+                _ => None,
+            })
+            .next())
+        .collect()
+}
+
+fn num_ticks_arg() -> usize {
+    let args: Vec<String> = std::env::args().collect();
+    args.iter()
+        .position(|arg| arg == "--ticks")
+        .and_then(|i| args.get(i + 1))
+        .and_then(|ticks| ticks.parse().ok())
+        .unwrap_or(DEFAULT_NUM_TICKS)
+}
+
+// Steps the PhysicalWorld without SDL video or OpenGL, printing entity positions. Under
+// --playback the recording replaces the scripted input and decides the number of ticks.
+pub fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let mut replay = Replay::from_args(&args).unwrap();
+    let num_ticks = num_ticks_arg();
+    let scripted_dt = FixedTimestep::new(fixed_timestep::DEFAULT_STEPS_PER_SECOND,
+                                         fixed_timestep::DEFAULT_MAX_STEPS_PER_FRAME).step_delta_time();
+    let mut world = PhysicalWorld::new().unwrap();
+    let mut input = Input::read().unwrap();
+    let mut tick = 0;
+    loop {
+        let dt = match replay {
+            Replay::Playback(ref mut playback) => match playback.next(&mut input) {
+                Some(dt) => dt,
+                None => break,
+            },
+            _ if tick == num_ticks => break,
+            _ => {
+                let keys = scripted_keys(&input.input_map, &scripted_actions(tick, num_ticks));
+                input.keyboard.update_pressed(keys);
+                scripted_dt
+            },
+        };
+        if let Replay::Record(ref mut recorder) = replay {
+            recorder.record(&input, dt).unwrap();
+        }
+        world.update(&input, dt);
+        if tick % 60 == 0 {
+            print_positions(tick, &world);
+        }
+        tick += 1;
+    }
+    if tick > 0 {
+        print_positions(tick - 1, &world);
+    }
+}
+
+// {:?} prints the shortest string that round-trips, so runs can be diffed bit-for-bit.
+fn print_positions(tick: usize, world: &PhysicalWorld) {
+    let athlete = world.athlete().position();
+    let ball = world.ball().position();
+    println!("tick {}: athlete ({:?}, {:?}, {:?}), ball ({:?}, {:?}, {:?})",
+             tick, athlete.x, athlete.y, athlete.z, ball.x, ball.y, ball.z);
+}
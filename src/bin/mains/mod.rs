@@ -1,3 +1,4 @@
 pub mod app;
 pub mod build_obj;
-pub mod size_of;
\ No newline at end of file
+pub mod headless;
+pub mod size_of;
@@ -1,8 +1,15 @@
 use app::{
+    Error,
+    fixed_timestep,
+    FixedTimestep,
     StatusOr,
     time::Clock,
 };
-use controls::KeyboardControls;
+use controls::{
+    Binding,
+    Input,
+    Replay,
+};
 use gl;
 use render::RenderState;
 use sdl2::{
@@ -10,7 +17,6 @@ use sdl2::{
         Event,
         WindowEvent,
     },
-    keyboard::Keycode,
     render::WindowCanvas,
     self,
 };
@@ -21,7 +27,7 @@ fn find_sdl_gl_driver() -> StatusOr<u32> {
             return Ok(index as u32);
         }
     }
-    Err(String::from("Could not find SDL GL driver."))
+    Err(Error::SdlInit(String::from("Could not find SDL GL driver.")))
 }
 
 pub struct AppRunner {
@@ -31,17 +37,21 @@ pub struct AppRunner {
     canvas: WindowCanvas,
     events: sdl2::EventPump,
     clock: Clock,
+    fixed_timestep: FixedTimestep,
     render: RenderState,
-    keyboard: KeyboardControls,
+    input: Input,
+    replay: Replay,
 }
 
 impl AppRunner {
-    pub fn new() -> StatusOr<AppRunner> {
-        let sdl_context = sdl2::init()?;
-        let video_subsystem = sdl_context.video()?;
+    pub fn new(replay: Replay) -> StatusOr<AppRunner> {
+        let sdl_context = sdl2::init().map_err(Error::SdlInit)?;
+        let video_subsystem = sdl_context.video().map_err(Error::SdlInit)?;
         {
             let gl_attr = video_subsystem.gl_attr();
+            // Pinned so GBuffer's DEPTH24_STENCIL8 depth can be blitted into the default framebuffer.
             gl_attr.set_depth_size(24);
+            gl_attr.set_stencil_size(8);
             gl_attr.set_context_version(4, 5);
             gl_attr.set_context_profile(sdl2::video::GLProfile::Core);
         }
@@ -50,9 +60,9 @@ impl AppRunner {
         let window = video_subsystem.window("App", window_size.0 as u32, window_size.1 as u32)
             .opengl()
             .build()
-            .map_err(|err| format!("Error initializing window: {}", err))?;
+            .map_err(|err| Error::SdlInit(format!("Error initializing window: {}", err)))?;
 
-        let gl_context = window.gl_create_context()?;
+        let gl_context = window.gl_create_context().map_err(Error::SdlInit)?;
         gl::load_with(|name| video_subsystem.gl_get_proc_address(name) as *const _);
         video_subsystem.gl_set_swap_interval(1);
 
@@ -60,12 +70,16 @@ impl AppRunner {
             .into_canvas()
             .index(find_sdl_gl_driver()?)
             .build()
-            .map_err(|err| format!("Error initializing canvas: {}", err))?;
-        canvas.window().gl_set_context_to_current()?;
+            .map_err(|err| Error::SdlInit(format!("Error initializing canvas: {}", err)))?;
+        canvas.window().gl_set_context_to_current().map_err(Error::SdlInit)?;
 
         let render = RenderState::new(window_size.0, window_size.1)?;
+        let mut input = Input::read()?;
+        for event in input.controllers.attach(sdl_context.game_controller().map_err(Error::SdlInit)?) {
+            println!("{}", event);
+        }
 
-        let events = sdl_context.event_pump()?;
+        let events = sdl_context.event_pump().map_err(Error::SdlInit)?;
         Ok(AppRunner {
             _sdl_context: sdl_context,
             _gl_context: gl_context,
@@ -73,45 +87,82 @@ impl AppRunner {
             canvas,
             events,
             clock: Clock::start(),
+            fixed_timestep: FixedTimestep::new(fixed_timestep::DEFAULT_STEPS_PER_SECOND,
+                                               fixed_timestep::DEFAULT_MAX_STEPS_PER_FRAME),
             render,
-            keyboard: KeyboardControls::new(),
+            input,
+            replay,
         })
     }
 
     pub fn run(&mut self) -> StatusOr<()> {
         let _ = self.clock.restart();
+        self.fixed_timestep.clear();
         loop {
             match self.process_events() {
                 Err(e) => return Err(e),
-                Ok(false) => return Ok(()),
+                Ok(false) => break,
                 _ => {
-                    self.update();
+                    if !self.update()? {
+                        break;
+                    }
                     self.draw();
                 }
             }
         }
+        if let Some(summary) = self.render.frame_stats().summary() {
+            println!("Last {} frames: {}", self.render.frame_stats().frame_times().len(), summary);
+        }
+        Ok(())
     }
 
     // Return false on quit.
     fn process_events(&mut self) -> StatusOr<bool> {
         for event in self.events.poll_iter() {
            match event {
-               Event::Quit { .. } | Event::KeyDown {keycode: Some(Keycode::Q), ..} => return Ok(false),
+               Event::Quit { .. } => return Ok(false),
+               Event::KeyDown { scancode: Some(scancode), .. }
+                   if self.input.input_map.is_bound("quit", Binding::Key(scancode)) => return Ok(false),
                Event::Window { win_event: WindowEvent::Resized(width, height), .. } =>
                    self.render.resize(width, height)?,
-               _ => ()
+               _ => if let Some(controller_event) = self.input.controllers.handle_event(&event) {
+                   println!("{}", controller_event);
+               },
            }
         }
         Ok(true)
     }
 
-    fn update(&mut self) {
+    // Return false once playback runs out of recorded steps.
+    fn update(&mut self) -> StatusOr<bool> {
         let dt = self.clock.restart();
-        self.keyboard.update(&self.events);
-        self.render.update(&self.keyboard, dt);
+        self.render.record_frame(dt);
+        let num_steps = self.fixed_timestep.accumulate(dt);
+        let live_step_dt = self.fixed_timestep.step_delta_time();
+        for _ in 0..num_steps {
+            let step_dt = match self.replay {
+                Replay::Playback(ref mut playback) => match playback.next(&mut self.input) {
+                    Some(step_dt) => step_dt,
+                    None => return Ok(false),
+                },
+                _ => {
+                    // Sampled per step so just_pressed/just_released fire on exactly one step, and
+                    // aren't lost on frames that run no steps at all.
+                    self.input.keyboard.update(&self.events);
+                    self.input.controllers.update();
+                    live_step_dt
+                },
+            };
+            if let Replay::Record(ref mut recorder) = self.replay {
+                recorder.record(&self.input, step_dt)?;
+            }
+            self.render.update(&self.input, step_dt);
+        }
+        Ok(true)
     }
 
     fn draw(&mut self) {
-        self.render.draw(&mut self.canvas);
+        let alpha = self.fixed_timestep.interpolation_alpha();
+        self.render.draw(&mut self.canvas, alpha);
     }
 }
@@ -0,0 +1,53 @@
+use dimensions::time::{
+    DeltaTime,
+    Microseconds
+};
+
+pub const DEFAULT_STEPS_PER_SECOND: i64 = 120;
+pub const DEFAULT_MAX_STEPS_PER_FRAME: usize = 8;
+
+// Converts variable frame times into a whole number of equally sized simulation steps.
+// Time that doesn't fill a complete step carries over to the next frame, and the fraction
+// of a step it represents is used to interpolate rendering between the last two steps.
+pub struct FixedTimestep {
+    step_duration: Microseconds,
+    max_steps_per_frame: usize,
+    microseconds_accumulated: Microseconds,
+}
+
+impl FixedTimestep {
+    pub fn new(steps_per_second: i64, max_steps_per_frame: usize) -> FixedTimestep {
+        FixedTimestep {
+            step_duration: 1000000 / steps_per_second,
+            max_steps_per_frame,
+            microseconds_accumulated: 0,
+        }
+    }
+
+    pub fn step_delta_time(&self) -> DeltaTime {
+        DeltaTime::from_microseconds(self.step_duration)
+    }
+
+    // Returns how many simulation steps should run to catch up with dt.
+    pub fn accumulate(&mut self, dt: DeltaTime) -> usize {
+        self.microseconds_accumulated += dt.as_microseconds();
+        let num_steps = (self.microseconds_accumulated / self.step_duration) as usize;
+        if num_steps > self.max_steps_per_frame {
+            // Too far behind to ever catch up (spiral of death). Drop the backlog and let
+            // the simulation run slower than real time instead.
+            self.microseconds_accumulated %= self.step_duration;
+            return self.max_steps_per_frame;
+        }
+        self.microseconds_accumulated -= num_steps as Microseconds * self.step_duration;
+        num_steps
+    }
+
+    // How far the leftover time is into the next step, in [0, 1).
+    pub fn interpolation_alpha(&self) -> f32 {
+        self.microseconds_accumulated as f32 / self.step_duration as f32
+    }
+
+    pub fn clear(&mut self) {
+        self.microseconds_accumulated = 0;
+    }
+}
@@ -0,0 +1,112 @@
+use dimensions::time::{
+    DeltaTime,
+    Microseconds,
+};
+use std::{
+    collections::VecDeque,
+    fmt,
+};
+
+// Two seconds of frames at 120 Hz.
+pub const DEFAULT_WINDOW_SIZE: usize = 240;
+
+fn to_millis(microseconds: Microseconds) -> f32 {
+    microseconds as f32 / 1000.0
+}
+
+// Summarizes the frames currently in a FrameStats window. Times are in milliseconds.
+#[derive(Clone, Copy, Debug)]
+pub struct FrameStatsSummary {
+    pub fps: f32,
+    pub min_frame_ms: f32,
+    pub avg_frame_ms: f32,
+    pub max_frame_ms: f32,
+    pub p99_frame_ms: f32,
+    pub avg_physics_step_ms: f32,
+    pub max_physics_step_ms: f32,
+}
+
+impl fmt::Display for FrameStatsSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:.1} fps, frame ms min {:.2} / avg {:.2} / max {:.2} / p99 {:.2}, physics ms avg {:.2} / max {:.2}",
+               self.fps, self.min_frame_ms, self.avg_frame_ms, self.max_frame_ms, self.p99_frame_ms,
+               self.avg_physics_step_ms, self.max_physics_step_ms)
+    }
+}
+
+// Keeps the last window_size frame times and physics step times.
+pub struct FrameStats {
+    window_size: usize,
+    frame_times: VecDeque<Microseconds>,
+    physics_step_times: VecDeque<Microseconds>,
+}
+
+impl FrameStats {
+    pub fn new(window_size: usize) -> FrameStats {
+        FrameStats {
+            window_size,
+            frame_times: VecDeque::with_capacity(window_size),
+            physics_step_times: VecDeque::with_capacity(window_size),
+        }
+    }
+
+    fn push(window: &mut VecDeque<Microseconds>, window_size: usize, dt: DeltaTime) {
+        if window.len() == window_size {
+            window.pop_front();
+        }
+        window.push_back(dt.as_microseconds());
+    }
+
+    pub fn record_frame(&mut self, dt: DeltaTime) {
+        Self::push(&mut self.frame_times, self.window_size, dt);
+    }
+
+    // dt is wall time spent simulating one step, not the step's DeltaTime.
+    pub fn record_physics_step(&mut self, dt: DeltaTime) {
+        Self::push(&mut self.physics_step_times, self.window_size, dt);
+    }
+
+    pub fn window_size(&self) -> usize {
+        self.window_size
+    }
+
+    // Oldest first.
+    pub fn frame_times(&self) -> &VecDeque<Microseconds> {
+        &self.frame_times
+    }
+
+    // None until a frame has been recorded.
+    pub fn summary(&self) -> Option<FrameStatsSummary> {
+        if self.frame_times.is_empty() {
+            return None;
+        }
+
+        let mut sorted: Vec<Microseconds> = self.frame_times.iter().cloned().collect();
+        sorted.sort();
+        let total: Microseconds = sorted.iter().sum();
+        let p99_index = ((sorted.len() as f32 * 0.99).ceil() as usize).max(1) - 1;
+
+        let physics_total: Microseconds = self.physics_step_times.iter().sum();
+        let avg_physics_step_ms = if self.physics_step_times.is_empty() {
+            0.0
+        } else {
+            to_millis(physics_total) / self.physics_step_times.len() as f32
+        };
+        let max_physics_step_ms = to_millis(self.physics_step_times.iter().cloned().max().unwrap_or(0));
+
+        Some(FrameStatsSummary {
+            fps: if total > 0 { sorted.len() as f32 * 1000000.0 / total as f32 } else { 0.0 },
+            min_frame_ms: to_millis(sorted[0]),
+            avg_frame_ms: to_millis(total) / sorted.len() as f32,
+            max_frame_ms: to_millis(sorted[sorted.len() - 1]),
+            p99_frame_ms: to_millis(sorted[p99_index]),
+            avg_physics_step_ms,
+            max_physics_step_ms,
+        })
+    }
+
+    pub fn clear(&mut self) {
+        self.frame_times.clear();
+        self.physics_step_times.clear();
+    }
+}
@@ -1,9 +1,15 @@
 pub mod app_runner;
+pub mod fixed_timestep;
+pub mod frame_stats;
 pub mod opengl;
 pub mod status;
 pub mod ticker;
 pub mod time;
 
 pub use self::app_runner::AppRunner;
+pub use self::fixed_timestep::FixedTimestep;
+pub use self::frame_stats::FrameStats;
+pub use self::frame_stats::FrameStatsSummary;
+pub use self::status::Error;
 pub use self::status::StatusOr;
 pub use self::ticker::Ticker;
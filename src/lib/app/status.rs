@@ -1,4 +1,94 @@
-use std;
+use lodepng;
+use serde_json;
+use std::{
+    error,
+    fmt,
+    io,
+    path::PathBuf,
+    self,
+};
 
-pub type StatusOr<T> = std::result::Result<T, String>;
+#[derive(Debug)]
+pub enum Error {
+    // Reading or writing a file failed.
+    Io { path: PathBuf, source: io::Error },
+    // A resource wasn't found under any of the searched roots.
+    ResourceNotFound { resource: PathBuf, searched: Vec<PathBuf> },
+    // A config file isn't valid JSON, or doesn't match the expected layout.
+    ConfigParse { path: PathBuf, source: serde_json::Error },
+    // A text asset (e.g. an OBJ file) has a malformed line.
+    Parse { path: PathBuf, line: usize, reason: String },
+    PngDecode { path: PathBuf, source: lodepng::Error },
+    PngEncode { path: PathBuf, source: lodepng::Error },
+    // An image saved by another editor (e.g. a .piskel file) is malformed or unsupported.
+    ImageImport { path: PathBuf, reason: String },
+    // A font file couldn't be parsed, or lacks a glyph we need.
+    FontLoad { path: PathBuf, reason: String },
+    ShaderCompile { path: PathBuf, info_log: String },
+    ShaderLink { paths: Vec<PathBuf>, info_log: String },
+    // Holds the glCheckFramebufferStatus result.
+    FramebufferIncomplete { status: u32 },
+    SdlInit(String),
+    // A caller passed a value outside what the callee supports.
+    InvalidArgument(String),
+}
 
+pub type StatusOr<T> = std::result::Result<T, Error>;
+
+impl Error {
+    pub fn io(path: PathBuf, source: io::Error) -> Error {
+        Error::Io { path, source }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Io { ref path, ref source } =>
+                write!(f, "IO error on {}: {}", path.display(), source),
+            Error::ResourceNotFound { ref resource, ref searched } => {
+                write!(f, "Couldn't find resource {}. Searched:", resource.display())?;
+                for path in searched.iter() {
+                    write!(f, "\n  {}", path.display())?;
+                }
+                Ok(())
+            },
+            Error::ConfigParse { ref path, ref source } =>
+                write!(f, "Couldn't parse config {}: {}", path.display(), source),
+            Error::Parse { ref path, line, ref reason } =>
+                write!(f, "[Bad line in {}:{}] {}", path.display(), line, reason),
+            Error::PngDecode { ref path, ref source } =>
+                write!(f, "Failed to decode PNG {}: {}", path.display(), source),
+            Error::PngEncode { ref path, ref source } =>
+                write!(f, "Failed to encode PNG {}: {}", path.display(), source),
+            Error::ImageImport { ref path, ref reason } =>
+                write!(f, "Failed to import image {}: {}", path.display(), reason),
+            Error::FontLoad { ref path, ref reason } =>
+                write!(f, "Failed to load font {}: {}", path.display(), reason),
+            Error::ShaderCompile { ref path, ref info_log } =>
+                write!(f, "Shader {} failed to compile:\n{}", path.display(), info_log),
+            Error::ShaderLink { ref paths, ref info_log } => {
+                let paths: Vec<String> = paths.iter().map(|path| path.display().to_string()).collect();
+                write!(f, "Shader program ({}) failed to link:\n{}", paths.join(", "), info_log)
+            },
+            Error::FramebufferIncomplete { status } =>
+                write!(f, "Framebuffer not complete! Status: {:#x}", status),
+            Error::SdlInit(ref reason) =>
+                write!(f, "SDL initialization failed: {}", reason),
+            Error::InvalidArgument(ref reason) =>
+                write!(f, "Invalid argument: {}", reason),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            Error::Io { ref source, .. } => Some(source),
+            Error::ConfigParse { ref source, .. } => Some(source),
+            Error::PngDecode { ref source, .. } => Some(source),
+            Error::PngEncode { ref source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,87 @@
+use app::StatusOr;
+use std::{
+    collections::HashMap,
+    rc::{
+        Rc,
+        Weak,
+    },
+};
+
+// Cheap, clonable reference to a loaded asset.
+pub type Handle<T> = Rc<T>;
+
+// Hands out shared handles to assets by key. Only weak references are kept, so an asset is
+// freed as soon as its last handle drops, and loaded again if it is asked for later.
+pub struct AssetCache<T> {
+    assets: HashMap<String, Weak<T>>,
+}
+
+impl <T> AssetCache<T> {
+    pub fn new() -> AssetCache<T> {
+        AssetCache {
+            assets: HashMap::new(),
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<Handle<T>> {
+        self.assets.get(key).and_then(|asset| asset.upgrade())
+    }
+
+    pub fn get_or_load<F>(&mut self, key: &str, load: F) -> StatusOr<Handle<T>>
+        where F: FnOnce() -> StatusOr<T> {
+        if let Some(asset) = self.get(key) {
+            return Ok(asset);
+        }
+        let asset = Rc::new(load()?);
+        self.assets.retain(|_, cached| cached.upgrade().is_some());
+        self.assets.insert(key.to_string(), Rc::downgrade(&asset));
+        Ok(asset)
+    }
+
+    // Number of assets that still have live handles.
+    pub fn num_loaded(&self) -> usize {
+        self.assets.values().filter(|asset| asset.upgrade().is_some()).count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use app::Error;
+
+    #[test]
+    fn same_key_shares_one_asset() {
+        let mut cache = AssetCache::new();
+        let mut loads = 0;
+        let first = cache.get_or_load("a", || { loads += 1; Ok(1) }).unwrap();
+        let second = cache.get_or_load("a", || { loads += 1; Ok(2) }).unwrap();
+        assert!(Rc::ptr_eq(&first, &second));
+        assert_eq!((*second, loads), (1, 1));
+
+        let other = cache.get_or_load("b", || Ok(3)).unwrap();
+        assert!(!Rc::ptr_eq(&first, &other));
+        assert_eq!(cache.num_loaded(), 2);
+    }
+
+    #[test]
+    fn reloads_after_the_last_handle_drops() {
+        let mut cache = AssetCache::new();
+        let first = cache.get_or_load("a", || Ok(1)).unwrap();
+        let copy = first.clone();
+        drop(first);
+        assert_eq!(cache.get("a").map(|asset| *asset), Some(1));
+        drop(copy);
+        assert!(cache.get("a").is_none());
+        assert_eq!(cache.num_loaded(), 0);
+
+        let reloaded = cache.get_or_load("a", || Ok(2)).unwrap();
+        assert_eq!(*reloaded, 2);
+    }
+
+    #[test]
+    fn failed_loads_cache_nothing() {
+        let mut cache: AssetCache<i32> = AssetCache::new();
+        assert!(cache.get_or_load("a", || Err(Error::InvalidArgument("nope".to_string()))).is_err());
+        assert_eq!(*cache.get_or_load("a", || Ok(1)).unwrap(), 1);
+    }
+}
@@ -0,0 +1,153 @@
+use app::{
+    Error,
+    StatusOr,
+};
+use assets::{
+    mesh_disk_cache,
+    AssetCache,
+    Handle,
+    MeshDiskCache,
+    SpriteMeshKey,
+};
+use file;
+use font::FontAtlas;
+use geometry::Mesh;
+use image::{
+    sheet_file,
+    Png,
+    Texture,
+};
+use render::{
+    sprite_3d,
+    Sprite3DSheetInfo,
+};
+use shader::ShaderProgram;
+use std::path::Path;
+
+fn path_key(path: &Path) -> String {
+    path.display().to_string()
+}
+
+// Loads and shares GPU and image assets, so e.g. two athletes drawn from the same sprite
+// sheet compile its shaders and reify its meshes only once.
+pub struct AssetManager {
+    pngs: AssetCache<Png>,
+    textures: AssetCache<Texture>,
+    shaders: AssetCache<ShaderProgram>,
+    sprite_meshes: AssetCache<Vec<Mesh>>,
+    fonts: AssetCache<FontAtlas>,
+    // None if res/temp/mesh_cache can't be created, in which case meshes are always reified.
+    mesh_disk_cache: Option<MeshDiskCache>,
+}
+
+impl AssetManager {
+    pub fn new() -> AssetManager {
+        AssetManager {
+            pngs: AssetCache::new(),
+            textures: AssetCache::new(),
+            shaders: AssetCache::new(),
+            sprite_meshes: AssetCache::new(),
+            fonts: AssetCache::new(),
+            mesh_disk_cache: MeshDiskCache::in_resources().ok(),
+        }
+    }
+
+    // Editor files such as .piskel are imported into a Png, see sheet_file::load.
+    pub fn png(&mut self, path: &Path) -> StatusOr<Handle<Png>> {
+        self.pngs.get_or_load(path_key(path).as_str(), || sheet_file::load(path))
+    }
+
+    pub fn texture(&mut self, path: &Path) -> StatusOr<Handle<Texture>> {
+        let key = path_key(path);
+        if let Some(texture) = self.textures.get(key.as_str()) {
+            return Ok(texture);
+        }
+        let png = self.png(path)?;
+        self.textures.get_or_load(key.as_str(), || Ok(Texture::new(&png)))
+    }
+
+    // Shader file names are looked up in res/shaders. Without a geometry shader, builds a
+    // vertex -> fragment pipeline.
+    pub fn shader(&mut self,
+                  vertex_name: &str,
+                  geometry_name: Option<&str>,
+                  fragment_name: &str) -> StatusOr<Handle<ShaderProgram>> {
+        let key = format!("{}|{}|{}", vertex_name, geometry_name.unwrap_or(""), fragment_name);
+        self.shaders.get_or_load(key.as_str(), || {
+            let vert_path = file::util::resource_path("shaders", vertex_name)?;
+            let frag_path = file::util::resource_path("shaders", fragment_name)?;
+            match geometry_name {
+                Some(geometry_name) => {
+                    let geo_path = file::util::resource_path("shaders", geometry_name)?;
+                    ShaderProgram::from_long_pipeline(&vert_path, &geo_path, &frag_path)
+                },
+                None => ShaderProgram::from_short_pipeline(&vert_path, &frag_path),
+            }
+        })
+    }
+
+    // One GPU-ready mesh per frame of the sprite sheet described by info. Reified meshes are
+    // kept in the mesh disk cache, keyed by the sheet's and depth map's file contents.
+    pub fn sprite_meshes(&mut self, info: &Sprite3DSheetInfo) -> StatusOr<Handle<Vec<Mesh>>> {
+        let frame_rects: Vec<String> = info.frames.iter()
+            .map(|frame| format!("{}x{}+{}+{}", frame.width, frame.height, frame.x, frame.y))
+            .collect();
+        let mut key = format!("{}@{}:{:?}", path_key(info.filepath), frame_rects.join(","), info.backend);
+        if let Some(ref depth_map) = info.depth_map {
+            key = format!("{}|{}@{}", key, path_key(depth_map.filepath), depth_map.max_depth);
+        }
+        if let Some(meshes) = self.sprite_meshes.get(key.as_str()) {
+            return Ok(meshes);
+        }
+        let png = self.png(info.filepath)?;
+        if let Some(frame) = info.frames.iter().find(|frame| !frame.fits_in(png.size())) {
+            return Err(Error::InvalidArgument(format!("Frame {:?} does not fit in {}.", frame, path_key(info.filepath))));
+        }
+        let depth_png = match info.depth_map {
+            Some(ref depth_map) => {
+                let depth_png = self.png(depth_map.filepath)?;
+                if depth_png.size() != png.size() {
+                    return Err(Error::InvalidArgument(format!("Depth map {} is not the size of {}.",
+                                                              path_key(depth_map.filepath), path_key(info.filepath))));
+                }
+                Some(depth_png)
+            },
+            None => None,
+        };
+        let max_depth = info.depth_map.as_ref().map(|depth_map| depth_map.max_depth).unwrap_or(0.0);
+        let mesh_disk_cache = self.mesh_disk_cache.as_ref();
+        self.sprite_meshes.get_or_load(key.as_str(), || {
+            let sheet_bytes = mesh_disk_cache::read_bytes(info.filepath)?;
+            let depth_map_bytes = match info.depth_map {
+                Some(ref depth_map) => Some(mesh_disk_cache::read_bytes(depth_map.filepath)?),
+                None => None,
+            };
+            let disk_key = SpriteMeshKey::new(&sheet_bytes, depth_map_bytes.as_ref().map(|bytes| bytes.as_slice()),
+                                              info.frames, max_depth, info.backend);
+            let mut meshes = match mesh_disk_cache.and_then(|cache| cache.load(disk_key)) {
+                Some(meshes) => meshes,
+                None => {
+                    let meshes = sprite_3d::reify_sheet(&png, info.frames, depth_png.as_ref().map(|png| &**png),
+                                                       max_depth, info.backend);
+                    if let Some(cache) = mesh_disk_cache {
+                        // A failed write only costs reifying again next run.
+                        if let Err(err) = cache.store(disk_key, &meshes) {
+                            println!("Could not cache meshes for {}: {}", path_key(info.filepath), err);
+                        }
+                    }
+                    meshes
+                },
+            };
+            for mesh in meshes.iter_mut() {
+                mesh.gl_init();
+            }
+            Ok(meshes)
+        })
+    }
+
+    // The font res/fonts/<font_name>, rasterized at pixel_height.
+    pub fn font(&mut self, font_name: &str, pixel_height: u32) -> StatusOr<Handle<FontAtlas>> {
+        let key = format!("{}@{}", font_name, pixel_height);
+        self.fonts.get_or_load(key.as_str(), || FontAtlas::from_resource(font_name, pixel_height as f32))
+    }
+}
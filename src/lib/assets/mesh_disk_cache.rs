@@ -0,0 +1,325 @@
+use app::{
+    Error,
+    StatusOr,
+};
+use file::{
+    self,
+    bytes::ByteReader,
+};
+use geometry::{
+    mesh::VertexIndex,
+    Mesh,
+    VertexLayout,
+};
+use glm;
+use render::{
+    ReifyBackend,
+    SpriteFrame,
+    REIFY_VERSION,
+};
+use std::{
+    fs,
+    hash::Hasher,
+    io::Write,
+    path::{
+        Path,
+        PathBuf,
+    },
+};
+
+const MAGIC: &[u8; 4] = b"S3DM";
+// Bump whenever this format changes. Keys cover changes to reification, see REIFY_VERSION.
+const FORMAT_VERSION: u32 = 2;
+
+// 64 bit FNV-1a. Unlike std's DefaultHasher, its output is fixed across Rust releases, so
+// cache keys stay valid between builds.
+struct FnvHasher(u64);
+
+impl FnvHasher {
+    fn new() -> FnvHasher {
+        FnvHasher(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher for FnvHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes.iter() {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+}
+
+// Identifies the meshes reified from a sprite sheet with particular settings.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct SpriteMeshKey(u64);
+
+impl SpriteMeshKey {
+    // sheet and depth_map are the raw bytes of the PNG files, so any edit to either misses.
+    pub fn new(sheet: &[u8], depth_map: Option<&[u8]>, frames: &[SpriteFrame], max_depth: f32,
+               backend: ReifyBackend) -> SpriteMeshKey {
+        let mut hasher = FnvHasher::new();
+        hasher.write_u32(FORMAT_VERSION);
+        hasher.write_u32(REIFY_VERSION);
+        hasher.write_u64(sheet.len() as u64);
+        hasher.write(sheet);
+        match depth_map {
+            Some(depth_map) => {
+                hasher.write_u8(1);
+                hasher.write_u64(depth_map.len() as u64);
+                hasher.write(depth_map);
+                hasher.write_u32(max_depth.to_bits());
+            },
+            None => hasher.write_u8(0),
+        }
+        hasher.write_u64(frames.len() as u64);
+        for frame in frames.iter() {
+            for value in [frame.x, frame.y, frame.width, frame.height].iter() {
+                hasher.write_u64(*value as u64);
+            }
+        }
+        hasher.write_u8(match backend {
+            ReifyBackend::Edges => 0,
+            ReifyBackend::Greedy => 1,
+        });
+        SpriteMeshKey(hasher.finish())
+    }
+}
+
+// Reified sprite meshes saved as <dir>/<key>.meshes, so later runs skip reification.
+//
+// Layout, little endian: MAGIC, FORMAT_VERSION: u32, key: u64, mesh count: u32, then per
+// mesh a vertex count: u32, an attribute flags byte (see VertexLayout), the positions and each
+// present attribute as f32s, an index count: u32 and the u32 indices.
+pub struct MeshDiskCache {
+    dir: PathBuf,
+}
+
+impl MeshDiskCache {
+    // Creates dir if it doesn't exist.
+    pub fn new(dir: PathBuf) -> StatusOr<MeshDiskCache> {
+        fs::create_dir_all(&dir).map_err(|err| Error::io(dir.clone(), err))?;
+        Ok(MeshDiskCache { dir })
+    }
+
+    // res/temp/mesh_cache
+    pub fn in_resources() -> StatusOr<MeshDiskCache> {
+        MeshDiskCache::new(file::util::resource_dir("temp")?.join("mesh_cache"))
+    }
+
+    fn path(&self, key: SpriteMeshKey) -> PathBuf {
+        self.dir.join(format!("{:016x}.meshes", key.0))
+    }
+
+    // None if there is no usable entry for key. Unreadable or truncated files count as misses.
+    pub fn load(&self, key: SpriteMeshKey) -> Option<Vec<Mesh>> {
+        let bytes = fs::read(self.path(key)).ok()?;
+        let mut reader = ByteReader::new(&bytes);
+        if reader.take(4)? != MAGIC || reader.u32()? != FORMAT_VERSION || reader.u64()? != key.0 {
+            return None;
+        }
+        let num_meshes = reader.u32()?;
+        let mut meshes = Vec::with_capacity(num_meshes as usize);
+        for _ in 0..num_meshes {
+            let num_vertices = reader.u32()? as usize;
+            let layout = layout_from_flags(reader.u8()?);
+            let mut vertices = Vec::with_capacity(num_vertices);
+            for _ in 0..num_vertices {
+                vertices.push(glm::vec3(reader.f32()?, reader.f32()?, reader.f32()?));
+            }
+            let mut normals = Vec::new();
+            if layout.normals {
+                for _ in 0..num_vertices {
+                    normals.push(glm::vec3(reader.f32()?, reader.f32()?, reader.f32()?));
+                }
+            }
+            let mut uvs = Vec::new();
+            if layout.uvs {
+                for _ in 0..num_vertices {
+                    uvs.push(glm::vec2(reader.f32()?, reader.f32()?));
+                }
+            }
+            let mut colors = Vec::new();
+            if layout.colors {
+                for _ in 0..num_vertices {
+                    colors.push(glm::vec4(reader.f32()?, reader.f32()?, reader.f32()?, reader.f32()?));
+                }
+            }
+            let mut tangents = Vec::new();
+            if layout.tangents {
+                for _ in 0..num_vertices {
+                    tangents.push(glm::vec4(reader.f32()?, reader.f32()?, reader.f32()?, reader.f32()?));
+                }
+            }
+            let num_indices = reader.u32()? as usize;
+            let mut faces: Vec<VertexIndex> = Vec::with_capacity(num_indices);
+            for _ in 0..num_indices {
+                let index = reader.u32()?;
+                if index as usize >= num_vertices {
+                    return None;
+                }
+                faces.push(index);
+            }
+            let mut mesh = Mesh::from_geometry(vertices, faces);
+            mesh.normals = normals;
+            mesh.uvs = uvs;
+            mesh.colors = colors;
+            mesh.tangents = tangents;
+            meshes.push(mesh);
+        }
+        Some(meshes)
+    }
+
+    // Writes to a temporary file first, so a crash mid-write never leaves a corrupt entry.
+    pub fn store(&self, key: SpriteMeshKey, meshes: &[Mesh]) -> StatusOr<()> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(MAGIC);
+        push_u32(&mut bytes, FORMAT_VERSION);
+        bytes.extend_from_slice(&key.0.to_le_bytes());
+        push_u32(&mut bytes, meshes.len() as u32);
+        for mesh in meshes.iter() {
+            let layout = mesh.layout();
+            push_u32(&mut bytes, mesh.vertices.len() as u32);
+            bytes.push(layout_flags(layout));
+            for vertex in mesh.vertices.iter() {
+                push_f32s(&mut bytes, &[vertex.x, vertex.y, vertex.z]);
+            }
+            if layout.normals {
+                for normal in mesh.normals.iter() {
+                    push_f32s(&mut bytes, &[normal.x, normal.y, normal.z]);
+                }
+            }
+            if layout.uvs {
+                for uv in mesh.uvs.iter() {
+                    push_f32s(&mut bytes, &[uv.x, uv.y]);
+                }
+            }
+            if layout.colors {
+                for color in mesh.colors.iter() {
+                    push_f32s(&mut bytes, &[color.x, color.y, color.z, color.w]);
+                }
+            }
+            if layout.tangents {
+                for tangent in mesh.tangents.iter() {
+                    push_f32s(&mut bytes, &[tangent.x, tangent.y, tangent.z, tangent.w]);
+                }
+            }
+            push_u32(&mut bytes, mesh.faces.len() as u32);
+            for index in mesh.faces.iter() {
+                push_u32(&mut bytes, *index);
+            }
+        }
+
+        let path = self.path(key);
+        let temp_path = path.with_extension("meshes.tmp");
+        let io_error = |err| Error::io(temp_path.clone(), err);
+        let mut writer = file::util::buffered_writer_for(&temp_path).map_err(io_error)?;
+        writer.write_all(&bytes).map_err(io_error)?;
+        writer.flush().map_err(io_error)?;
+        drop(writer);
+        fs::rename(&temp_path, &path).map_err(|err| Error::io(path.clone(), err))
+    }
+}
+
+// The bytes of a file, for hashing into a SpriteMeshKey.
+pub fn read_bytes(path: &Path) -> StatusOr<Vec<u8>> {
+    fs::read(path).map_err(|err| Error::io(path.to_path_buf(), err))
+}
+
+fn push_u32(bytes: &mut Vec<u8>, value: u32) {
+    bytes.extend_from_slice(&value.to_le_bytes());
+}
+
+fn push_f32s(bytes: &mut Vec<u8>, values: &[f32]) {
+    for value in values.iter() {
+        push_u32(bytes, value.to_bits());
+    }
+}
+
+fn layout_flags(layout: VertexLayout) -> u8 {
+    (layout.normals as u8) | (layout.uvs as u8) << 1 | (layout.colors as u8) << 2 | (layout.tangents as u8) << 3
+}
+
+fn layout_from_flags(flags: u8) -> VertexLayout {
+    VertexLayout {
+        normals: flags & 1 != 0,
+        uvs: flags & 2 != 0,
+        colors: flags & 4 != 0,
+        tangents: flags & 8 != 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        env,
+        process,
+    };
+
+    fn cache(name: &str) -> MeshDiskCache {
+        MeshDiskCache::new(env::temp_dir().join(format!("mesh_disk_cache_{}_{}", process::id(), name))).unwrap()
+    }
+
+    fn key() -> SpriteMeshKey {
+        SpriteMeshKey::new(b"sheet", Some(b"depth"), &[], 2.0, ReifyBackend::Edges)
+    }
+
+    fn quad() -> Mesh {
+        let mut mesh = Mesh::from_geometry(
+            vec![glm::vec3(0.0, 0.0, 0.5), glm::vec3(1.0, 0.0, 0.5), glm::vec3(1.0, 1.0, -0.5), glm::vec3(0.0, 1.0, -0.5)],
+            vec![0, 1, 2, 0, 2, 3]);
+        mesh.normals = vec![glm::vec3(0.0, 0.0, 1.0); 4];
+        mesh.uvs = vec![glm::vec2(0.0, 1.0), glm::vec2(1.0, 1.0), glm::vec2(1.0, 0.0), glm::vec2(0.0, 0.0)];
+        mesh
+    }
+
+    #[test]
+    fn loads_what_it_stored() {
+        let cache = cache("round_trip");
+        let meshes = vec![quad(), Mesh::from_geometry(vec![glm::vec3(2.0, 3.0, 4.0)], Vec::new())];
+        cache.store(key(), &meshes).unwrap();
+        let loaded = cache.load(key()).unwrap();
+        fs::remove_dir_all(&cache.dir).unwrap();
+
+        assert_eq!(loaded.len(), meshes.len());
+        for (loaded, mesh) in loaded.iter().zip(meshes.iter()) {
+            assert_eq!(loaded.vertices, mesh.vertices);
+            assert_eq!(loaded.normals, mesh.normals);
+            assert_eq!(loaded.uvs, mesh.uvs);
+            assert_eq!(loaded.faces, mesh.faces);
+        }
+    }
+
+    #[test]
+    fn truncated_files_miss() {
+        let cache = cache("truncated");
+        cache.store(key(), &[quad()]).unwrap();
+        let path = cache.path(key());
+        let bytes = fs::read(&path).unwrap();
+        fs::write(&path, &bytes[..bytes.len() - 1]).unwrap();
+        let loaded = cache.load(key());
+        fs::remove_dir_all(&cache.dir).unwrap();
+        assert!(loaded.is_none());
+    }
+
+    #[test]
+    fn keys_depend_on_every_input() {
+        let frame = SpriteFrame { name: None, x: 0, y: 0, width: 4, height: 4 };
+        let keys = [
+            key(),
+            SpriteMeshKey::new(b"sheeT", Some(b"depth"), &[], 2.0, ReifyBackend::Edges),
+            SpriteMeshKey::new(b"sheet", None, &[], 2.0, ReifyBackend::Edges),
+            SpriteMeshKey::new(b"sheet", Some(b"depth"), &[], 3.0, ReifyBackend::Edges),
+            SpriteMeshKey::new(b"sheet", Some(b"depth"), &[frame], 2.0, ReifyBackend::Edges),
+            SpriteMeshKey::new(b"sheet", Some(b"depth"), &[], 2.0, ReifyBackend::Greedy),
+        ];
+        for (i, key) in keys.iter().enumerate() {
+            assert!(keys[i + 1..].iter().all(|other| other != key), "key {}", i);
+        }
+    }
+}
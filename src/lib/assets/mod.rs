@@ -0,0 +1,9 @@
+pub mod asset_cache;
+pub mod asset_manager;
+pub mod mesh_disk_cache;
+
+pub use self::asset_cache::AssetCache;
+pub use self::asset_cache::Handle;
+pub use self::asset_manager::AssetManager;
+pub use self::mesh_disk_cache::MeshDiskCache;
+pub use self::mesh_disk_cache::SpriteMeshKey;
@@ -38,6 +38,19 @@ impl Rgba {
         f255.round() as u8
     }
 
+    // Draws self over below with self's alpha scaled by opacity, the usual "source over"
+    // blend for colors that aren't premultiplied.
+    pub fn blend_over(&self, below: &Rgba, opacity: f32) -> Rgba {
+        let above_alpha = self.a * opacity;
+        let alpha = above_alpha + below.a * (1.0 - above_alpha);
+        if alpha <= 0.0 {
+            return Rgba::new(0.0, 0.0, 0.0, 0.0);
+        }
+        let mix = |above: f32, below_channel: f32|
+            (above * above_alpha + below_channel * below.a * (1.0 - above_alpha)) / alpha;
+        Rgba::new(mix(self.r, below.r), mix(self.g, below.g), mix(self.b, below.b), alpha)
+    }
+
     pub fn as_byte_vec(&self) -> Vec<u8> {
        vec!(Rgba::f32_to_u8(self.r),
             Rgba::f32_to_u8(self.g),
@@ -0,0 +1,260 @@
+use sdl2::{
+    controller::{
+        Axis,
+        Button,
+        GameController,
+    },
+    event::Event,
+    GameControllerSubsystem,
+};
+use std::{
+    collections::{
+        HashMap,
+        HashSet,
+    },
+    fmt,
+};
+
+const ALL_BUTTONS: [Button; 15] = [
+    Button::A, Button::B, Button::X, Button::Y,
+    Button::Back, Button::Guide, Button::Start,
+    Button::LeftStick, Button::RightStick,
+    Button::LeftShoulder, Button::RightShoulder,
+    Button::DPadUp, Button::DPadDown, Button::DPadLeft, Button::DPadRight,
+];
+
+// Fractions of full deflection below which analog input reads as zero.
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub struct DeadZones {
+    pub stick: f32,
+    pub trigger: f32,
+}
+
+impl Default for DeadZones {
+    fn default() -> DeadZones {
+        DeadZones {
+            stick: 0.2,
+            trigger: 0.1,
+        }
+    }
+}
+
+// Maps |value| in [dead_zone, 1] onto [0, 1] so input ramps up smoothly past the dead zone.
+fn rescale(value: f32, dead_zone: f32) -> f32 {
+    if value <= dead_zone || dead_zone >= 1.0 {
+        0.0
+    } else {
+        ((value - dead_zone) / (1.0 - dead_zone)).min(1.0)
+    }
+}
+
+fn normalize(raw: i16) -> f32 {
+    (raw as f32 / 32767.0).max(-1.0)
+}
+
+fn merge_axis(axes: &mut HashMap<Axis, f32>, axis: Axis, value: f32) {
+    let merged = axes.entry(axis).or_insert(0.0);
+    if value.abs() > merged.abs() {
+        *merged = value;
+    }
+}
+
+// A controller coming or going, for the caller to report.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ControllerEvent {
+    // The controller's name.
+    Connected(String),
+    Disconnected(String),
+    OpenFailed { joystick_index: u32, reason: String },
+}
+
+impl fmt::Display for ControllerEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ControllerEvent::Connected(ref name) => write!(f, "Connected controller: {}", name),
+            ControllerEvent::Disconnected(ref name) => write!(f, "Disconnected controller: {}", name),
+            ControllerEvent::OpenFailed { joystick_index, ref reason } =>
+                write!(f, "Could not open controller {}: {}", joystick_index, reason),
+        }
+    }
+}
+
+// Tracks every connected SDL game controller. Since there's one local player, buttons and
+// axes are merged across controllers: a button is pressed if any controller presses it,
+// and each axis reads whichever controller deflects it furthest.
+pub struct ControllerControls {
+    subsystem: Option<GameControllerSubsystem>,
+    controllers: Vec<GameController>,
+    dead_zones: DeadZones,
+    currently_pressed: HashSet<Button>,
+    just_pressed: HashSet<Button>,
+    just_released: HashSet<Button>,
+    axes: HashMap<Axis, f32>,
+    previous_axes: HashMap<Axis, f32>,
+}
+
+impl ControllerControls {
+    pub fn new(dead_zones: DeadZones) -> ControllerControls {
+        ControllerControls {
+            subsystem: None,
+            controllers: Vec::new(),
+            dead_zones,
+            currently_pressed: HashSet::new(),
+            just_pressed: HashSet::new(),
+            just_released: HashSet::new(),
+            axes: HashMap::new(),
+            previous_axes: HashMap::new(),
+        }
+    }
+
+    // Opens the controllers already plugged in, returning what happened to each. Without a
+    // subsystem attached, every button reads as released and every axis as centered.
+    pub fn attach(&mut self, subsystem: GameControllerSubsystem) -> Vec<ControllerEvent> {
+        let num_joysticks = subsystem.num_joysticks().unwrap_or(0);
+        self.subsystem = Some(subsystem);
+        (0..num_joysticks).filter_map(|joystick_index| self.connect(joystick_index)).collect()
+    }
+
+    fn connect(&mut self, joystick_index: u32) -> Option<ControllerEvent> {
+        let controller = match self.subsystem.as_ref() {
+            Some(subsystem) if subsystem.is_game_controller(joystick_index) => subsystem.open(joystick_index),
+            _ => return None,
+        };
+        match controller {
+            Ok(controller) => {
+                // SDL also sends an added event for controllers that were open at startup.
+                let instance_id = controller.instance_id();
+                if self.controllers.iter().any(|open| open.instance_id() == instance_id) {
+                    return None;
+                }
+                let name = controller.name();
+                self.controllers.push(controller);
+                Some(ControllerEvent::Connected(name))
+            },
+            Err(err) => Some(ControllerEvent::OpenFailed { joystick_index, reason: err.to_string() }),
+        }
+    }
+
+    fn disconnect(&mut self, instance_id: i32) -> Option<ControllerEvent> {
+        let index = self.controllers.iter().position(|controller| controller.instance_id() == instance_id)?;
+        Some(ControllerEvent::Disconnected(self.controllers.remove(index).name()))
+    }
+
+    // Handles hot-plugging. Returns the connect or disconnect, if event was one that changed
+    // the controllers in use.
+    pub fn handle_event(&mut self, event: &Event) -> Option<ControllerEvent> {
+        match *event {
+            Event::ControllerDeviceAdded { which, .. } => self.connect(which),
+            Event::ControllerDeviceRemoved { which, .. } => self.disconnect(which),
+            _ => None,
+        }
+    }
+
+    pub fn num_connected(&self) -> usize {
+        self.controllers.len()
+    }
+
+    pub fn dead_zones(&self) -> DeadZones {
+        self.dead_zones
+    }
+
+    pub fn set_dead_zones(&mut self, dead_zones: DeadZones) {
+        self.dead_zones = dead_zones;
+    }
+
+    pub fn update(&mut self) {
+        let pressed: Vec<_> = ALL_BUTTONS.iter()
+            .cloned()
+            .filter(|button| self.controllers.iter().any(|controller| controller.button(*button)))
+            .collect();
+
+        let mut axes = HashMap::new();
+        for controller in self.controllers.iter() {
+            for &(x, y) in [(Axis::LeftX, Axis::LeftY), (Axis::RightX, Axis::RightY)].iter() {
+                let (x_value, y_value) = self.stick(normalize(controller.axis(x)), normalize(controller.axis(y)));
+                merge_axis(&mut axes, x, x_value);
+                merge_axis(&mut axes, y, y_value);
+            }
+            for &trigger in [Axis::TriggerLeft, Axis::TriggerRight].iter() {
+                let value = rescale(normalize(controller.axis(trigger)).max(0.0), self.dead_zones.trigger);
+                merge_axis(&mut axes, trigger, value);
+            }
+        }
+
+        self.update_state(pressed, axes);
+    }
+
+    // Like update, but takes button and axis state directly instead of asking SDL for it.
+    // Axis values are expected to already have dead zones applied.
+    pub fn update_state<I: IntoIterator<Item = Button>>(&mut self, pressed: I, axes: HashMap<Axis, f32>) {
+        let currently_pressed: HashSet<_> = pressed.into_iter().collect();
+        self.just_pressed = currently_pressed.difference(&self.currently_pressed).cloned().collect();
+        self.just_released = self.currently_pressed.difference(&currently_pressed).cloned().collect();
+        self.currently_pressed = currently_pressed;
+
+        self.previous_axes = ::std::mem::replace(&mut self.axes, axes);
+    }
+
+    // Applies the stick dead zone radially, so diagonals aren't clipped into a cross.
+    fn stick(&self, x: f32, y: f32) -> (f32, f32) {
+        let magnitude = (x * x + y * y).sqrt();
+        let scaled = rescale(magnitude, self.dead_zones.stick);
+        if scaled == 0.0 {
+            (0.0, 0.0)
+        } else {
+            (x / magnitude * scaled, y / magnitude * scaled)
+        }
+    }
+
+    pub fn pressed(&self) -> &HashSet<Button> {
+        &self.currently_pressed
+    }
+
+    pub fn is_pressed(&self, button: Button) -> bool {
+        self.currently_pressed.contains(&button)
+    }
+
+    pub fn just_pressed(&self, button: Button) -> bool {
+        self.just_pressed.contains(&button)
+    }
+
+    pub fn just_released(&self, button: Button) -> bool {
+        self.just_released.contains(&button)
+    }
+
+    // In [-1, 1] for sticks (+y is down) and [0, 1] for triggers.
+    pub fn axis(&self, axis: Axis) -> f32 {
+        self.axes.get(&axis).cloned().unwrap_or(0.0)
+    }
+
+    pub fn axes(&self) -> &HashMap<Axis, f32> {
+        &self.axes
+    }
+
+    // The axis value as of the previous update.
+    pub fn previous_axis(&self, axis: Axis) -> f32 {
+        self.previous_axes.get(&axis).cloned().unwrap_or(0.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hot_plugging_without_a_subsystem_changes_nothing() {
+        let mut controls = ControllerControls::new(DeadZones::default());
+        assert_eq!(controls.handle_event(&Event::ControllerDeviceAdded { timestamp: 0, which: 0 }), None);
+        assert_eq!(controls.handle_event(&Event::ControllerDeviceRemoved { timestamp: 0, which: 0 }), None);
+        assert_eq!(controls.handle_event(&Event::Quit { timestamp: 0 }), None);
+        assert_eq!(controls.num_connected(), 0);
+    }
+
+    #[test]
+    fn events_describe_themselves_for_logging() {
+        assert_eq!(ControllerEvent::Connected("Pad".to_string()).to_string(), "Connected controller: Pad");
+        assert_eq!(ControllerEvent::Disconnected("Pad".to_string()).to_string(), "Disconnected controller: Pad");
+        let failed = ControllerEvent::OpenFailed { joystick_index: 2, reason: "busy".to_string() };
+        assert_eq!(failed.to_string(), "Could not open controller 2: busy");
+    }
+}
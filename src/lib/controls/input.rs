@@ -0,0 +1,95 @@
+use app::StatusOr;
+use controls::{
+    AxisDirection,
+    Binding,
+    ControllerControls,
+    ControlsConfig,
+    InputMap,
+    KeyboardControls,
+};
+use file;
+
+// How far an axis must be pushed, past its dead zone, to count as a press.
+const AXIS_PRESS_THRESHOLD: f32 = 0.5;
+
+fn directed(value: f32, direction: AxisDirection) -> f32 {
+    match direction {
+        AxisDirection::Positive => value.max(0.0),
+        AxisDirection::Negative => (-value).max(0.0),
+    }
+}
+
+// The player's input for one simulation step, queried by action name.
+pub struct Input {
+    pub keyboard: KeyboardControls,
+    pub controllers: ControllerControls,
+    pub input_map: InputMap,
+}
+
+impl Input {
+    // Loads bindings and dead zones from res/config/controls.config.
+    pub fn read() -> StatusOr<Input> {
+        let config: ControlsConfig = file::util::read_config("controls.config")?;
+        Ok(Input {
+            keyboard: KeyboardControls::new(),
+            controllers: ControllerControls::new(config.dead_zones),
+            input_map: InputMap::new(config.actions),
+        })
+    }
+
+    // In [0, 1]: 1 for a held key or button, and how far the axis is pushed for an axis.
+    fn binding_value(&self, binding: Binding) -> f32 {
+        match binding {
+            Binding::Key(scancode) => if self.keyboard.is_pressed(scancode) { 1.0 } else { 0.0 },
+            Binding::Button(button) => if self.controllers.is_pressed(button) { 1.0 } else { 0.0 },
+            Binding::Axis(axis, direction) => directed(self.controllers.axis(axis), direction),
+        }
+    }
+
+    fn binding_pressed(&self, binding: Binding) -> bool {
+        match binding {
+            Binding::Axis(..) => self.binding_value(binding) >= AXIS_PRESS_THRESHOLD,
+            _ => self.binding_value(binding) > 0.0,
+        }
+    }
+
+    // Whether binding was held during the previous step.
+    fn binding_was_pressed(&self, binding: Binding) -> bool {
+        match binding {
+            Binding::Key(scancode) =>
+                (self.keyboard.is_pressed(scancode) && !self.keyboard.just_pressed(scancode))
+                    || self.keyboard.just_released(scancode),
+            Binding::Button(button) =>
+                (self.controllers.is_pressed(button) && !self.controllers.just_pressed(button))
+                    || self.controllers.just_released(button),
+            Binding::Axis(axis, direction) =>
+                directed(self.controllers.previous_axis(axis), direction) >= AXIS_PRESS_THRESHOLD,
+        }
+    }
+
+    fn action_was_pressed(&self, action: &str) -> bool {
+        self.input_map.bindings(action).iter().any(|binding| self.binding_was_pressed(*binding))
+    }
+
+    pub fn is_pressed(&self, action: &str) -> bool {
+        self.input_map.bindings(action).iter().any(|binding| self.binding_pressed(*binding))
+    }
+
+    // True on the step where the first of action's bindings goes down.
+    pub fn just_pressed(&self, action: &str) -> bool {
+        self.is_pressed(action) && !self.action_was_pressed(action)
+    }
+
+    // True on the step where the last of action's bindings comes up.
+    pub fn just_released(&self, action: &str) -> bool {
+        !self.is_pressed(action) && self.action_was_pressed(action)
+    }
+
+    // How strongly action is held, in [0, 1]. Analog bindings give partial values, so a
+    // half-pushed stick moves at half speed.
+    pub fn value(&self, action: &str) -> f32 {
+        self.input_map.bindings(action).iter()
+            .map(|binding| self.binding_value(*binding))
+            .fold(0.0, f32::max)
+    }
+}
@@ -0,0 +1,113 @@
+use controls::DeadZones;
+use sdl2::{
+    controller::{
+        Axis,
+        Button,
+    },
+    keyboard::Scancode,
+};
+use serde::de::{
+    self,
+    Deserialize,
+    Deserializer,
+};
+use std::collections::HashMap;
+
+// Prefix marking controller binding names in controls.config, e.g. "Pad a" or "Pad leftx-".
+const CONTROLLER_PREFIX: &str = "Pad ";
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum AxisDirection {
+    Positive,
+    Negative,
+}
+
+// Something physical the player can press to trigger an action.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Binding {
+    Key(Scancode),
+    Button(Button),
+    // Half of an analog axis, e.g. pushing the left stick left.
+    Axis(Axis, AxisDirection),
+}
+
+impl Binding {
+    // Parses a config binding name. Keys use SDL scancode names ("G", "Left Shift"), and
+    // controller inputs use SDL mapping names after "Pad " ("Pad a", "Pad dpleft"). Axes
+    // take a trailing + or - for direction ("Pad leftx-"); triggers may omit it.
+    pub fn from_name(name: &str) -> Option<Binding> {
+        if !name.starts_with(CONTROLLER_PREFIX) {
+            return Scancode::from_name(name).map(Binding::Key);
+        }
+
+        let name = &name[CONTROLLER_PREFIX.len()..];
+        if let Some(button) = Button::from_string(name) {
+            return Some(Binding::Button(button));
+        }
+        let (axis_name, direction) = if name.ends_with('+') {
+            (&name[..name.len() - 1], AxisDirection::Positive)
+        } else if name.ends_with('-') {
+            (&name[..name.len() - 1], AxisDirection::Negative)
+        } else {
+            (name, AxisDirection::Positive)
+        };
+        Axis::from_string(axis_name).map(|axis| Binding::Axis(axis, direction))
+    }
+}
+
+impl <'de> Deserialize<'de> for Binding {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Binding, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        Binding::from_name(name.as_str())
+            .ok_or_else(|| de::Error::custom(format!("Unknown binding \"{}\"", name)))
+    }
+}
+
+// The contents of res/config/controls.config.
+#[derive(Deserialize)]
+pub struct ControlsConfig {
+    #[serde(default)]
+    pub dead_zones: DeadZones,
+    pub actions: HashMap<String, Vec<Binding>>,
+}
+
+// Maps named actions ("move_left", "sprint", "camera_pitch_up") to the bindings that
+// trigger them, so gameplay never has to know which key does what.
+pub struct InputMap {
+    bindings: HashMap<String, Vec<Binding>>,
+}
+
+impl InputMap {
+    pub fn new(bindings: HashMap<String, Vec<Binding>>) -> InputMap {
+        InputMap {
+            bindings,
+        }
+    }
+
+    pub fn bindings(&self, action: &str) -> &[Binding] {
+        self.bindings.get(action).map(|bindings| bindings.as_slice()).unwrap_or(&[])
+    }
+
+    pub fn is_bound(&self, action: &str, binding: Binding) -> bool {
+        self.bindings(action).contains(&binding)
+    }
+
+    // Adds binding to action, keeping any existing bindings.
+    pub fn bind(&mut self, action: &str, binding: Binding) {
+        let bindings = self.bindings.entry(action.to_string()).or_insert_with(Vec::new);
+        if !bindings.contains(&binding) {
+            bindings.push(binding);
+        }
+    }
+
+    pub fn unbind(&mut self, action: &str, binding: Binding) {
+        if let Some(bindings) = self.bindings.get_mut(action) {
+            bindings.retain(|bound| *bound != binding);
+        }
+    }
+
+    // Replaces all of action's bindings.
+    pub fn rebind(&mut self, action: &str, bindings: Vec<Binding>) {
+        self.bindings.insert(action.to_string(), bindings);
+    }
+}
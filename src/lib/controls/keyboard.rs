@@ -20,7 +20,12 @@ impl KeyboardControls {
     }
 
     pub fn update(&mut self, e: &EventPump) {
-        let currently_pressed: HashSet<_> = e.keyboard_state().pressed_scancodes().collect();
+        self.update_pressed(e.keyboard_state().pressed_scancodes());
+    }
+
+    // Like update, but takes the pressed keys directly instead of asking SDL for them.
+    pub fn update_pressed<I: IntoIterator<Item = Scancode>>(&mut self, pressed: I) {
+        let currently_pressed: HashSet<_> = pressed.into_iter().collect();
         self.just_pressed.clear();
         for scancode in currently_pressed.difference(&self.currently_pressed) {
             self.just_pressed.insert(scancode.clone());
@@ -34,6 +39,10 @@ impl KeyboardControls {
         self.currently_pressed = currently_pressed;
     }
 
+    pub fn pressed(&self) -> &HashSet<Scancode> {
+        &self.currently_pressed
+    }
+
     pub fn is_pressed(&self, scancode: Scancode) -> bool {
         self.currently_pressed.contains(&scancode)
     }
@@ -1,3 +1,19 @@
+pub mod controller;
+pub mod input;
+pub mod input_map;
 pub mod keyboard;
+pub mod replay;
 
-pub use self::keyboard::KeyboardControls;
\ No newline at end of file
+pub use self::controller::ControllerControls;
+pub use self::controller::ControllerEvent;
+pub use self::controller::DeadZones;
+pub use self::input::Input;
+pub use self::input_map::AxisDirection;
+pub use self::input_map::Binding;
+pub use self::input_map::ControlsConfig;
+pub use self::input_map::InputMap;
+pub use self::keyboard::KeyboardControls;
+pub use self::replay::InputPlayback;
+pub use self::replay::InputRecorder;
+pub use self::replay::InputSnapshot;
+pub use self::replay::Replay;
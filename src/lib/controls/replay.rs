@@ -0,0 +1,143 @@
+use app::{
+    Error,
+    StatusOr,
+};
+use controls::Input;
+use dimensions::time::DeltaTime;
+use file;
+use sdl2::{
+    controller::{
+        Axis,
+        Button,
+    },
+    keyboard::Scancode,
+};
+use serde_json;
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{
+        BufWriter,
+        prelude::*,
+    },
+    path::{
+        Path,
+        PathBuf,
+    },
+    vec,
+};
+
+// Everything a simulation step reads from Input, plus the step's DeltaTime. Replay files
+// hold one of these per line as JSON.
+#[derive(Serialize, Deserialize)]
+pub struct InputSnapshot {
+    pub dt_microseconds: i64,
+    pub keys: Vec<i32>,
+    pub buttons: Vec<String>,
+    pub axes: Vec<(String, f32)>,
+}
+
+impl InputSnapshot {
+    pub fn capture(input: &Input, dt: DeltaTime) -> InputSnapshot {
+        let mut keys: Vec<_> = input.keyboard.pressed().iter().map(|scancode| *scancode as i32).collect();
+        keys.sort();
+        let mut buttons: Vec<_> = input.controllers.pressed().iter().map(|button| button.string()).collect();
+        buttons.sort();
+        let mut axes: Vec<_> = input.controllers.axes().iter()
+            .filter(|&(_, value)| *value != 0.0)
+            .map(|(axis, value)| (axis.string(), *value))
+            .collect();
+        axes.sort_by(|a, b| a.0.cmp(&b.0));
+        InputSnapshot {
+            dt_microseconds: dt.as_microseconds(),
+            keys,
+            buttons,
+            axes,
+        }
+    }
+
+    // Overwrites input's state with this snapshot, as if it had just been sampled.
+    pub fn apply(&self, input: &mut Input) -> DeltaTime {
+        input.keyboard.update_pressed(self.keys.iter().filter_map(|key| Scancode::from_i32(*key)));
+        let axes: HashMap<_, _> = self.axes.iter()
+            .filter_map(|&(ref name, value)| Axis::from_string(name).map(|axis| (axis, value)))
+            .collect();
+        input.controllers.update_state(self.buttons.iter().filter_map(|name| Button::from_string(name)), axes);
+        DeltaTime::from_microseconds(self.dt_microseconds)
+    }
+}
+
+// Writes an InputSnapshot per simulation step.
+pub struct InputRecorder {
+    path: PathBuf,
+    writer: BufWriter<File>,
+}
+
+impl InputRecorder {
+    pub fn create(path: &Path) -> StatusOr<InputRecorder> {
+        let writer = file::util::buffered_writer_for(path)
+            .map_err(|err| Error::io(path.to_path_buf(), err))?;
+        Ok(InputRecorder {
+            path: path.to_path_buf(),
+            writer,
+        })
+    }
+
+    pub fn record(&mut self, input: &Input, dt: DeltaTime) -> StatusOr<()> {
+        let line = serde_json::to_string(&InputSnapshot::capture(input, dt))
+            .map_err(|err| Error::InvalidArgument(format!("Unserializable input snapshot: {}", err)))?;
+        writeln!(self.writer, "{}", line)
+            .map_err(|err| Error::io(self.path.clone(), err))
+    }
+}
+
+// Feeds a recording back one step at a time.
+pub struct InputPlayback {
+    snapshots: vec::IntoIter<InputSnapshot>,
+}
+
+impl InputPlayback {
+    pub fn open(path: &Path) -> StatusOr<InputPlayback> {
+        let lines = file::util::lines(path)
+            .map_err(|err| Error::io(path.to_path_buf(), err))?;
+        let mut snapshots = Vec::new();
+        for (index, line) in lines.enumerate() {
+            let line = line.map_err(|err| Error::io(path.to_path_buf(), err))?;
+            let snapshot = serde_json::from_str(line.as_str())
+                .map_err(|err| Error::Parse { path: path.to_path_buf(), line: index + 1, reason: err.to_string() })?;
+            snapshots.push(snapshot);
+        }
+        Ok(InputPlayback {
+            snapshots: snapshots.into_iter(),
+        })
+    }
+
+    // Loads the next step's input, returning its DeltaTime, or None once the recording ends.
+    pub fn next(&mut self, input: &mut Input) -> Option<DeltaTime> {
+        self.snapshots.next().map(|snapshot| snapshot.apply(input))
+    }
+}
+
+// Whether the simulation's input is live, live and being recorded, or played back.
+pub enum Replay {
+    Off,
+    Record(InputRecorder),
+    Playback(InputPlayback),
+}
+
+impl Replay {
+    // Looks for --record <path> or --playback <path>.
+    pub fn from_args(args: &[String]) -> StatusOr<Replay> {
+        let flag_value = |flag: &str| args.iter()
+            .position(|arg| arg == flag)
+            .map(|i| args.get(i + 1)
+                .map(PathBuf::from)
+                .ok_or_else(|| Error::InvalidArgument(format!("{} needs a file path", flag))));
+        match (flag_value("--record"), flag_value("--playback")) {
+            (Some(_), Some(_)) => Err(Error::InvalidArgument(String::from("Can't both --record and --playback"))),
+            (Some(path), None) => Ok(Replay::Record(InputRecorder::create(&path?)?)),
+            (None, Some(path)) => Ok(Replay::Playback(InputPlayback::open(&path?)?)),
+            (None, None) => Ok(Replay::Off),
+        }
+    }
+}
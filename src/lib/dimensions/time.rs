@@ -23,6 +23,12 @@ impl DeltaTime {
         }
     }
 
+    pub fn from_microseconds(microseconds_elapsed: Microseconds) -> DeltaTime {
+        DeltaTime {
+            microseconds_elapsed
+        }
+    }
+
     pub fn as_microseconds(&self) -> Microseconds {
         self.microseconds_elapsed
     }
@@ -1,9 +1,6 @@
 use app::StatusOr;
-use controls::KeyboardControls;
-use dimensions::{
-    Pixels,
-    time
-};
+use controls::Input;
+use dimensions::time;
 use file;
 use geometry::{
     MeshCuboid,
@@ -18,110 +15,145 @@ use nphysics3d::{
 };
 use num_traits::identities::Zero;
 use render::{
-    Sprite3D,
+    AnimationConfig,
+    AnimationEvent,
+    DepthMapConfig,
+    DepthMapInfo,
+    ReifyBackend,
+    SheetLayout,
     Sprite3DSheetInfo,
+    SpriteAnimation,
+    SpriteSheet,
 };
-use sdl2::keyboard::Scancode;
-use serde_json;
+use std::path::PathBuf;
 
 #[derive(Deserialize)]
 pub struct AthleteConfig {
-    pub sprite_segment_width: Pixels,
-    pub sprite_segment_millis: i64,
-    pub sideways_states: Vec<usize>,
-    pub backwards_states: Vec<usize>,
+    pub sheet: SheetLayout,
+    // Defaults to the sheet file's own frame durations, if it has them.
+    #[serde(default)]
+    pub sprite_segment_millis: Option<i64>,
+    #[serde(default)]
+    pub depth_map: Option<DepthMapConfig>,
+    #[serde(default)]
+    pub reify_backend: ReifyBackend,
+    // Driven by the depth_speed parameter, how fast the athlete is running into or out of the screen.
+    pub animation: AnimationConfig,
 }
 
 impl AthleteConfig {
     pub fn read() -> StatusOr<AthleteConfig> {
-        let path = file::util::resource_path("config", "athlete.config");
-        let data = file::util::slurp_file(path)
-            .map_err(|err| format!("Couldn't slurp athlete config: {}", err))?;
-        serde_json::from_str(data.as_str())
-            .map_err(|err| format!("Couldn't read athlete config: {}", err))
+        file::util::read_config("athlete.config")
     }
 }
 
 pub struct Athlete {
-    pub sprite: Sprite3D,
+    pub animation: SpriteAnimation,
+    // Emitted by animation during the last update.
+    animation_events: Vec<AnimationEvent>,
     pub transform: Transform,
+    previous_transform: Transform,
     cuboid_bounds: MeshCuboid,
     rigid_body: Option<nphysics3d::object::RigidBodyHandle<f32>>,
+    sprite_sheet: SpriteSheet,
+    depth_map_filepath: Option<PathBuf>,
+    max_depth: f32,
+    reify_backend: ReifyBackend,
 }
 
 impl Athlete {
    pub fn new() -> StatusOr<Athlete> {
-       let filepath = file::util::resource_path("images", "player.png");
        let config = AthleteConfig::read()?;
-       let sheet_info = Sprite3DSheetInfo {
-           filepath: filepath.as_str(),
-           segment_width: config.sprite_segment_width,
-           time_per_segment: time::milliseconds(config.sprite_segment_millis),
-           all_states: vec![config.sideways_states.clone(), config.backwards_states.clone()],
-       };
+       let max_depth = config.depth_map.as_ref().map(|depth_map| depth_map.max_depth).unwrap_or(1.0);
+       let sprite_sheet = SpriteSheet::from_layout(&config.sheet)?;
+       let frame_durations = sprite_sheet.frame_durations(config.sprite_segment_millis)?;
        let mut athlete = Athlete {
-           sprite: Sprite3D::new(sheet_info)?,
+           animation: SpriteAnimation::new(&config.animation, &sprite_sheet, frame_durations)?,
+           animation_events: Vec::new(),
            transform: Transform::new(),
+           previous_transform: Transform::new(),
            cuboid_bounds: MeshCuboid::new(12.0, 20.998, 1.001, 25.998),
            rigid_body: None,
+           sprite_sheet,
+           depth_map_filepath: match config.depth_map {
+               Some(ref depth_map) => Some(file::util::resource_path("images", &depth_map.image)?),
+               None => None,
+           },
+           max_depth,
+           reify_backend: config.reify_backend,
        };
        athlete.transform.scale = glm::vec3(0.5, 0.5, 5.0);
        athlete.transform.position = glm::vec3(0.0, 29.5, 130.0);
-       athlete.sprite.object_center = athlete.cuboid_bounds.object_center();
+       athlete.previous_transform = athlete.transform;
        Ok(athlete)
    }
 
-    pub fn draw(&self, projection_view: &glm::Mat4) {
-        let model = self.transform.model();
-        self.sprite.draw(projection_view, &model);
+    pub fn sprite_sheet_info<'a>(&'a self) -> Sprite3DSheetInfo<'a> {
+        Sprite3DSheetInfo {
+            filepath: self.sprite_sheet.source(),
+            frames: self.sprite_sheet.frames(),
+            depth_map: self.depth_map_filepath.as_ref().map(|filepath| DepthMapInfo {
+                filepath,
+                max_depth: self.max_depth,
+            }),
+            backend: self.reify_backend,
+        }
     }
 
-    pub fn update(&mut self, dt: time::DeltaTime, keyboard: &KeyboardControls, world: &mut nphysics3d::world::World<f32>) {
-        if keyboard.just_released(Scancode::Y) || keyboard.just_released(Scancode::H) {
-            self.sprite.set_state_index(0).unwrap();
-        }
-        if keyboard.just_pressed(Scancode::Y) || keyboard.just_pressed(Scancode::H) {
-            self.sprite.set_state_index(1).unwrap();
-        }
+    // e.g. foot plants, for gameplay to react to.
+    pub fn animation_events(&self) -> &[AnimationEvent] {
+        &self.animation_events
+    }
+
+    pub fn object_center(&self) -> glm::Vec3 {
+        self.cuboid_bounds.object_center()
+    }
+
+    pub fn position(&self) -> glm::Vec3 {
+        self.transform.position
+    }
+
+    // alpha is how far between the last two physics steps to place the model.
+    pub fn model(&self, alpha: f32) -> glm::Mat4 {
+        Transform::interpolate(&self.previous_transform, &self.transform, alpha).model()
+    }
+
+    // The physics collider's world-space center and half extents, interpolated like model.
+    pub fn collider_bounds(&self, alpha: f32) -> (glm::Vec3, glm::Vec3) {
+        let center = Transform::interpolate(&self.previous_transform, &self.transform, alpha).position;
+        let half_extents = *self.cuboid_bounds.cuboid(self.transform.scale).half_extents();
+        (center, glm::vec3(half_extents.x, half_extents.y, half_extents.z))
+    }
 
-        self.sprite.update(dt);
-        self.update_physics(keyboard, world);
+    pub fn update(&mut self, dt: time::DeltaTime, input: &Input, world: &mut nphysics3d::world::World<f32>) {
+        self.previous_transform = self.transform;
+        self.animation.set_parameter("depth_speed", (input.value("move_backward") - input.value("move_forward")).abs());
+        self.animation_events = self.animation.update(dt);
+        self.update_physics(input, world);
     }
 
-    fn update_physics(&mut self, keyboard: &KeyboardControls, world: &mut nphysics3d::world::World<f32>) {
+    fn update_physics(&mut self, input: &Input, world: &mut nphysics3d::world::World<f32>) {
         if self.rigid_body.is_none() {
             let cuboid = self.cuboid_bounds.cuboid(self.transform.scale);
             let (mass, center_of_mass, _angular_inertia) = cuboid.mass_properties(1.0);
             let zero_angular_inertia = nphysics3d::math::AngularInertia::<f32>::zero();
             let shape_handle = ncollide::shape::ShapeHandle::new(cuboid);
             let mut rigid_body = nphysics3d::object::RigidBody::new(shape_handle, Some((mass, center_of_mass, zero_angular_inertia)), 0.9, 0.0);
-            let pos = self.transform.position;
-            rigid_body.set_translation(nalgebra::Translation3::new(pos.x, pos.y, pos.z));
+            rigid_body.set_transformation(self.transform.to_isometry());
             self.rigid_body = Some(world.add_rigid_body(rigid_body));
         } else if let Some(ref rigid_body) = self.rigid_body.as_ref() {
             let mut rigid_body_mut = rigid_body.borrow_mut();
             let vertical_lin_vel = rigid_body_mut.lin_vel().y;
-            let mut horizontal_lin_vel = glm::vec2(0.0, 0.0);
-            if keyboard.is_pressed(Scancode::J) {
-                horizontal_lin_vel.x += 5.0;
-            }
-            if keyboard.is_pressed(Scancode::G) {
-                horizontal_lin_vel.x -= 5.0;
-            }
-            if keyboard.is_pressed(Scancode::Y) {
-                horizontal_lin_vel.y -= 5.0;
-            }
-            if keyboard.is_pressed(Scancode::H) {
-                horizontal_lin_vel.y += 5.0;
-            }
+            let speed = if input.is_pressed("sprint") { 10.0 } else { 5.0 };
+            let horizontal_lin_vel = glm::vec2(input.value("move_right") - input.value("move_left"),
+                                               input.value("move_backward") - input.value("move_forward")) * speed;
             rigid_body_mut.set_lin_vel(nalgebra::Vector3::new(horizontal_lin_vel.x, vertical_lin_vel, horizontal_lin_vel.y));
         }
     }
 
     pub fn apply_physics(&mut self) {
         if let Some(ref rigid_body) = self.rigid_body.as_ref() {
-            let translation = rigid_body.borrow().position().translation.vector;
-            self.transform.position = glm::vec3(translation.x, translation.y, translation.z);
+            self.transform.set_isometry(rigid_body.borrow().position());
         }
     }
 }
\ No newline at end of file
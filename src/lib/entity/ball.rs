@@ -1,8 +1,5 @@
 use app::StatusOr;
-use dimensions::{
-    Pixels,
-    time
-};
+use dimensions::time;
 use file;
 use geometry::{
     MeshCuboid,
@@ -17,64 +14,109 @@ use nphysics3d::{
 };
 use num_traits::identities::Zero;
 use render::{
-    Sprite3D,
-    Sprite3DSheetInfo
+    AnimationConfig,
+    DepthMapConfig,
+    DepthMapInfo,
+    ReifyBackend,
+    SheetLayout,
+    Sprite3DSheetInfo,
+    SpriteAnimation,
+    SpriteSheet,
 };
-use serde_json;
+use std::path::PathBuf;
 
 #[derive(Deserialize)]
 pub struct BallConfig {
-    pub sprite_segment_width: Pixels,
-    pub sprite_segment_millis: i64,
-    pub states: Vec<usize>,
+    pub sheet: SheetLayout,
+    // Defaults to the sheet file's own frame durations, if it has them.
+    #[serde(default)]
+    pub sprite_segment_millis: Option<i64>,
+    #[serde(default)]
+    pub depth_map: Option<DepthMapConfig>,
+    #[serde(default)]
+    pub reify_backend: ReifyBackend,
+    pub animation: AnimationConfig,
 }
 
 impl BallConfig {
     pub fn read() -> StatusOr<BallConfig> {
-        let path = file::util::resource_path("config", "ball.config");
-        let data = file::util::slurp_file(path)
-            .map_err(|err| format!("Couldn't slurp ball config: {}", err))?;
-        serde_json::from_str(data.as_str())
-            .map_err(|err| format!("Couldn't read ball config: {}", err))
+        file::util::read_config("ball.config")
     }
 }
 
 pub struct Ball {
-    pub sprite: Sprite3D,
+    pub animation: SpriteAnimation,
     transform: Transform,
+    previous_transform: Transform,
     cuboid_bounds: MeshCuboid,
     rigid_body: Option<nphysics3d::object::RigidBodyHandle<f32>>,
+    sprite_sheet: SpriteSheet,
+    depth_map_filepath: Option<PathBuf>,
+    max_depth: f32,
+    reify_backend: ReifyBackend,
 }
 
 impl Ball {
     pub fn new() -> StatusOr<Ball> {
         let config = BallConfig::read()?;
-        let filepath = file::util::resource_path("images", "ball.png");
-        let sheet_info = Sprite3DSheetInfo {
-            filepath: filepath.as_str(),
-            segment_width: config.sprite_segment_width,
-            time_per_segment: time::milliseconds(config.sprite_segment_millis),
-            all_states: vec![config.states.clone()],
-        };
+        let max_depth = config.depth_map.as_ref().map(|depth_map| depth_map.max_depth).unwrap_or(1.0);
+        let sprite_sheet = SpriteSheet::from_layout(&config.sheet)?;
+        let frame_durations = sprite_sheet.frame_durations(config.sprite_segment_millis)?;
         let mut ball = Ball {
-            sprite: Sprite3D::new(sheet_info)?,
+            animation: SpriteAnimation::new(&config.animation, &sprite_sheet, frame_durations)?,
             transform: Transform::new(),
+            previous_transform: Transform::new(),
             cuboid_bounds: MeshCuboid::new(1.001, 13.999, 1.001, 13.999),
             rigid_body: None,
+            sprite_sheet,
+            depth_map_filepath: match config.depth_map {
+                Some(ref depth_map) => Some(file::util::resource_path("images", &depth_map.image)?),
+                None => None,
+            },
+            max_depth,
+            reify_backend: config.reify_backend,
         };
         ball.transform.scale = glm::vec3(0.5, 0.5, 0.5);
         ball.transform.position = glm::vec3(0.0, 50.0, 130.0);
-        ball.sprite.object_center = ball.cuboid_bounds.object_center();
+        ball.previous_transform = ball.transform;
         Ok(ball)
     }
 
-    pub fn draw(&self, projection_view: &glm::Mat4) {
-        let model = self.transform.model();
-        self.sprite.draw(projection_view, &model);
+    pub fn sprite_sheet_info<'a>(&'a self) -> Sprite3DSheetInfo<'a> {
+        Sprite3DSheetInfo {
+            filepath: self.sprite_sheet.source(),
+            frames: self.sprite_sheet.frames(),
+            depth_map: self.depth_map_filepath.as_ref().map(|filepath| DepthMapInfo {
+                filepath,
+                max_depth: self.max_depth,
+            }),
+            backend: self.reify_backend,
+        }
+    }
+
+    pub fn object_center(&self) -> glm::Vec3 {
+        self.cuboid_bounds.object_center()
+    }
+
+    pub fn position(&self) -> glm::Vec3 {
+        self.transform.position
+    }
+
+    // alpha is how far between the last two physics steps to place the model.
+    pub fn model(&self, alpha: f32) -> glm::Mat4 {
+        Transform::interpolate(&self.previous_transform, &self.transform, alpha).model()
+    }
+
+    // The physics collider's world-space center and half extents, interpolated like model.
+    pub fn collider_bounds(&self, alpha: f32) -> (glm::Vec3, glm::Vec3) {
+        let center = Transform::interpolate(&self.previous_transform, &self.transform, alpha).position;
+        let half_extents = *self.cuboid_bounds.cuboid(self.transform.scale).half_extents();
+        (center, glm::vec3(half_extents.x, half_extents.y, half_extents.z))
     }
 
     pub fn update(&mut self, dt: time::DeltaTime, world: &mut nphysics3d::world::World<f32>) {
-        self.sprite.update(dt);
+        self.previous_transform = self.transform;
+        self.animation.update(dt);
         self.update_physics(world);
     }
 
@@ -85,8 +127,7 @@ impl Ball {
             let zero_angular_inertia = nphysics3d::math::AngularInertia::<f32>::zero();
             let shape_handle = ncollide::shape::ShapeHandle::new(cuboid);
             let mut rigid_body = nphysics3d::object::RigidBody::new(shape_handle, Some((mass, center_of_mass, zero_angular_inertia)), 0.7, 0.0);
-            let pos = self.transform.position;
-            rigid_body.set_translation(nalgebra::Translation3::new(pos.x, pos.y, pos.z));
+            rigid_body.set_transformation(self.transform.to_isometry());
             self.rigid_body = Some(world.add_rigid_body(rigid_body));
         } else if let Some(ref rigid_body) = self.rigid_body.as_ref() {
             let mut rigid_body_mut = rigid_body.borrow_mut();
@@ -97,8 +138,7 @@ impl Ball {
 
     pub fn apply_physics(&mut self) {
         if let Some(ref rigid_body) = self.rigid_body.as_ref() {
-            let translation = rigid_body.borrow().position().translation.vector;
-            self.transform.position = glm::vec3(translation.x, translation.y, translation.z);
+            self.transform.set_isometry(rigid_body.borrow().position());
         }
     }
 }
@@ -1,12 +1,11 @@
 use app::StatusOr;
-use controls::KeyboardControls;
+use controls::Input;
 use dimensions::time::DeltaTime;
 use entity::{
     Athlete,
     Ball,
     HexGrid,
 };
-use glm;
 use nalgebra;
 use nphysics3d;
 
@@ -32,9 +31,9 @@ impl PhysicalWorld {
         })
     }
 
-    pub fn update(&mut self, keyboard: &KeyboardControls, dt: DeltaTime) {
+    pub fn update(&mut self, input: &Input, dt: DeltaTime) {
         // Pre-physics step
-        self.athlete.update(dt, keyboard, &mut self.world);
+        self.athlete.update(dt, input, &mut self.world);
         self.ball.update(dt, &mut self.world);
         self.hex_grid.update(dt);
 
@@ -46,9 +45,114 @@ impl PhysicalWorld {
         self.ball.apply_physics();
     }
 
-    pub fn draw(&self, projection_view: &glm::Mat4) {
-        self.athlete.draw(projection_view);
-        self.ball.draw(projection_view);
-        self.hex_grid.draw(projection_view);
+    pub fn athlete(&self) -> &Athlete {
+        &self.athlete
+    }
+
+    pub fn ball(&self) -> &Ball {
+        &self.ball
+    }
+
+    pub fn hex_grid(&self) -> &HexGrid {
+        &self.hex_grid
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use app::{
+        fixed_timestep,
+        FixedTimestep,
+    };
+    use controls::{
+        Binding,
+        InputPlayback,
+        InputRecorder,
+    };
+    use glm;
+    use std::{
+        env,
+        fs,
+        process,
+    };
+
+    fn step_delta_time() -> DeltaTime {
+        FixedTimestep::new(fixed_timestep::DEFAULT_STEPS_PER_SECOND,
+                           fixed_timestep::DEFAULT_MAX_STEPS_PER_FRAME).step_delta_time()
+    }
+
+    // Holds the first key bound to each action, and nothing else.
+    fn hold(input: &mut Input, actions: &[&str]) {
+        let keys: Vec<_> = actions.iter()
+            .filter_map(|action| input.input_map.bindings(action).iter()
+                .filter_map(|binding| match *binding {
+                    Binding::Key(scancode) => Some(scancode),
+                    _ => None,
+                })
+                .next())
+            .collect();
+        input.keyboard.update_pressed(keys);
+    }
+
+    #[test]
+    fn steps_without_sdl_or_gl() {
+        let mut world = PhysicalWorld::new().unwrap();
+        let mut input = Input::read().unwrap();
+        let start_athlete = world.athlete().position();
+        let start_ball = world.ball().position();
+        let dt = step_delta_time();
+        // A second of running left, at 5 units a second.
+        for _ in 0..fixed_timestep::DEFAULT_STEPS_PER_SECOND {
+            hold(&mut input, &["move_left"]);
+            world.update(&input, dt);
+        }
+        let athlete = world.athlete().position();
+        assert!(athlete.x < start_athlete.x - 4.0 && athlete.x > start_athlete.x - 6.0, "athlete at {:?}", athlete.x);
+        assert!((athlete.z - start_athlete.z).abs() < 0.01, "athlete at {:?}", athlete.z);
+        let ball = world.ball().position();
+        assert!(ball.y < start_ball.y, "ball at {:?}", ball.y);
+        assert!((ball.x - start_ball.x).abs() < 0.01 && (ball.z - start_ball.z).abs() < 0.01, "ball at {:?}", ball);
+    }
+
+    fn position_bits(position: glm::Vec3) -> [u32; 3] {
+        [position.x.to_bits(), position.y.to_bits(), position.z.to_bits()]
+    }
+
+    #[test]
+    fn playback_reproduces_recording() {
+        let path = env::temp_dir().join(format!("physical_world_replay_{}.txt", process::id()));
+        let dt = step_delta_time();
+        let script: Vec<&[&str]> = vec![&["move_right"], &["move_right", "sprint"], &["move_forward", "move_left"], &[]];
+
+        let mut recorded_world = PhysicalWorld::new().unwrap();
+        {
+            let mut input = Input::read().unwrap();
+            let mut recorder = InputRecorder::create(&path).unwrap();
+            for actions in script.iter() {
+                for _ in 0..60 {
+                    hold(&mut input, actions);
+                    recorder.record(&input, dt).unwrap();
+                    recorded_world.update(&input, dt);
+                }
+            }
+        }
+
+        let mut replayed_world = PhysicalWorld::new().unwrap();
+        let mut input = Input::read().unwrap();
+        let mut playback = InputPlayback::open(&path).unwrap();
+        let mut num_steps = 0;
+        while let Some(dt) = playback.next(&mut input) {
+            replayed_world.update(&input, dt);
+            num_steps += 1;
+        }
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(num_steps, 60 * script.len());
+        assert_eq!(position_bits(replayed_world.athlete().position()), position_bits(recorded_world.athlete().position()));
+        assert_eq!(position_bits(replayed_world.ball().position()), position_bits(recorded_world.ball().position()));
+        let (replayed, recorded) = (replayed_world.athlete().transform.rotation, recorded_world.athlete().transform.rotation);
+        assert_eq!(replayed.coords.as_slice().iter().map(|c| c.to_bits()).collect::<Vec<_>>(),
+                   recorded.coords.as_slice().iter().map(|c| c.to_bits()).collect::<Vec<_>>());
     }
 }
@@ -0,0 +1,95 @@
+// Reads little endian values off the front of a byte slice. Each read is None once too few
+// bytes are left, so a truncated file can be rejected with ?.
+pub struct ByteReader<'a> {
+    bytes: &'a [u8],
+}
+
+impl <'a> ByteReader<'a> {
+    pub fn new(bytes: &'a [u8]) -> ByteReader<'a> {
+        ByteReader { bytes }
+    }
+
+    // Whatever hasn't been read yet.
+    pub fn rest(&self) -> &'a [u8] {
+        self.bytes
+    }
+
+    pub fn take(&mut self, count: usize) -> Option<&'a [u8]> {
+        if self.bytes.len() < count {
+            return None;
+        }
+        let (taken, rest) = self.bytes.split_at(count);
+        self.bytes = rest;
+        Some(taken)
+    }
+
+    pub fn u8(&mut self) -> Option<u8> {
+        self.take(1).map(|bytes| bytes[0])
+    }
+
+    pub fn u16(&mut self) -> Option<u16> {
+        self.take(2).map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    pub fn i16(&mut self) -> Option<i16> {
+        self.u16().map(|value| value as i16)
+    }
+
+    pub fn u32(&mut self) -> Option<u32> {
+        self.take(4).map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    pub fn u64(&mut self) -> Option<u64> {
+        let mut buffer = [0u8; 8];
+        buffer.copy_from_slice(self.take(8)?);
+        Some(u64::from_le_bytes(buffer))
+    }
+
+    pub fn f32(&mut self) -> Option<f32> {
+        self.u32().map(f32::from_bits)
+    }
+
+    // A u16 length, then that many bytes of UTF-8.
+    pub fn string(&mut self) -> Option<String> {
+        let length = self.u16()? as usize;
+        self.take(length).map(|bytes| String::from_utf8_lossy(bytes).into_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_little_endian_values_in_order() {
+        let bytes = [
+            7,
+            0x34, 0x12,
+            0xfe, 0xff,
+            0x78, 0x56, 0x34, 0x12,
+            1, 0, 0, 0, 0, 0, 0, 0x80,
+            0x00, 0x00, 0xc0, 0x3f,
+            2, 0, b'h', b'i',
+            9,
+        ];
+        let mut reader = ByteReader::new(&bytes);
+        assert_eq!(reader.u8(), Some(7));
+        assert_eq!(reader.u16(), Some(0x1234));
+        assert_eq!(reader.i16(), Some(-2));
+        assert_eq!(reader.u32(), Some(0x1234_5678));
+        assert_eq!(reader.u64(), Some(0x8000_0000_0000_0001));
+        assert_eq!(reader.f32(), Some(1.5));
+        assert_eq!(reader.string(), Some("hi".to_string()));
+        assert_eq!(reader.rest(), &[9]);
+    }
+
+    #[test]
+    fn short_reads_are_none_and_consume_nothing() {
+        let mut reader = ByteReader::new(&[1, 2, 3]);
+        assert_eq!(reader.u32(), None);
+        assert_eq!(reader.take(4), None);
+        assert_eq!(reader.u16(), Some(0x0201));
+        assert_eq!(reader.string(), None);
+        assert_eq!(reader.rest(), &[3]);
+    }
+}
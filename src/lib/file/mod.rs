@@ -1 +1,3 @@
-pub mod util;
\ No newline at end of file
+pub mod bytes;
+pub mod resource_root;
+pub mod util;
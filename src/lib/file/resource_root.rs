@@ -0,0 +1,57 @@
+use std::{
+    env,
+    path::{
+        Path,
+        PathBuf,
+    },
+};
+
+// Command line flag naming the resource root, as "--res-dir <dir>" or "--res-dir=<dir>".
+pub const RES_DIR_FLAG: &'static str = "--res-dir";
+// Environment variable naming the resource root.
+pub const RES_DIR_ENV_VAR: &'static str = "GAME_RES_DIR";
+
+fn res_dir_flag_values() -> Vec<PathBuf> {
+    let args: Vec<String> = env::args().collect();
+    let flag_with_equals = format!("{}=", RES_DIR_FLAG);
+    let mut values = vec!();
+    for (i, arg) in args.iter().enumerate() {
+        if arg == RES_DIR_FLAG {
+            if let Some(value) = args.get(i + 1) {
+                values.push(PathBuf::from(value));
+            }
+        } else if arg.starts_with(flag_with_equals.as_str()) {
+            values.push(PathBuf::from(&arg[flag_with_equals.len()..]));
+        }
+    }
+    values
+}
+
+// Every directory that may hold the res/ tree, in the order they should be searched:
+// the --res-dir flag, the GAME_RES_DIR variable, next to the executable, and finally
+// the source tree the game was compiled from.
+pub fn candidate_roots() -> Vec<PathBuf> {
+    let mut roots = res_dir_flag_values();
+    if let Some(dir) = env::var_os(RES_DIR_ENV_VAR) {
+        roots.push(PathBuf::from(dir));
+    }
+    if let Some(exe_dir) = env::current_exe().ok().and_then(|exe| exe.parent().map(Path::to_path_buf)) {
+        // Shipped builds keep res/ beside the binary. Cargo builds live in target/<profile>/.
+        roots.push(exe_dir.join("res"));
+        roots.push(exe_dir.join("..").join("..").join("src").join("res"));
+    }
+    roots.push(Path::new(env!("CARGO_MANIFEST_DIR")).join("src").join("res"));
+    roots
+}
+
+// Finds the first existing <root>/<relative_path> among the candidate roots. On failure,
+// returns every path that was tried.
+pub fn resolve(relative_path: &Path) -> Result<PathBuf, Vec<PathBuf>> {
+    let searched: Vec<PathBuf> = candidate_roots().into_iter()
+        .map(|root| root.join(relative_path))
+        .collect();
+    match searched.iter().find(|path| path.exists()) {
+        Some(found) => Ok(found.clone()),
+        None => Err(searched),
+    }
+}
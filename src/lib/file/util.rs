@@ -1,3 +1,10 @@
+use app::{
+    Error,
+    StatusOr,
+};
+use file::resource_root;
+use serde::de::DeserializeOwned;
+use serde_json;
 use std::{
     convert::AsRef,
     fs::File,
@@ -7,7 +14,10 @@ use std::{
         Lines,
         prelude::*,
     },
-    path::Path,
+    path::{
+        Path,
+        PathBuf,
+    },
     self,
 };
 
@@ -30,7 +40,25 @@ pub fn buffered_writer_for<P: AsRef<Path>>(path: P) -> std::io::Result<BufWriter
     Ok(BufWriter::new(file))
 }
 
-pub fn resource_path(parent_folder: &'static str, resource_name: &'static str) -> String {
-    // TODO fix this ugly hack
-    format!("D:\\Programming\\IntelliJ\\Game\\src\\res\\{}\\{}", parent_folder, resource_name)
+// Locates res/<parent_folder>/<resource_name>, which must already exist.
+pub fn resource_path(parent_folder: &str, resource_name: &str) -> StatusOr<PathBuf> {
+    let relative_path = Path::new(parent_folder).join(resource_name);
+    resource_root::resolve(&relative_path)
+        .map_err(|searched| Error::ResourceNotFound { resource: relative_path.clone(), searched })
+}
+
+// Locates the res/<parent_folder> directory, e.g. to write new files into it.
+pub fn resource_dir(parent_folder: &str) -> StatusOr<PathBuf> {
+    let relative_path = Path::new(parent_folder);
+    resource_root::resolve(relative_path)
+        .map_err(|searched| Error::ResourceNotFound { resource: relative_path.to_path_buf(), searched })
+}
+
+// Reads res/config/<config_name> as JSON.
+pub fn read_config<T: DeserializeOwned>(config_name: &str) -> StatusOr<T> {
+    let path = resource_path("config", config_name)?;
+    let data = slurp_file(&path)
+        .map_err(|err| Error::io(path.clone(), err))?;
+    serde_json::from_str(data.as_str())
+        .map_err(|err| Error::ConfigParse { path, source: err })
 }
@@ -0,0 +1,199 @@
+use app::{
+    Error,
+    StatusOr,
+};
+use color::Rgba;
+use dimensions::Pixels;
+use file;
+use image::Png;
+use rusttype::{
+    self,
+    Font,
+    Scale,
+};
+use std::{
+    collections::HashMap,
+    path::Path,
+};
+use ttf_parser::{
+    self,
+    gpos::{
+        PairAdjustment,
+        PositioningSubtable,
+    },
+};
+
+const ATLAS_WIDTH: Pixels = 512;
+// Empty pixels around each glyph, so linear filtering doesn't bleed neighbors in.
+const GLYPH_PADDING: Pixels = 1;
+// Drawn in place of characters the atlas doesn't have.
+pub const FALLBACK_CHAR: char = '?';
+
+// Printable ASCII.
+fn atlas_chars() -> Vec<char> {
+    (b' '..=b'~').map(|byte| byte as char).collect()
+}
+
+// Where a glyph sits in the atlas and how to place it, in atlas pixels.
+#[derive(Clone, Copy, Debug)]
+pub struct GlyphInfo {
+    pub atlas_x: Pixels,
+    pub atlas_y: Pixels,
+    pub width: Pixels,
+    pub height: Pixels,
+    // Offset from the pen position on the baseline to the bitmap's top left corner.
+    pub bearing_x: f32,
+    pub bearing_y: f32,
+    pub advance: f32,
+}
+
+// A font rasterized at one pixel height into a white-on-transparent Png, with the metrics
+// needed to lay text out from it.
+pub struct FontAtlas {
+    pub png: Png,
+    pixel_height: f32,
+    ascent: f32,
+    line_height: f32,
+    glyphs: HashMap<char, GlyphInfo>,
+    kerning: HashMap<(char, char), f32>,
+}
+
+impl FontAtlas {
+    // Loads res/fonts/<font_name>.
+    pub fn from_resource(font_name: &str, pixel_height: f32) -> StatusOr<FontAtlas> {
+        let path = file::util::resource_path("fonts", font_name)?;
+        FontAtlas::from_file(&path, pixel_height)
+    }
+
+    pub fn from_file(path: &Path, pixel_height: f32) -> StatusOr<FontAtlas> {
+        let font_load_error = |reason: &str| Error::FontLoad { path: path.to_path_buf(), reason: reason.to_string() };
+        let data = ::std::fs::read(path)
+            .map_err(|err| Error::io(path.to_path_buf(), err))?;
+        let face = ttf_parser::Face::from_slice(&data, 0)
+            .map_err(|err| font_load_error(err.to_string().as_str()))?;
+        let font = Font::try_from_bytes(&data)
+            .ok_or_else(|| font_load_error("Unsupported font format"))?;
+
+        let scale = Scale::uniform(pixel_height);
+        let v_metrics = font.v_metrics(scale);
+        let chars = atlas_chars();
+
+        // Shelf-pack each glyph's bitmap left to right, starting a new row when one fills up.
+        let mut bitmaps = Vec::with_capacity(chars.len());
+        let mut glyphs = HashMap::new();
+        let (mut pen_x, mut pen_y, mut row_height) = (GLYPH_PADDING, GLYPH_PADDING, 0);
+        for c in chars.iter() {
+            let glyph = font.glyph(*c).scaled(scale);
+            let advance = glyph.h_metrics().advance_width;
+            let glyph = glyph.positioned(rusttype::point(0.0, 0.0));
+            let (width, height, bearing_x, bearing_y) = match glyph.pixel_bounding_box() {
+                Some(bounds) => (bounds.width() as Pixels, bounds.height() as Pixels, bounds.min.x as f32, bounds.min.y as f32),
+                None => (0, 0, 0.0, 0.0),
+            };
+            if pen_x + width + GLYPH_PADDING > ATLAS_WIDTH {
+                pen_x = GLYPH_PADDING;
+                pen_y += row_height + GLYPH_PADDING;
+                row_height = 0;
+            }
+            glyphs.insert(*c, GlyphInfo {
+                atlas_x: pen_x,
+                atlas_y: pen_y,
+                width,
+                height,
+                bearing_x,
+                bearing_y,
+                advance,
+            });
+            bitmaps.push((pen_x, pen_y, glyph));
+            pen_x += width + GLYPH_PADDING;
+            row_height = row_height.max(height);
+        }
+        if !glyphs.contains_key(&FALLBACK_CHAR) {
+            return Err(font_load_error("Missing fallback glyph"));
+        }
+
+        let atlas_height = (pen_y + row_height + GLYPH_PADDING).next_power_of_two();
+        let mut png = Png::new(ATLAS_WIDTH, atlas_height, &Rgba::new(1.0, 1.0, 1.0, 0.0));
+        for &(left, top, ref glyph) in bitmaps.iter() {
+            glyph.draw(|x, y, coverage| {
+                png.img[top + y as usize][left + x as usize].a = coverage;
+            });
+        }
+
+        let units_to_pixels = pixel_height / face.units_per_em() as f32;
+        let mut kerning = HashMap::new();
+        for first in chars.iter() {
+            for second in chars.iter() {
+                let kern = match gpos_kerning(&face, *first, *second) {
+                    Some(units) => units as f32 * units_to_pixels,
+                    None => font.pair_kerning(scale, *first, *second),
+                };
+                if kern != 0.0 {
+                    kerning.insert((*first, *second), kern);
+                }
+            }
+        }
+
+        Ok(FontAtlas {
+            png,
+            pixel_height,
+            ascent: v_metrics.ascent,
+            line_height: v_metrics.ascent - v_metrics.descent + v_metrics.line_gap,
+            glyphs,
+            kerning,
+        })
+    }
+
+    pub fn pixel_height(&self) -> f32 {
+        self.pixel_height
+    }
+
+    // Distance from the top of a line to its baseline.
+    pub fn ascent(&self) -> f32 {
+        self.ascent
+    }
+
+    pub fn line_height(&self) -> f32 {
+        self.line_height
+    }
+
+    // Falls back to FALLBACK_CHAR for characters outside the atlas.
+    pub fn glyph(&self, c: char) -> &GlyphInfo {
+        self.glyphs.get(&c).unwrap_or_else(|| &self.glyphs[&FALLBACK_CHAR])
+    }
+
+    // Extra advance between first and second, usually negative (e.g. "AV").
+    pub fn kerning(&self, first: char, second: char) -> f32 {
+        self.kerning.get(&(first, second)).cloned().unwrap_or(0.0)
+    }
+}
+
+// OpenType fonts (like aileron.otf) keep kerning in GPOS pair adjustments rather than the
+// legacy kern table, which is all rusttype reads. Returns design units.
+fn gpos_kerning(face: &ttf_parser::Face, first: char, second: char) -> Option<i16> {
+    let gpos = face.tables().gpos?;
+    let first = face.glyph_index(first)?;
+    let second = face.glyph_index(second)?;
+    for lookup in gpos.lookups {
+        for subtable in lookup.subtables.into_iter::<PositioningSubtable>() {
+            let pair = match subtable {
+                PositioningSubtable::Pair(pair) => pair,
+                _ => continue,
+            };
+            let coverage_index = match pair.coverage().get(first) {
+                Some(coverage_index) => coverage_index,
+                None => continue,
+            };
+            let records = match pair {
+                PairAdjustment::Format1 { sets, .. } =>
+                    sets.get(coverage_index).and_then(|set| set.get(second)),
+                PairAdjustment::Format2 { classes, matrix, .. } =>
+                    matrix.get((classes.0.get(first), classes.1.get(second))),
+            };
+            if let Some((first_record, _)) = records {
+                return Some(first_record.x_advance);
+            }
+        }
+    }
+    None
+}
@@ -0,0 +1,8 @@
+pub mod font_atlas;
+pub mod text_layout;
+
+pub use self::font_atlas::FontAtlas;
+pub use self::font_atlas::GlyphInfo;
+pub use self::text_layout::Alignment;
+pub use self::text_layout::GlyphMetrics;
+pub use self::text_layout::GlyphQuad;
@@ -0,0 +1,221 @@
+use dimensions::Pixels;
+use font::{
+    FontAtlas,
+    GlyphInfo,
+};
+
+// Where each line of text sits relative to the layout's x origin.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Alignment {
+    Left,
+    Center,
+    Right,
+}
+
+// One glyph's screen rectangle, in pixels with y down, and its atlas texture coordinates.
+#[derive(Clone, Copy, Debug)]
+pub struct GlyphQuad {
+    pub left: f32,
+    pub top: f32,
+    pub right: f32,
+    pub bottom: f32,
+    pub uv_left: f32,
+    pub uv_top: f32,
+    pub uv_right: f32,
+    pub uv_bottom: f32,
+}
+
+// The glyph metrics text is laid out from, in the pixels of an atlas rasterized at
+// pixel_height.
+pub trait GlyphMetrics {
+    fn pixel_height(&self) -> f32;
+    // Distance from the top of a line to its baseline.
+    fn ascent(&self) -> f32;
+    fn line_height(&self) -> f32;
+    fn atlas_size(&self) -> (Pixels, Pixels);
+    fn glyph(&self, c: char) -> &GlyphInfo;
+    fn kerning(&self, first: char, second: char) -> f32;
+}
+
+impl GlyphMetrics for FontAtlas {
+    fn pixel_height(&self) -> f32 {
+        FontAtlas::pixel_height(self)
+    }
+
+    fn ascent(&self) -> f32 {
+        FontAtlas::ascent(self)
+    }
+
+    fn line_height(&self) -> f32 {
+        FontAtlas::line_height(self)
+    }
+
+    fn atlas_size(&self) -> (Pixels, Pixels) {
+        self.png.size()
+    }
+
+    fn glyph(&self, c: char) -> &GlyphInfo {
+        FontAtlas::glyph(self, c)
+    }
+
+    fn kerning(&self, first: char, second: char) -> f32 {
+        FontAtlas::kerning(self, first, second)
+    }
+}
+
+impl FontAtlas {
+    pub fn layout(&self, text: &str, x: f32, y: f32, pixel_height: f32, alignment: Alignment) -> Vec<GlyphQuad> {
+        layout(self, text, x, y, pixel_height, alignment)
+    }
+}
+
+fn line_width<M: GlyphMetrics>(metrics: &M, line: &str, scale: f32) -> f32 {
+    let mut width = 0.0;
+    let mut previous = None;
+    for c in line.chars() {
+        if let Some(previous) = previous {
+            width += metrics.kerning(previous, c) * scale;
+        }
+        width += metrics.glyph(c).advance * scale;
+        previous = Some(c);
+    }
+    width
+}
+
+// Lays text out at pixel_height with its first line's top at (x, y). Each line in text
+// (split on '\n') is aligned on its own, and kerned pairwise.
+pub fn layout<M: GlyphMetrics>(metrics: &M, text: &str, x: f32, y: f32, pixel_height: f32,
+                               alignment: Alignment) -> Vec<GlyphQuad> {
+    let scale = pixel_height / metrics.pixel_height();
+    let (atlas_width, atlas_height) = metrics.atlas_size();
+    let (atlas_width, atlas_height) = (atlas_width as f32, atlas_height as f32);
+
+    let mut quads = Vec::with_capacity(text.len());
+    let mut baseline = y + metrics.ascent() * scale;
+    for line in text.split('\n') {
+        let mut pen_x = match alignment {
+            Alignment::Left => x,
+            Alignment::Center => x - line_width(metrics, line, scale) / 2.0,
+            Alignment::Right => x - line_width(metrics, line, scale),
+        };
+        let mut previous = None;
+        for c in line.chars() {
+            if let Some(previous) = previous {
+                pen_x += metrics.kerning(previous, c) * scale;
+            }
+            let glyph = metrics.glyph(c);
+            if glyph.width > 0 && glyph.height > 0 {
+                let left = pen_x + glyph.bearing_x * scale;
+                let top = baseline + glyph.bearing_y * scale;
+                quads.push(GlyphQuad {
+                    left,
+                    top,
+                    right: left + glyph.width as f32 * scale,
+                    bottom: top + glyph.height as f32 * scale,
+                    uv_left: glyph.atlas_x as f32 / atlas_width,
+                    uv_top: glyph.atlas_y as f32 / atlas_height,
+                    uv_right: (glyph.atlas_x + glyph.width) as f32 / atlas_width,
+                    uv_bottom: (glyph.atlas_y + glyph.height) as f32 / atlas_height,
+                });
+            }
+            pen_x += glyph.advance * scale;
+            previous = Some(c);
+        }
+        baseline += metrics.line_height() * scale;
+    }
+    quads
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Every glyph is a 4x6 box 1 pixel right of the pen and 6 above the baseline, advancing
+    // 5, except spaces, which only advance 3. "AV" kerns in by 2. Lines are 10 pixels apart
+    // with an ascent of 8, and the atlas is 100 x 50.
+    struct StubMetrics {
+        letter: GlyphInfo,
+        space: GlyphInfo,
+    }
+
+    impl StubMetrics {
+        fn new() -> StubMetrics {
+            StubMetrics {
+                letter: GlyphInfo { atlas_x: 10, atlas_y: 5, width: 4, height: 6, bearing_x: 1.0, bearing_y: -6.0, advance: 5.0 },
+                space: GlyphInfo { atlas_x: 0, atlas_y: 0, width: 0, height: 0, bearing_x: 0.0, bearing_y: 0.0, advance: 3.0 },
+            }
+        }
+    }
+
+    impl GlyphMetrics for StubMetrics {
+        fn pixel_height(&self) -> f32 {
+            10.0
+        }
+
+        fn ascent(&self) -> f32 {
+            8.0
+        }
+
+        fn line_height(&self) -> f32 {
+            10.0
+        }
+
+        fn atlas_size(&self) -> (Pixels, Pixels) {
+            (100, 50)
+        }
+
+        fn glyph(&self, c: char) -> &GlyphInfo {
+            if c == ' ' { &self.space } else { &self.letter }
+        }
+
+        fn kerning(&self, first: char, second: char) -> f32 {
+            if (first, second) == ('A', 'V') { -2.0 } else { 0.0 }
+        }
+    }
+
+    fn lefts(quads: &[GlyphQuad]) -> Vec<f32> {
+        quads.iter().map(|quad| quad.left).collect()
+    }
+
+    #[test]
+    fn places_glyphs_along_the_baseline() {
+        let quads = layout(&StubMetrics::new(), "ab", 100.0, 20.0, 10.0, Alignment::Left);
+        assert_eq!(lefts(&quads), vec![101.0, 106.0]);
+        let quad = quads[0];
+        assert_eq!((quad.top, quad.right, quad.bottom), (22.0, 105.0, 28.0));
+        assert_eq!((quad.uv_left, quad.uv_top, quad.uv_right, quad.uv_bottom), (0.1, 0.1, 0.14, 0.22));
+    }
+
+    #[test]
+    fn spaces_advance_without_a_quad() {
+        let quads = layout(&StubMetrics::new(), "a b", 0.0, 0.0, 10.0, Alignment::Left);
+        assert_eq!(lefts(&quads), vec![1.0, 9.0]);
+    }
+
+    #[test]
+    fn kerns_pairs() {
+        let quads = layout(&StubMetrics::new(), "AVA", 0.0, 0.0, 10.0, Alignment::Left);
+        assert_eq!(lefts(&quads), vec![1.0, 4.0, 9.0]);
+    }
+
+    #[test]
+    fn aligns_each_line_on_its_own() {
+        // "AV" is 8 wide once kerned, "abc" 15.
+        let text = "AV\nabc";
+        let left = layout(&StubMetrics::new(), text, 50.0, 0.0, 10.0, Alignment::Left);
+        assert_eq!(lefts(&left), vec![51.0, 54.0, 51.0, 56.0, 61.0]);
+        let center = layout(&StubMetrics::new(), text, 50.0, 0.0, 10.0, Alignment::Center);
+        assert_eq!(lefts(&center), vec![47.0, 50.0, 43.5, 48.5, 53.5]);
+        let right = layout(&StubMetrics::new(), text, 50.0, 0.0, 10.0, Alignment::Right);
+        assert_eq!(lefts(&right), vec![43.0, 46.0, 36.0, 41.0, 46.0]);
+    }
+
+    #[test]
+    fn newlines_move_down_a_line_and_scale_with_pixel_height() {
+        let quads = layout(&StubMetrics::new(), "a\n\nb", 0.0, 0.0, 20.0, Alignment::Left);
+        let tops: Vec<f32> = quads.iter().map(|quad| quad.top).collect();
+        // Baselines at 16 then, past the empty line, 56, each glyph 12 tall above them.
+        assert_eq!(tops, vec![4.0, 44.0]);
+        assert_eq!((quads[0].left, quads[0].right), (2.0, 10.0));
+    }
+}
@@ -0,0 +1,527 @@
+use app::{
+    Error,
+    StatusOr,
+};
+use file;
+use geometry::Mesh;
+use glm::Vec3;
+use serde_json;
+use std::{
+    io::{
+        self,
+        Write,
+    },
+    path::Path,
+};
+
+// GL enums glTF reuses.
+const ARRAY_BUFFER: u32 = 34962;
+const ELEMENT_ARRAY_BUFFER: u32 = 34963;
+const FLOAT: u32 = 5126;
+const UNSIGNED_INT: u32 = 5125;
+const NEAREST: u32 = 9728;
+const CLAMP_TO_EDGE: u32 = 33071;
+
+// "glTF", "JSON" and "BIN\0" as little endian u32s.
+const GLB_MAGIC: u32 = 0x4654_6c67;
+const GLB_VERSION: u32 = 2;
+const GLB_JSON_CHUNK: u32 = 0x4e4f_534a;
+const GLB_BIN_CHUNK: u32 = 0x004e_4942;
+
+#[derive(Serialize)]
+struct Asset {
+    version: &'static str,
+    generator: &'static str,
+}
+
+#[derive(Serialize)]
+struct Scene {
+    nodes: Vec<usize>,
+}
+
+#[derive(Serialize)]
+struct Node {
+    name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mesh: Option<usize>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    children: Vec<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    translation: Option<[f32; 3]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    scale: Option<[f32; 3]>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+struct Attributes {
+    position: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    normal: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    texcoord_0: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    color_0: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tangent: Option<usize>,
+}
+
+#[derive(Serialize)]
+struct Primitive {
+    attributes: Attributes,
+    indices: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    material: Option<usize>,
+}
+
+#[derive(Serialize)]
+struct GltfMesh {
+    name: String,
+    primitives: Vec<Primitive>,
+}
+
+#[derive(Serialize)]
+struct TextureInfo {
+    index: usize,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PbrMetallicRoughness {
+    base_color_texture: TextureInfo,
+    metallic_factor: f32,
+    roughness_factor: f32,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Material {
+    name: String,
+    pbr_metallic_roughness: PbrMetallicRoughness,
+}
+
+#[derive(Serialize)]
+struct Texture {
+    source: usize,
+    sampler: usize,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Image {
+    buffer_view: usize,
+    mime_type: &'static str,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Sampler {
+    mag_filter: u32,
+    min_filter: u32,
+    wrap_s: u32,
+    wrap_t: u32,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Accessor {
+    buffer_view: usize,
+    component_type: u32,
+    count: usize,
+    #[serde(rename = "type")]
+    kind: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    min: Option<Vec<f32>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max: Option<Vec<f32>>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct BufferView {
+    buffer: usize,
+    byte_offset: usize,
+    byte_length: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    target: Option<u32>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Buffer {
+    byte_length: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    uri: Option<String>,
+}
+
+#[derive(Serialize)]
+struct AnimationTarget {
+    node: usize,
+    path: &'static str,
+}
+
+#[derive(Serialize)]
+struct Channel {
+    sampler: usize,
+    target: AnimationTarget,
+}
+
+#[derive(Serialize)]
+struct AnimationSampler {
+    input: usize,
+    output: usize,
+    interpolation: &'static str,
+}
+
+#[derive(Serialize)]
+struct Animation {
+    name: String,
+    channels: Vec<Channel>,
+    samplers: Vec<AnimationSampler>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Document {
+    asset: Asset,
+    scene: usize,
+    scenes: Vec<Scene>,
+    nodes: Vec<Node>,
+    // glTF doesn't allow empty top level arrays, so those left empty aren't written.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    meshes: Vec<GltfMesh>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    materials: Vec<Material>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    textures: Vec<Texture>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    images: Vec<Image>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    samplers: Vec<Sampler>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    accessors: Vec<Accessor>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    buffer_views: Vec<BufferView>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    buffers: Vec<Buffer>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    animations: Vec<Animation>,
+}
+
+// Assembles a glTF 2.0 scene whose binary data all lives in one buffer, then writes it as
+// .gltf with a .bin beside it, or as a single .glb.
+pub struct GltfBuilder {
+    document: Document,
+    bin: Vec<u8>,
+}
+
+impl GltfBuilder {
+    pub fn new() -> GltfBuilder {
+        GltfBuilder {
+            document: Document {
+                asset: Asset { version: "2.0", generator: "sports3dgame" },
+                scene: 0,
+                scenes: vec![Scene { nodes: Vec::new() }],
+                nodes: Vec::new(),
+                meshes: Vec::new(),
+                materials: Vec::new(),
+                textures: Vec::new(),
+                images: Vec::new(),
+                samplers: Vec::new(),
+                accessors: Vec::new(),
+                buffer_views: Vec::new(),
+                buffers: Vec::new(),
+                animations: Vec::new(),
+            },
+            bin: Vec::new(),
+        }
+    }
+
+    // A matte material sampling png_bytes, an encoded PNG, with nearest filtering
+    // so pixel art stays crisp. Returns the material index.
+    pub fn add_png_material(&mut self, name: &str, png_bytes: &[u8]) -> usize {
+        let buffer_view = self.push_view(png_bytes, None);
+        self.document.images.push(Image { buffer_view, mime_type: "image/png" });
+        self.document.samplers.push(Sampler {
+            mag_filter: NEAREST,
+            min_filter: NEAREST,
+            wrap_s: CLAMP_TO_EDGE,
+            wrap_t: CLAMP_TO_EDGE,
+        });
+        self.document.textures.push(Texture {
+            source: self.document.images.len() - 1,
+            sampler: self.document.samplers.len() - 1,
+        });
+        self.document.materials.push(Material {
+            name: name.to_string(),
+            pbr_metallic_roughness: PbrMetallicRoughness {
+                base_color_texture: TextureInfo { index: self.document.textures.len() - 1 },
+                metallic_factor: 0.0,
+                roughness_factor: 1.0,
+            },
+        });
+        self.document.materials.len() - 1
+    }
+
+    // Copies mesh's positions, indices and whichever optional attributes it has. Returns the
+    // glTF mesh index, or None for a mesh without faces, since glTF has no empty accessors.
+    pub fn add_mesh(&mut self, name: &str, mesh: &Mesh, material: Option<usize>) -> Option<usize> {
+        if mesh.vertices.is_empty() || mesh.faces.is_empty() {
+            return None;
+        }
+        let layout = mesh.layout();
+        let count = mesh.vertices.len();
+
+        let positions: Vec<[f32; 3]> = mesh.vertices.iter().map(|v| [v.x, v.y, v.z]).collect();
+        let mut min = vec![f32::INFINITY; 3];
+        let mut max = vec![f32::NEG_INFINITY; 3];
+        for position in positions.iter() {
+            for axis in 0..3 {
+                min[axis] = min[axis].min(position[axis]);
+                max[axis] = max[axis].max(position[axis]);
+            }
+        }
+        let position = self.push_floats(&positions.concat(), Some(ARRAY_BUFFER));
+        let position = self.push_accessor(position, FLOAT, count, "VEC3", Some((min, max)));
+
+        let mut attributes = Attributes { position, normal: None, texcoord_0: None, color_0: None, tangent: None };
+        if layout.normals {
+            let floats: Vec<f32> = mesh.normals.iter().flat_map(|v| vec![v.x, v.y, v.z]).collect();
+            let view = self.push_floats(&floats, Some(ARRAY_BUFFER));
+            attributes.normal = Some(self.push_accessor(view, FLOAT, count, "VEC3", None));
+        }
+        if layout.uvs {
+            // Both put v = 0 at the image's top row, so uvs carry over as is.
+            let floats: Vec<f32> = mesh.uvs.iter().flat_map(|v| vec![v.x, v.y]).collect();
+            let view = self.push_floats(&floats, Some(ARRAY_BUFFER));
+            attributes.texcoord_0 = Some(self.push_accessor(view, FLOAT, count, "VEC2", None));
+        }
+        if layout.colors {
+            let floats: Vec<f32> = mesh.colors.iter().flat_map(|v| vec![v.x, v.y, v.z, v.w]).collect();
+            let view = self.push_floats(&floats, Some(ARRAY_BUFFER));
+            attributes.color_0 = Some(self.push_accessor(view, FLOAT, count, "VEC4", None));
+        }
+        if layout.tangents {
+            let floats: Vec<f32> = mesh.tangents.iter().flat_map(|v| vec![v.x, v.y, v.z, v.w]).collect();
+            let view = self.push_floats(&floats, Some(ARRAY_BUFFER));
+            attributes.tangent = Some(self.push_accessor(view, FLOAT, count, "VEC4", None));
+        }
+
+        let index_bytes: Vec<u8> = mesh.faces.iter().flat_map(|index| index.to_le_bytes().to_vec()).collect();
+        let indices = self.push_view(&index_bytes, Some(ELEMENT_ARRAY_BUFFER));
+        let indices = self.push_accessor(indices, UNSIGNED_INT, mesh.faces.len(), "SCALAR", None);
+
+        self.document.meshes.push(GltfMesh {
+            name: name.to_string(),
+            primitives: vec![Primitive { attributes, indices, material }],
+        });
+        Some(self.document.meshes.len() - 1)
+    }
+
+    // Returns the node index. Nodes show up only once added as a root or a root's descendant.
+    pub fn add_node(&mut self, name: &str, mesh: Option<usize>) -> usize {
+        self.document.nodes.push(Node {
+            name: name.to_string(),
+            mesh,
+            children: Vec::new(),
+            translation: None,
+            scale: None,
+        });
+        self.document.nodes.len() - 1
+    }
+
+    pub fn set_translation(&mut self, node: usize, translation: Vec3) {
+        self.document.nodes[node].translation = Some([translation.x, translation.y, translation.z]);
+    }
+
+    pub fn set_scale(&mut self, node: usize, scale: Vec3) {
+        self.document.nodes[node].scale = Some([scale.x, scale.y, scale.z]);
+    }
+
+    pub fn add_child(&mut self, parent: usize, child: usize) {
+        self.document.nodes[parent].children.push(child);
+    }
+
+    pub fn add_root(&mut self, node: usize) {
+        self.document.scenes[0].nodes.push(node);
+    }
+
+    // Shows or hides nodes at each of times, in seconds, by stepping their scale between
+    // one and zero. tracks pairs a node with whether it's visible at each time.
+    pub fn add_visibility_animation(&mut self, name: &str, times: &[f32], tracks: &[(usize, Vec<bool>)]) {
+        let min = times.iter().cloned().fold(f32::INFINITY, f32::min);
+        let max = times.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+        let input = self.push_floats(times, None);
+        let input = self.push_accessor(input, FLOAT, times.len(), "SCALAR", Some((vec![min], vec![max])));
+
+        let mut animation = Animation { name: name.to_string(), channels: Vec::new(), samplers: Vec::new() };
+        for &(node, ref visible) in tracks.iter() {
+            let scales: Vec<f32> = visible.iter()
+                .flat_map(|&visible| if visible { vec![1.0; 3] } else { vec![0.0; 3] })
+                .collect();
+            let output = self.push_floats(&scales, None);
+            let output = self.push_accessor(output, FLOAT, visible.len(), "VEC3", None);
+            animation.samplers.push(AnimationSampler { input, output, interpolation: "STEP" });
+            animation.channels.push(Channel {
+                sampler: animation.samplers.len() - 1,
+                target: AnimationTarget { node, path: "scale" },
+            });
+        }
+        self.document.animations.push(animation);
+    }
+
+    // A .glb if out_path ends in .glb, otherwise glTF JSON with the buffer in a .bin of the
+    // same stem.
+    pub fn write(mut self, out_path: &Path) -> StatusOr<()> {
+        let is_glb = out_path.extension().map_or(false, |extension| extension == "glb");
+        let bin_path = out_path.with_extension("bin");
+        let uri = if is_glb {
+            None
+        } else {
+            bin_path.file_name().map(|name| name.to_string_lossy().into_owned())
+        };
+        // A buffer can't be empty, and with nothing in it there's no .bin or BIN chunk either.
+        let has_bin = !self.bin.is_empty();
+        if has_bin {
+            self.document.buffers = vec![Buffer { byte_length: self.bin.len(), uri }];
+        }
+
+        let io_error = |err| Error::io(out_path.to_path_buf(), err);
+        let json = serde_json::to_vec(&self.document).map_err(|err| io_error(io::Error::from(err)))?;
+        let mut out = file::util::buffered_writer_for(out_path).map_err(io_error)?;
+        if is_glb {
+            // Chunks are 4 byte aligned, JSON padded with spaces and binary with zeros.
+            let json_length = padded_length(json.len());
+            let bin_length = padded_length(self.bin.len());
+            let total_length = 12 + 8 + json_length + if has_bin { 8 + bin_length } else { 0 };
+            let mut glb = Vec::with_capacity(total_length);
+            for word in [GLB_MAGIC, GLB_VERSION, total_length as u32, json_length as u32, GLB_JSON_CHUNK].iter() {
+                glb.extend_from_slice(&word.to_le_bytes());
+            }
+            glb.extend_from_slice(&json);
+            glb.resize(12 + 8 + json_length, b' ');
+            if has_bin {
+                glb.extend_from_slice(&(bin_length as u32).to_le_bytes());
+                glb.extend_from_slice(&GLB_BIN_CHUNK.to_le_bytes());
+                glb.extend_from_slice(&self.bin);
+                glb.resize(total_length, 0);
+            }
+            out.write_all(&glb).map_err(io_error)?;
+        } else {
+            out.write_all(&json).map_err(io_error)?;
+            if has_bin {
+                let mut bin_out = file::util::buffered_writer_for(&bin_path).map_err(|err| Error::io(bin_path.clone(), err))?;
+                bin_out.write_all(&self.bin)
+                    .and_then(|_| bin_out.flush())
+                    .map_err(|err| Error::io(bin_path.clone(), err))?;
+            }
+        }
+        out.flush().map_err(io_error)
+    }
+
+    // Appends bytes to the buffer, 4 byte aligned as accessors require, returning the view.
+    fn push_view(&mut self, bytes: &[u8], target: Option<u32>) -> usize {
+        let byte_offset = padded_length(self.bin.len());
+        self.bin.resize(byte_offset, 0);
+        self.bin.extend_from_slice(bytes);
+        self.document.buffer_views.push(BufferView { buffer: 0, byte_offset, byte_length: bytes.len(), target });
+        self.document.buffer_views.len() - 1
+    }
+
+    fn push_floats(&mut self, floats: &[f32], target: Option<u32>) -> usize {
+        let bytes: Vec<u8> = floats.iter().flat_map(|float| float.to_bits().to_le_bytes().to_vec()).collect();
+        self.push_view(&bytes, target)
+    }
+
+    fn push_accessor(&mut self, buffer_view: usize, component_type: u32, count: usize, kind: &'static str,
+                     bounds: Option<(Vec<f32>, Vec<f32>)>) -> usize {
+        let (min, max) = match bounds {
+            Some((min, max)) => (Some(min), Some(max)),
+            None => (None, None),
+        };
+        self.document.accessors.push(Accessor { buffer_view, component_type, count, kind, min, max });
+        self.document.accessors.len() - 1
+    }
+}
+
+fn padded_length(length: usize) -> usize {
+    (length + 3) / 4 * 4
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glm;
+    use std::{
+        env,
+        fs,
+        process,
+    };
+
+    fn triangle() -> Mesh {
+        Mesh::from_geometry(
+            vec![glm::vec3(0.0, 0.0, 0.0), glm::vec3(1.0, 0.0, 0.0), glm::vec3(0.0, 2.0, -1.0)], vec![0, 1, 2])
+    }
+
+    fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+        let mut word = [0; 4];
+        word.copy_from_slice(&bytes[offset..offset + 4]);
+        u32::from_le_bytes(word)
+    }
+
+    #[test]
+    fn empty_meshes_get_no_accessors() {
+        let mut gltf = GltfBuilder::new();
+        assert_eq!(gltf.add_mesh("empty", &Mesh::new(), None), None);
+        assert_eq!(gltf.add_mesh("triangle", &triangle(), None), Some(0));
+        let node = gltf.add_node("empty", None);
+        gltf.add_root(node);
+
+        let json = serde_json::to_string(&gltf.document).unwrap();
+        assert!(!json.contains("null"), "{}", json);
+        assert!(json.contains(r#""min":[0.0,0.0,-1.0],"max":[1.0,2.0,0.0]"#), "{}", json);
+
+        // With only an empty mesh there's nothing for these arrays or a buffer to hold.
+        let path = env::temp_dir().join(format!("gltf_empty_{}.gltf", process::id()));
+        Mesh::new().write_to_gltf(&path).unwrap();
+        let json = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        for key in ["\"meshes\"", "\"accessors\"", "\"bufferViews\"", "\"buffers\"", "[]"].iter() {
+            assert!(!json.contains(key), "{} in {}", key, json);
+        }
+        assert!(!path.with_extension("bin").exists());
+    }
+
+    #[test]
+    fn glb_chunks_are_aligned_and_add_up() {
+        let mut gltf = GltfBuilder::new();
+        // 3 vertices and 3 indices leave the buffer 48 bytes, and the PNG then makes it unaligned.
+        let mesh = gltf.add_mesh("triangle", &triangle(), None);
+        gltf.add_png_material("material", &[1, 2, 3, 4, 5]);
+        let node = gltf.add_node("triangle", mesh);
+        gltf.add_root(node);
+        let path = env::temp_dir().join(format!("gltf_chunks_{}.glb", process::id()));
+        gltf.write(&path).unwrap();
+        let glb = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(u32_at(&glb, 0), GLB_MAGIC);
+        assert_eq!(u32_at(&glb, 4), GLB_VERSION);
+        assert_eq!(u32_at(&glb, 8) as usize, glb.len());
+
+        let json_length = u32_at(&glb, 12) as usize;
+        assert_eq!(u32_at(&glb, 16), GLB_JSON_CHUNK);
+        assert_eq!(json_length % 4, 0);
+        let json: serde_json::Value = serde_json::from_slice(&glb[20..20 + json_length]).unwrap();
+
+        let bin_start = 20 + json_length;
+        let bin_length = u32_at(&glb, bin_start) as usize;
+        assert_eq!(u32_at(&glb, bin_start + 4), GLB_BIN_CHUNK);
+        assert_eq!(bin_length % 4, 0);
+        assert_eq!(bin_start + 8 + bin_length, glb.len());
+        let buffer_length = json["buffers"][0]["byteLength"].as_u64().unwrap() as usize;
+        assert_eq!(buffer_length, 36 + 12 + 5);
+        assert_eq!(bin_length, padded_length(buffer_length));
+        assert!(json["buffers"][0].get("uri").is_none());
+    }
+}
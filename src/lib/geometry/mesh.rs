@@ -1,31 +1,53 @@
 use app::StatusOr;
-use file;
+use geometry::{
+    obj::{
+        self,
+        ObjModel,
+    },
+    GltfBuilder,
+};
 use gl::{
     self,
     types::*,
 };
 use glm::{
-    self,
-    Vec3
+    Vec2,
+    Vec3,
+    Vec4,
 };
 use std::{
-    io::Write,
+    path::Path,
     self,
 };
 
-fn vertex_index_parse(s: &str) -> StatusOr<VertexIndex> {
-    s.parse::<VertexIndex>().map_err(|_err| format!("Couldn't parse VertexIndex in ({}).", s))
-}
-
-fn float_parse(s: &str) -> StatusOr<f32> {
-   s.parse::<f32>().map_err(|_err| format!("Couldn't parse float in ({}).", s))
-}
-
 // An index into Mesh.vertices Vec.
 pub type VertexIndex = GLuint;
 
+// Attribute locations gl_init binds each vertex attribute to, for shaders' layout qualifiers.
+pub const POSITION_LOCATION: GLuint = 0;
+pub const NORMAL_LOCATION: GLuint = 1;
+pub const UV_LOCATION: GLuint = 2;
+pub const COLOR_LOCATION: GLuint = 3;
+pub const TANGENT_LOCATION: GLuint = 4;
+
+// Which optional attributes a Mesh has.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct VertexLayout {
+    pub normals: bool,
+    pub uvs: bool,
+    pub colors: bool,
+    pub tangents: bool,
+}
+
 pub struct Mesh {
     pub vertices: Vec<Vec3>,
+    // Each optional attribute is either empty or has one entry per vertex.
+    pub normals: Vec<Vec3>,
+    pub uvs: Vec<Vec2>,
+    // RGBA in [0, 1].
+    pub colors: Vec<Vec4>,
+    // xyz along increasing u, w = +-1 for the handedness of the bitangent.
+    pub tangents: Vec<Vec4>,
     pub faces: Vec<VertexIndex>,
     vao: GLuint,
     vbo: GLuint,
@@ -40,6 +62,10 @@ impl Mesh {
     pub fn from_geometry(vertices: Vec<Vec3>, faces: Vec<VertexIndex>) -> Mesh {
         Mesh {
             vertices,
+            normals: vec!(),
+            uvs: vec!(),
+            colors: vec!(),
+            tangents: vec!(),
             faces,
             vao: 0,
             vbo: 0,
@@ -47,7 +73,38 @@ impl Mesh {
         }
     }
 
+    // Attributes whose length doesn't match vertices are treated as missing.
+    pub fn layout(&self) -> VertexLayout {
+        let num_vertices = self.vertices.len();
+        let present = |len: usize| len > 0 && len == num_vertices;
+        VertexLayout {
+            normals: present(self.normals.len()),
+            uvs: present(self.uvs.len()),
+            colors: present(self.colors.len()),
+            tangents: present(self.tangents.len()),
+        }
+    }
+
+    // Uploads each attribute in the layout as its own block of one vertex buffer, pointed to
+    // from its *_LOCATION.
     pub fn gl_init(&mut self) {
+        let layout = self.layout();
+        let mut blocks: Vec<(GLuint, GLint, &[f32])> = vec![(POSITION_LOCATION, 3, as_floats(&self.vertices))];
+        if layout.normals {
+            blocks.push((NORMAL_LOCATION, 3, as_floats(&self.normals)));
+        }
+        if layout.uvs {
+            blocks.push((UV_LOCATION, 2, as_floats(&self.uvs)));
+        }
+        if layout.colors {
+            blocks.push((COLOR_LOCATION, 4, as_floats(&self.colors)));
+        }
+        if layout.tangents {
+            blocks.push((TANGENT_LOCATION, 4, as_floats(&self.tangents)));
+        }
+        let float_size = std::mem::size_of::<f32>();
+        let total_floats: usize = blocks.iter().map(|&(_, _, floats)| floats.len()).sum();
+
         unsafe {
             gl::GenVertexArrays(1, &mut self.vao);
             gl::GenBuffers(1, &mut self.vbo);
@@ -56,19 +113,20 @@ impl Mesh {
             gl::BindVertexArray(self.vao);
 
             gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo);
-            let vertex_array_byte_size = (self.vertices.len() * std::mem::size_of::<Vec3>()) as isize;
-            let vertices_ptr = std::mem::transmute(self.vertices.as_ptr());
-            gl::BufferData(gl::ARRAY_BUFFER, vertex_array_byte_size, vertices_ptr, gl::STATIC_DRAW);
+            gl::BufferData(gl::ARRAY_BUFFER, (total_floats * float_size) as isize, std::ptr::null(), gl::STATIC_DRAW);
+            let mut offset = 0;
+            for &(location, components, floats) in blocks.iter() {
+                let byte_size = floats.len() * float_size;
+                gl::BufferSubData(gl::ARRAY_BUFFER, offset as isize, byte_size as isize, floats.as_ptr() as *const GLvoid);
+                gl::EnableVertexAttribArray(location);
+                gl::VertexAttribPointer(location, components, gl::FLOAT, gl::FALSE,
+                                        components * float_size as GLint, offset as *const GLvoid);
+                offset += byte_size;
+            }
 
             gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, self.ebo);
             let faces_array_byte_size = (self.faces.len() * std::mem::size_of::<VertexIndex>()) as isize;
-            let faces_ptr = std::mem::transmute(self.faces.as_ptr());
-            gl::BufferData(gl::ELEMENT_ARRAY_BUFFER, faces_array_byte_size, faces_ptr, gl::STATIC_DRAW);
-
-            // At position 0, 3 floats will be passed (not normalized) w/ stride sizeof(Vec3) (no offset)
-            let size_of_vec3_i32 = std::mem::size_of::<Vec3>() as i32;
-            gl::EnableVertexAttribArray(0);
-            gl::VertexAttribPointer(0, 3, gl::FLOAT, gl::FALSE, size_of_vec3_i32, std::ptr::null());
+            gl::BufferData(gl::ELEMENT_ARRAY_BUFFER, faces_array_byte_size, self.faces.as_ptr() as *const GLvoid, gl::STATIC_DRAW);
 
             gl::BindBuffer(gl::ARRAY_BUFFER, 0);
             gl::BindVertexArray(0);
@@ -83,46 +141,32 @@ impl Mesh {
         }
     }
 
-    pub fn write_to_obj(&self, out_path: &str) -> StatusOr<()> {
-        let mut file_buffer = file::util::buffered_writer_for(out_path)
-            .map_err(|_err| format!("Failed to open file for writing: {}", out_path))?;
-        for v in self.vertices.iter() {
-            writeln!(&mut file_buffer, "v {} {} {}", v.x, v.y, v.z)
-                .map_err(|err| format!("Failed to write vertex to file: {}", err))?;
-        }
-        for face_arr in self.faces.chunks(3) {
-            writeln!(&mut file_buffer, "f {} {} {}",
-                     face_arr[0] + 1,
-                     face_arr[1] + 1,
-                     face_arr[2] + 1)
-                .map_err(|err| format!("Failed to write vertex to file: {}", err))?;
-        }
-        Ok(())
+    // Writes positions, plus colors, uvs and normals if present, as a single OBJ group.
+    pub fn write_to_obj(&self, out_path: &Path) -> StatusOr<()> {
+        obj::write_mesh_obj(self, out_path)
     }
 
-    pub fn load_obj(filepath: &'static str) -> StatusOr<Mesh> {
-        let mut mesh = Mesh::new();
-        let mut line_no = 1;
-        let lines = file::util::lines(filepath)
-            .map_err(|err| format!("Couldn't load OBJ file ({}): {}", filepath, err))?;
-        for line in lines {
-            let unwrapped_line = line.map_err(|err| format!("[Bad line in {}:{}] {}", filepath, line_no, err))?;
-            let parts: Vec<&str> = unwrapped_line.split(' ').collect();
-            if parts[0] == "v" {
-                mesh.vertices.push(glm::vec3(float_parse(parts[1])?, float_parse(parts[2])?, float_parse(parts[3])?));
-            } else if parts[0] == "f" {
-                mesh.faces.push(vertex_index_parse(parts[1])?);
-                mesh.faces.push(vertex_index_parse(parts[2])?);
-                mesh.faces.push(vertex_index_parse(parts[3])?);
-            } else {
-                return Err(format!("[Bad line in {}:{}] {}", filepath, line_no, unwrapped_line));
-            }
-            line_no += 1;
-        }
-        Ok(mesh)
+    // A .glb, or a .gltf with a .bin beside it, by out_path's extension.
+    pub fn write_to_gltf(&self, out_path: &Path) -> StatusOr<()> {
+        let mut gltf = GltfBuilder::new();
+        let mesh = gltf.add_mesh("mesh", self, None);
+        let node = gltf.add_node("mesh", mesh);
+        gltf.add_root(node);
+        gltf.write(out_path)
+    }
+
+    // Every group of the file in one mesh. See ObjModel for materials and groups.
+    pub fn load_obj(filepath: &Path) -> StatusOr<Mesh> {
+        Ok(ObjModel::load(filepath)?.into_mesh())
     }
 }
 
+// glm vectors are plain f32s, so a slice of them is a slice of floats.
+fn as_floats<T>(vectors: &[T]) -> &[f32] {
+    let floats_per_vector = std::mem::size_of::<T>() / std::mem::size_of::<f32>();
+    unsafe { std::slice::from_raw_parts(vectors.as_ptr() as *const f32, vectors.len() * floats_per_vector) }
+}
+
 impl Drop for Mesh {
     fn drop(&mut self) {
         unsafe {
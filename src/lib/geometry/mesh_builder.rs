@@ -12,17 +12,73 @@ use std::{
     }
 };
 
+// Vertices are shared between faces only when position, normal and uv all match, so
+// faces meeting at an angle keep their own flat normals.
 #[derive(PartialEq, Eq, Hash)]
-struct MeshBuilderVertex(u32, u32, u32);
+struct MeshBuilderVertex([u32; 8]);
 
 impl MeshBuilderVertex {
-    pub fn from_vec(vec3: glm::Vec3) -> MeshBuilderVertex {
-        MeshBuilderVertex(vec3.x.to_bits(), vec3.y.to_bits(), vec3.z.to_bits())
+    pub fn new(position: glm::Vec3, normal: glm::Vec3, uv: glm::Vec2) -> MeshBuilderVertex {
+        MeshBuilderVertex([position.x.to_bits(), position.y.to_bits(), position.z.to_bits(),
+                           normal.x.to_bits(), normal.y.to_bits(), normal.z.to_bits(),
+                           uv.x.to_bits(), uv.y.to_bits()])
     }
 
-    pub fn into_vec(self) -> glm::Vec3 {
-       glm::vec3(f32::from_bits(self.0), f32::from_bits(self.1), f32::from_bits(self.2))
+    pub fn position(&self) -> glm::Vec3 {
+       glm::vec3(f32::from_bits(self.0[0]), f32::from_bits(self.0[1]), f32::from_bits(self.0[2]))
     }
+
+    pub fn normal(&self) -> glm::Vec3 {
+       glm::vec3(f32::from_bits(self.0[3]), f32::from_bits(self.0[4]), f32::from_bits(self.0[5]))
+    }
+
+    pub fn uv(&self) -> glm::Vec2 {
+       glm::vec2(f32::from_bits(self.0[6]), f32::from_bits(self.0[7]))
+    }
+}
+
+// How builders assign texture coordinates.
+#[derive(Clone, Copy)]
+pub enum UvMapping {
+    // Each quad spans the whole texture, and each triangle its lower left half.
+    PerFace,
+    // Projects positions onto the xy plane: uv = (xy + offset) / scale, with v running down
+    // the image to match Texture's top row first upload. Sprite meshes use this to address
+    // their segment of the sheet.
+    PlanarXY { offset: glm::Vec2, scale: glm::Vec2 },
+}
+
+impl UvMapping {
+    fn uv(&self, position: glm::Vec3, per_face_uv: glm::Vec2) -> glm::Vec2 {
+        match *self {
+            UvMapping::PerFace => per_face_uv,
+            UvMapping::PlanarXY { offset, scale } =>
+                glm::vec2((position.x + offset.x) / scale.x, 1.0 - (position.y + offset.y) / scale.y),
+        }
+    }
+}
+
+// The unit normal of triangle v0, v1, v2 wound counter-clockwise, or zero if degenerate.
+fn flat_normal(v0: glm::Vec3, v1: glm::Vec3, v2: glm::Vec3) -> glm::Vec3 {
+    let normal = glm::builtin::cross(v1 - v0, v2 - v0);
+    let length = glm::builtin::length(normal);
+    if length > 0.0 {
+        normal / length
+    } else {
+        normal
+    }
+}
+
+// Turns the builder's vertex table into a Mesh with positions, normals and uvs.
+fn build_mesh(vertex_indices: HashMap<MeshBuilderVertex, VertexIndex>, faces: Vec<VertexIndex>) -> Mesh {
+    let mut reverse_index = BTreeMap::new();
+    for (vertex, idx) in vertex_indices.into_iter() {
+        reverse_index.insert(idx, vertex);
+    }
+    let mut mesh = Mesh::from_geometry(reverse_index.values().map(|vertex| vertex.position()).collect(), faces);
+    mesh.normals = reverse_index.values().map(|vertex| vertex.normal()).collect();
+    mesh.uvs = reverse_index.values().map(|vertex| vertex.uv()).collect();
+    mesh
 }
 
 struct OriginalFace(VertexIndex, VertexIndex, VertexIndex, VertexIndex);
@@ -38,19 +94,25 @@ impl SortedFace {
     }
 }
 
-// Constructs a Mesh from a series of quad-vertex faces.
+// Constructs a Mesh with flat normals and uvs from a series of quad-vertex faces.
 pub struct QuadMeshBuilder {
     vertex_indices: HashMap<MeshBuilderVertex, VertexIndex>,
     face_bag: HashSet<SortedFace>,
     final_faces: Vec<OriginalFace>,
+    uv_mapping: UvMapping,
 }
 
 impl QuadMeshBuilder {
     pub fn new() -> QuadMeshBuilder {
+        QuadMeshBuilder::with_uv_mapping(UvMapping::PerFace)
+    }
+
+    pub fn with_uv_mapping(uv_mapping: UvMapping) -> QuadMeshBuilder {
         QuadMeshBuilder {
             vertex_indices: HashMap::new(),
             face_bag: HashSet::new(),
             final_faces: vec!(),
+            uv_mapping,
         }
     }
 
@@ -60,13 +122,19 @@ impl QuadMeshBuilder {
         *value
     }
 
+    // The face is front facing when its corners are as named, seen from the front.
     pub fn add_face(&mut self,
                     top_left: glm::Vec3, top_right: glm::Vec3,
                     bottom_left: glm::Vec3, bottom_right: glm::Vec3) {
-        let tl_idx= self.add_vertex(MeshBuilderVertex::from_vec(top_left));
-        let tr_idx= self.add_vertex(MeshBuilderVertex::from_vec(top_right));
-        let bl_idx= self.add_vertex(MeshBuilderVertex::from_vec(bottom_left));
-        let br_idx= self.add_vertex(MeshBuilderVertex::from_vec(bottom_right));
+        let normal = flat_normal(top_left, bottom_left, bottom_right);
+        let mut vertex = |position: glm::Vec3, u: f32, v: f32| {
+            let uv = self.uv_mapping.uv(position, glm::vec2(u, v));
+            self.add_vertex(MeshBuilderVertex::new(position, normal, uv))
+        };
+        let tl_idx = vertex(top_left, 0.0, 0.0);
+        let tr_idx = vertex(top_right, 1.0, 0.0);
+        let bl_idx = vertex(bottom_left, 0.0, 1.0);
+        let br_idx = vertex(bottom_right, 1.0, 1.0);
 
         let sorted_face = SortedFace::new(tl_idx, tr_idx, bl_idx, br_idx);
         if self.face_bag.insert(sorted_face) {
@@ -75,17 +143,12 @@ impl QuadMeshBuilder {
     }
 
     pub fn build(self) -> Mesh {
-        let mut reverse_index = BTreeMap::new();
-        for (vertex, idx) in self.vertex_indices.into_iter() {
-            reverse_index.insert(idx, vertex.into_vec());
-        }
-        let vertices: Vec<_> = reverse_index.values().cloned().collect();
         let mut faces = Vec::with_capacity(6 * self.final_faces.len());
         for &OriginalFace(v0, v1, v2, v3) in self.final_faces.iter() {
             let mut two_triangles = vec!(v0, v2, v3, v1, v0, v3);
             faces.append(&mut two_triangles);
         }
-        Mesh::from_geometry(vertices, faces)
+        build_mesh(self.vertex_indices, faces)
     }
 }
 
@@ -102,19 +165,25 @@ impl SortedTriangle {
     }
 }
 
-// Constructs a Mesh from a series of triangles.
+// Constructs a Mesh with flat normals and uvs from a series of triangles.
 pub struct TriMeshBuilder {
     vertex_indices: HashMap<MeshBuilderVertex, VertexIndex>,
     face_bag: HashSet<SortedTriangle>,
     final_faces: Vec<OriginalTriangle>,
+    uv_mapping: UvMapping,
 }
 
 impl TriMeshBuilder {
     pub fn new() -> TriMeshBuilder {
+        TriMeshBuilder::with_uv_mapping(UvMapping::PerFace)
+    }
+
+    pub fn with_uv_mapping(uv_mapping: UvMapping) -> TriMeshBuilder {
         TriMeshBuilder {
             vertex_indices: HashMap::new(),
             face_bag: HashSet::new(),
             final_faces: vec!(),
+            uv_mapping,
         }
     }
 
@@ -124,10 +193,16 @@ impl TriMeshBuilder {
         *value
     }
 
+    // Counter-clockwise triangles face the viewer.
     pub fn add_triangle(&mut self, v0: glm::Vec3, v1: glm::Vec3, v2: glm::Vec3) {
-        let idx_0 = self.add_vertex(MeshBuilderVertex::from_vec(v0));
-        let idx_1 = self.add_vertex(MeshBuilderVertex::from_vec(v1));
-        let idx_2 = self.add_vertex(MeshBuilderVertex::from_vec(v2));
+        let normal = flat_normal(v0, v1, v2);
+        let mut vertex = |position: glm::Vec3, u: f32, v: f32| {
+            let uv = self.uv_mapping.uv(position, glm::vec2(u, v));
+            self.add_vertex(MeshBuilderVertex::new(position, normal, uv))
+        };
+        let idx_0 = vertex(v0, 0.0, 1.0);
+        let idx_1 = vertex(v1, 1.0, 1.0);
+        let idx_2 = vertex(v2, 0.0, 0.0);
 
         let sorted_face = SortedTriangle::new(idx_0, idx_1, idx_2);
         if self.face_bag.insert(sorted_face) {
@@ -136,16 +211,11 @@ impl TriMeshBuilder {
     }
 
     pub fn build(self) -> Mesh {
-        let mut reverse_index = BTreeMap::new();
-        for (vertex, idx) in self.vertex_indices.into_iter() {
-            reverse_index.insert(idx, vertex.into_vec());
-        }
-        let vertices: Vec<_> = reverse_index.values().cloned().collect();
         let mut faces = Vec::with_capacity(3 * self.final_faces.len());
         for &OriginalTriangle(v0, v1, v2) in self.final_faces.iter() {
             let mut triangle = vec!(v0, v1, v2);
             faces.append(&mut triangle);
         }
-        Mesh::from_geometry(vertices, faces)
+        build_mesh(self.vertex_indices, faces)
     }
 }
@@ -1,10 +1,22 @@
+pub mod gltf;
 pub mod mesh;
 pub mod mesh_builder;
 pub mod mesh_cuboid;
+pub mod obj;
+pub mod polygon;
+pub mod scene_graph;
 pub mod transform;
 
+pub use self::gltf::GltfBuilder;
 pub use self::mesh::Mesh;
+pub use self::mesh::VertexLayout;
 pub use self::mesh_builder::QuadMeshBuilder;
 pub use self::mesh_builder::TriMeshBuilder;
+pub use self::mesh_builder::UvMapping;
 pub use self::mesh_cuboid::MeshCuboid;
+pub use self::obj::ObjGroup;
+pub use self::obj::ObjMaterial;
+pub use self::obj::ObjModel;
+pub use self::scene_graph::SceneGraph;
+pub use self::scene_graph::SceneNodeId;
 pub use self::transform::Transform;
@@ -0,0 +1,588 @@
+use app::{
+    Error,
+    StatusOr,
+};
+use file;
+use geometry::{
+    mesh::VertexIndex,
+    polygon,
+    Mesh,
+};
+use glm::{
+    self,
+    Vec2,
+    Vec3,
+    Vec4,
+};
+use std::{
+    collections::BTreeMap,
+    io::Write,
+    path::{
+        Path,
+        PathBuf,
+    },
+};
+
+// A newmtl entry of an MTL file. Colors are RGB in [0, 1].
+#[derive(Clone, Debug)]
+pub struct ObjMaterial {
+    pub name: String,
+    // Ka
+    pub ambient: Vec3,
+    // Kd
+    pub diffuse: Vec3,
+    // Ks
+    pub specular: Vec3,
+    // Ke
+    pub emissive: Vec3,
+    // Ns
+    pub shininess: f32,
+    // d, or 1 - Tr
+    pub opacity: f32,
+    // illum
+    pub illumination: u32,
+    // map_Kd, relative to the working directory rather than the MTL file.
+    pub diffuse_map: Option<PathBuf>,
+}
+
+impl ObjMaterial {
+    // The MTL spec's defaults for anything a newmtl entry leaves out.
+    pub fn new(name: String) -> ObjMaterial {
+        ObjMaterial {
+            name,
+            ambient: glm::vec3(0.2, 0.2, 0.2),
+            diffuse: glm::vec3(0.8, 0.8, 0.8),
+            specular: glm::vec3(1.0, 1.0, 1.0),
+            emissive: glm::vec3(0.0, 0.0, 0.0),
+            shininess: 0.0,
+            opacity: 1.0,
+            illumination: 2,
+            diffuse_map: None,
+        }
+    }
+}
+
+// The faces between two o/g/usemtl statements, as a triangle mesh with one material.
+pub struct ObjGroup {
+    // The last o or g name, or empty before the first one.
+    pub name: String,
+    pub material: Option<String>,
+    pub mesh: Mesh,
+}
+
+// An OBJ file with the materials of its mtllib statements.
+//
+// Polygons are triangulated on load. Each group's mesh has normals and uvs only if every
+// face corner in it named a vn and vt, and colors only if every vertex it uses had the
+// common "v x y z r g b" extension. Lines, points, free-form geometry and smoothing groups
+// are ignored.
+pub struct ObjModel {
+    pub groups: Vec<ObjGroup>,
+    pub materials: Vec<ObjMaterial>,
+}
+
+impl ObjModel {
+    pub fn new() -> ObjModel {
+        ObjModel {
+            groups: Vec::new(),
+            materials: Vec::new(),
+        }
+    }
+
+    pub fn load(filepath: &Path) -> StatusOr<ObjModel> {
+        let mut parser = ObjParser::new(filepath);
+        for (line_no, line) in logical_lines(filepath)? {
+            parser.parse_line(line_no, &line)?;
+        }
+        Ok(parser.finish())
+    }
+
+    pub fn material(&self, name: &str) -> Option<&ObjMaterial> {
+        self.materials.iter().find(|material| material.name == name)
+    }
+
+    // All groups in one mesh. An attribute survives only if every group has it.
+    pub fn into_mesh(self) -> Mesh {
+        let mut groups = self.groups.into_iter().map(|group| group.mesh);
+        let mut merged = match groups.next() {
+            Some(mesh) => mesh,
+            None => return Mesh::new(),
+        };
+        for mesh in groups {
+            let merged_layout = merged.layout();
+            let layout = mesh.layout();
+            let offset = merged.vertices.len() as VertexIndex;
+            merged.faces.extend(mesh.faces.iter().map(|index| index + offset));
+            merged.vertices.extend_from_slice(&mesh.vertices);
+            merge_attribute(&mut merged.normals, merged_layout.normals && layout.normals, &mesh.normals);
+            merge_attribute(&mut merged.uvs, merged_layout.uvs && layout.uvs, &mesh.uvs);
+            merge_attribute(&mut merged.colors, merged_layout.colors && layout.colors, &mesh.colors);
+            merge_attribute(&mut merged.tangents, merged_layout.tangents && layout.tangents, &mesh.tangents);
+        }
+        merged
+    }
+
+    // Writes obj_path and, if there are materials, an MTL file beside it with the same stem.
+    pub fn write(&self, obj_path: &Path) -> StatusOr<()> {
+        let io_error = |err| Error::io(obj_path.to_path_buf(), err);
+        let mut out = file::util::buffered_writer_for(obj_path).map_err(io_error)?;
+        if !self.materials.is_empty() {
+            let mtl_path = obj_path.with_extension("mtl");
+            write_mtl(&self.materials, &mtl_path)?;
+            let mtl_name = mtl_path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+            writeln!(out, "mtllib {}", mtl_name).map_err(io_error)?;
+        }
+        let mut offsets = ObjOffsets::default();
+        for group in self.groups.iter() {
+            if !group.name.is_empty() {
+                writeln!(out, "o {}", group.name).map_err(io_error)?;
+            }
+            if let Some(ref material) = group.material {
+                writeln!(out, "usemtl {}", material).map_err(io_error)?;
+            }
+            write_mesh(&mut out, &group.mesh, &mut offsets).map_err(io_error)?;
+        }
+        out.flush().map_err(io_error)
+    }
+}
+
+fn merge_attribute<T: Copy>(merged: &mut Vec<T>, keep: bool, other: &[T]) {
+    if keep {
+        merged.extend_from_slice(other);
+    } else {
+        merged.clear();
+    }
+}
+
+// Writes one anonymous group, as Mesh::write_to_obj does.
+pub fn write_mesh_obj(mesh: &Mesh, out_path: &Path) -> StatusOr<()> {
+    let io_error = |err| Error::io(out_path.to_path_buf(), err);
+    let mut out = file::util::buffered_writer_for(out_path).map_err(io_error)?;
+    write_mesh(&mut out, mesh, &mut ObjOffsets::default()).map_err(io_error)?;
+    out.flush().map_err(io_error)
+}
+
+// How many v, vt and vn lines earlier groups wrote, since indices are file-wide.
+#[derive(Default)]
+struct ObjOffsets {
+    positions: usize,
+    uvs: usize,
+    normals: usize,
+}
+
+// Writes a v line per vertex plus a vt and vn per vertex if present, so a corner's three
+// indices are all the same. Colors go on the v lines without alpha. Tangents aren't written.
+fn write_mesh<W: Write>(out: &mut W, mesh: &Mesh, offsets: &mut ObjOffsets) -> std::io::Result<()> {
+    let layout = mesh.layout();
+    for (i, v) in mesh.vertices.iter().enumerate() {
+        if layout.colors {
+            let color = mesh.colors[i];
+            writeln!(out, "v {} {} {} {} {} {}", v.x, v.y, v.z, color.x, color.y, color.z)?;
+        } else {
+            writeln!(out, "v {} {} {}", v.x, v.y, v.z)?;
+        }
+    }
+    if layout.uvs {
+        for uv in mesh.uvs.iter() {
+            writeln!(out, "vt {} {}", uv.x, uv.y)?;
+        }
+    }
+    if layout.normals {
+        for normal in mesh.normals.iter() {
+            writeln!(out, "vn {} {} {}", normal.x, normal.y, normal.z)?;
+        }
+    }
+    let corner = |index: VertexIndex| {
+        let index = index as usize + 1;
+        match (layout.uvs, layout.normals) {
+            (false, false) => format!("{}", index + offsets.positions),
+            (true, false) => format!("{}/{}", index + offsets.positions, index + offsets.uvs),
+            (false, true) => format!("{}//{}", index + offsets.positions, index + offsets.normals),
+            (true, true) => format!("{}/{}/{}", index + offsets.positions, index + offsets.uvs, index + offsets.normals),
+        }
+    };
+    for face in mesh.faces.chunks(3) {
+        writeln!(out, "f {} {} {}", corner(face[0]), corner(face[1]), corner(face[2]))?;
+    }
+    offsets.positions += mesh.vertices.len();
+    if layout.uvs {
+        offsets.uvs += mesh.vertices.len();
+    }
+    if layout.normals {
+        offsets.normals += mesh.vertices.len();
+    }
+    Ok(())
+}
+
+fn write_mtl(materials: &[ObjMaterial], mtl_path: &Path) -> StatusOr<()> {
+    let io_error = |err| Error::io(mtl_path.to_path_buf(), err);
+    let mut out = file::util::buffered_writer_for(mtl_path).map_err(io_error)?;
+    let mtl_dir = mtl_path.parent().unwrap_or_else(|| Path::new(""));
+    for material in materials.iter() {
+        let rgb = |v: Vec3| format!("{} {} {}", v.x, v.y, v.z);
+        writeln!(out, "newmtl {}", material.name).map_err(io_error)?;
+        writeln!(out, "Ka {}", rgb(material.ambient)).map_err(io_error)?;
+        writeln!(out, "Kd {}", rgb(material.diffuse)).map_err(io_error)?;
+        writeln!(out, "Ks {}", rgb(material.specular)).map_err(io_error)?;
+        writeln!(out, "Ke {}", rgb(material.emissive)).map_err(io_error)?;
+        writeln!(out, "Ns {}", material.shininess).map_err(io_error)?;
+        writeln!(out, "d {}", material.opacity).map_err(io_error)?;
+        writeln!(out, "illum {}", material.illumination).map_err(io_error)?;
+        if let Some(ref map) = material.diffuse_map {
+            // Loading joins map paths onto the MTL's directory, so undo that where we can.
+            let map = map.strip_prefix(mtl_dir).unwrap_or(map);
+            writeln!(out, "map_Kd {}", map.display()).map_err(io_error)?;
+        }
+        writeln!(out).map_err(io_error)?;
+    }
+    out.flush().map_err(io_error)
+}
+
+// A face corner's position, vt and vn, as 0-based indices.
+#[derive(Clone, Copy, Eq, Ord, PartialEq, PartialOrd)]
+struct Corner {
+    position: usize,
+    uv: Option<usize>,
+    normal: Option<usize>,
+}
+
+struct ObjParser<'a> {
+    path: &'a Path,
+    positions: Vec<Vec3>,
+    // One per position.
+    position_colors: Vec<Option<Vec4>>,
+    uvs: Vec<Vec2>,
+    normals: Vec<Vec3>,
+    model: ObjModel,
+    group_name: String,
+    group_material: Option<String>,
+    // Three per triangle.
+    group_corners: Vec<Corner>,
+}
+
+impl <'a> ObjParser<'a> {
+    fn new(path: &'a Path) -> ObjParser<'a> {
+        ObjParser {
+            path,
+            positions: Vec::new(),
+            position_colors: Vec::new(),
+            uvs: Vec::new(),
+            normals: Vec::new(),
+            model: ObjModel::new(),
+            group_name: String::new(),
+            group_material: None,
+            group_corners: Vec::new(),
+        }
+    }
+
+    fn parse_line(&mut self, line_no: usize, line: &str) -> StatusOr<()> {
+        let path = self.path;
+        let parse_error = |reason: String| Error::Parse { path: path.to_path_buf(), line: line_no, reason };
+        let mut parts = line.split_whitespace();
+        let keyword = match parts.next() {
+            Some(keyword) => keyword,
+            None => return Ok(()),
+        };
+        let args: Vec<&str> = parts.collect();
+        match keyword {
+            "v" => {
+                let floats = parse_floats(&args).map_err(parse_error)?;
+                match floats.len() {
+                    // An optional w, which we have no use for.
+                    3 | 4 => self.position_colors.push(None),
+                    6 => self.position_colors.push(Some(glm::vec4(floats[3], floats[4], floats[5], 1.0))),
+                    _ => return Err(parse_error(format!("Expected 3, 4 or 6 numbers in ({}).", line))),
+                }
+                self.positions.push(glm::vec3(floats[0], floats[1], floats[2]));
+            },
+            "vt" => {
+                let floats = parse_floats(&args).map_err(parse_error)?;
+                if floats.is_empty() || floats.len() > 3 {
+                    return Err(parse_error(format!("Expected 1 to 3 numbers in ({}).", line)));
+                }
+                self.uvs.push(glm::vec2(floats[0], floats.get(1).cloned().unwrap_or(0.0)));
+            },
+            "vn" => {
+                let floats = parse_floats(&args).map_err(parse_error)?;
+                if floats.len() != 3 {
+                    return Err(parse_error(format!("Expected 3 numbers in ({}).", line)));
+                }
+                self.normals.push(glm::vec3(floats[0], floats[1], floats[2]));
+            },
+            "f" => {
+                if args.len() < 3 {
+                    return Err(parse_error(format!("A face needs at least 3 corners in ({}).", line)));
+                }
+                let mut corners = Vec::with_capacity(args.len());
+                for arg in args.iter() {
+                    corners.push(self.parse_corner(arg).map_err(parse_error)?);
+                }
+                let points: Vec<Vec3> = corners.iter().map(|corner| self.positions[corner.position]).collect();
+                for triangle in polygon::triangulate(&points).iter() {
+                    self.group_corners.extend(triangle.iter().map(|&i| corners[i]));
+                }
+            },
+            "o" | "g" => {
+                self.finish_group();
+                self.group_name = args.join(" ");
+            },
+            "usemtl" => {
+                let material = args.join(" ");
+                if self.group_material.as_ref() != Some(&material) {
+                    self.finish_group();
+                    self.group_material = Some(material);
+                }
+            },
+            "mtllib" => {
+                let dir = self.path.parent().unwrap_or_else(|| Path::new(""));
+                for name in args.iter() {
+                    let materials = load_mtl(&dir.join(name))?;
+                    self.model.materials.extend(materials);
+                }
+            },
+            // Smoothing groups, lines, points, free-form surfaces and so on.
+            _ => {},
+        }
+        Ok(())
+    }
+
+    // "v", "v/vt", "v//vn" or "v/vt/vn", each 1-based or negative from the end.
+    fn parse_corner(&self, arg: &str) -> Result<Corner, String> {
+        let indices: Vec<&str> = arg.split('/').collect();
+        if indices.len() > 3 {
+            return Err(format!("Couldn't parse face corner ({}).", arg));
+        }
+        let optional = |i: usize, count: usize| match indices.get(i) {
+            Some(index) if !index.is_empty() => resolve_index(index, count).map(Some),
+            _ => Ok(None),
+        };
+        Ok(Corner {
+            position: resolve_index(indices[0], self.positions.len())?,
+            uv: optional(1, self.uvs.len())?,
+            normal: optional(2, self.normals.len())?,
+        })
+    }
+
+    fn finish_group(&mut self) {
+        if self.group_corners.is_empty() {
+            return;
+        }
+        // Numbering vertices in order of their v indices keeps write_to_obj's output in its
+        // original order.
+        let mut vertex_indices = BTreeMap::new();
+        for corner in self.group_corners.iter() {
+            vertex_indices.insert(*corner, 0);
+        }
+        for (i, index) in vertex_indices.values_mut().enumerate() {
+            *index = i as VertexIndex;
+        }
+
+        let corners: Vec<Corner> = vertex_indices.keys().cloned().collect();
+        let vertices = corners.iter().map(|corner| self.positions[corner.position]).collect();
+        let faces = self.group_corners.iter().map(|corner| vertex_indices[corner]).collect();
+        let mut mesh = Mesh::from_geometry(vertices, faces);
+        if corners.iter().all(|corner| corner.normal.is_some()) {
+            mesh.normals = corners.iter().filter_map(|corner| corner.normal).map(|i| self.normals[i]).collect();
+        }
+        if corners.iter().all(|corner| corner.uv.is_some()) {
+            mesh.uvs = corners.iter().filter_map(|corner| corner.uv).map(|i| self.uvs[i]).collect();
+        }
+        if corners.iter().all(|corner| self.position_colors[corner.position].is_some()) {
+            mesh.colors = corners.iter().filter_map(|corner| self.position_colors[corner.position]).collect();
+        }
+
+        self.model.groups.push(ObjGroup {
+            name: self.group_name.clone(),
+            material: self.group_material.clone(),
+            mesh,
+        });
+        self.group_corners.clear();
+    }
+
+    fn finish(mut self) -> ObjModel {
+        self.finish_group();
+        self.model
+    }
+}
+
+fn load_mtl(mtl_path: &Path) -> StatusOr<Vec<ObjMaterial>> {
+    let dir = mtl_path.parent().unwrap_or_else(|| Path::new(""));
+    let mut materials: Vec<ObjMaterial> = Vec::new();
+    for (line_no, line) in logical_lines(mtl_path)? {
+        let parse_error = |reason: String| Error::Parse { path: mtl_path.to_path_buf(), line: line_no, reason };
+        let mut parts = line.split_whitespace();
+        let keyword = match parts.next() {
+            Some(keyword) => keyword,
+            None => continue,
+        };
+        let args: Vec<&str> = parts.collect();
+        if keyword == "newmtl" {
+            materials.push(ObjMaterial::new(args.join(" ")));
+            continue;
+        }
+        let material = match materials.last_mut() {
+            Some(material) => material,
+            None => return Err(parse_error(format!("({}) comes before any newmtl.", line))),
+        };
+        let rgb = || -> StatusOr<Vec3> {
+            let floats = parse_floats(&args).map_err(parse_error)?;
+            match floats.len() {
+                1 => Ok(glm::vec3(floats[0], floats[0], floats[0])),
+                3 => Ok(glm::vec3(floats[0], floats[1], floats[2])),
+                _ => Err(parse_error(format!("Expected 1 or 3 numbers in ({}).", line))),
+            }
+        };
+        let scalar = || -> StatusOr<f32> {
+            match parse_floats(&args).map_err(parse_error)?.as_slice() {
+                &[value] => Ok(value),
+                _ => Err(parse_error(format!("Expected 1 number in ({}).", line))),
+            }
+        };
+        match keyword {
+            "Ka" => material.ambient = rgb()?,
+            "Kd" => material.diffuse = rgb()?,
+            "Ks" => material.specular = rgb()?,
+            "Ke" => material.emissive = rgb()?,
+            "Ns" => material.shininess = scalar()?,
+            "d" => material.opacity = scalar()?,
+            "Tr" => material.opacity = 1.0 - scalar()?,
+            "illum" => material.illumination = scalar()? as u32,
+            // Options like -bm come first, so the file name is last.
+            "map_Kd" => match args.last() {
+                Some(name) => material.diffuse_map = Some(dir.join(name)),
+                None => return Err(parse_error(format!("Missing file name in ({}).", line))),
+            },
+            // Other maps, Ni, Tf and so on.
+            _ => {},
+        }
+    }
+    Ok(materials)
+}
+
+// Non-empty lines without comments, joining lines that end in a backslash, each with the
+// 1-based number of its first physical line.
+fn logical_lines(path: &Path) -> StatusOr<Vec<(usize, String)>> {
+    let lines = file::util::lines(path).map_err(|err| Error::io(path.to_path_buf(), err))?;
+    let mut logical = Vec::new();
+    let mut pending: Option<(usize, String)> = None;
+    for (i, line) in lines.enumerate() {
+        let line = line.map_err(|err| Error::io(path.to_path_buf(), err))?;
+        let line = match line.find('#') {
+            Some(comment_start) => &line[..comment_start],
+            None => &line[..],
+        };
+        let line = line.trim_end();
+        let (line, continues) = match line.strip_suffix('\\') {
+            Some(line) => (line, true),
+            None => (line, false),
+        };
+        let (line_no, mut joined) = pending.take().unwrap_or_else(|| (i + 1, String::new()));
+        joined.push(' ');
+        joined.push_str(line);
+        if continues {
+            pending = Some((line_no, joined));
+        } else if !joined.trim().is_empty() {
+            logical.push((line_no, joined.trim().to_string()));
+        }
+    }
+    if let Some((line_no, joined)) = pending {
+        if !joined.trim().is_empty() {
+            logical.push((line_no, joined.trim().to_string()));
+        }
+    }
+    Ok(logical)
+}
+
+fn parse_floats(args: &[&str]) -> Result<Vec<f32>, String> {
+    args.iter()
+        .map(|arg| arg.parse::<f32>().map_err(|_err| format!("Couldn't parse float in ({}).", arg)))
+        .collect()
+}
+
+// Turns a 1-based or negative (counting back from the latest) OBJ index into a 0-based one.
+fn resolve_index(s: &str, count: usize) -> Result<usize, String> {
+    let index = s.parse::<i64>().map_err(|_err| format!("Couldn't parse index in ({}).", s))?;
+    let resolved = if index < 0 { count as i64 + index } else { index - 1 };
+    if index == 0 || resolved < 0 || resolved >= count as i64 {
+        return Err(format!("Index {} is out of range, there are {} so far.", index, count));
+    }
+    Ok(resolved as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        env,
+        fs,
+        process,
+    };
+
+    // A quad with its own vt and vn, then a pentagon whose corners count back from the end
+    // and share one vt and vn.
+    const POLYGONS: &'static str = "\
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+vt 0 0
+vt 1 0
+vt 1 1
+vt 0 1
+vn 0 0 1
+f 1/1/1 2/2/1 3/3/1 4/4/1
+v 2 0 0.5
+v 3 0 0.5
+v 3.5 1 0.5
+v 2.5 2 0.5
+v 1.5 1 0.5
+vt 0.25 0.75
+vn 0 0 -1
+f -5/-1/-1 -4/-1/-1 -3/-1/-1 -2/-1/-1 -1/-1/-1
+";
+
+    fn temp_path(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("obj_{}_{}.obj", process::id(), name))
+    }
+
+    fn load(contents: &str, name: &str) -> Mesh {
+        let path = temp_path(name);
+        fs::write(&path, contents).unwrap();
+        let mesh = Mesh::load_obj(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        mesh
+    }
+
+    #[test]
+    fn reads_polygons_and_negative_indices() {
+        let mesh = load(POLYGONS, "read");
+        assert_eq!(mesh.vertices, vec![
+            glm::vec3(0.0, 0.0, 0.0), glm::vec3(1.0, 0.0, 0.0), glm::vec3(1.0, 1.0, 0.0), glm::vec3(0.0, 1.0, 0.0),
+            glm::vec3(2.0, 0.0, 0.5), glm::vec3(3.0, 0.0, 0.5), glm::vec3(3.5, 1.0, 0.5), glm::vec3(2.5, 2.0, 0.5),
+            glm::vec3(1.5, 1.0, 0.5),
+        ]);
+        assert_eq!(&mesh.uvs[..4], &[glm::vec2(0.0, 0.0), glm::vec2(1.0, 0.0), glm::vec2(1.0, 1.0), glm::vec2(0.0, 1.0)]);
+        assert_eq!(&mesh.uvs[4..], &[glm::vec2(0.25, 0.75); 5]);
+        assert_eq!(&mesh.normals[..4], &[glm::vec3(0.0, 0.0, 1.0); 4]);
+        assert_eq!(&mesh.normals[4..], &[glm::vec3(0.0, 0.0, -1.0); 5]);
+        // The quad makes 2 triangles and the pentagon 3, each within its own polygon.
+        assert_eq!(mesh.faces.len(), 3 * 5);
+        assert!(mesh.faces[..6].iter().all(|&index| index < 4));
+        assert!(mesh.faces[6..].iter().all(|&index| index >= 4));
+    }
+
+    #[test]
+    fn round_trips_through_write() {
+        let mesh = load(POLYGONS, "original");
+        let path = temp_path("written");
+        mesh.write_to_obj(&path).unwrap();
+        let written = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        let reloaded = load(&written, "reloaded");
+
+        assert_eq!(reloaded.vertices, mesh.vertices);
+        assert_eq!(reloaded.normals, mesh.normals);
+        assert_eq!(reloaded.uvs, mesh.uvs);
+        assert_eq!(reloaded.faces, mesh.faces);
+    }
+}
@@ -0,0 +1,68 @@
+use glm::{
+    self,
+    Vec2,
+    Vec3,
+};
+
+// Splits a planar polygon into triangles with its winding, by ear clipping so concave
+// polygons work too. Returns indices into points.
+pub fn triangulate(points: &[Vec3]) -> Vec<[usize; 3]> {
+    if points.len() == 3 {
+        return vec![[0, 1, 2]];
+    }
+
+    // Newell's method gives the polygon's normal even if some corners are collinear.
+    let mut normal = glm::vec3(0.0, 0.0, 0.0);
+    for (i, a) in points.iter().enumerate() {
+        let b = points[(i + 1) % points.len()];
+        normal.x += (a.y - b.y) * (a.z + b.z);
+        normal.y += (a.z - b.z) * (a.x + b.x);
+        normal.z += (a.x - b.x) * (a.y + b.y);
+    }
+    // Drop the normal's largest axis, ordering the other two so the polygon is counter-clockwise.
+    let (ax, ay, az) = (normal.x.abs(), normal.y.abs(), normal.z.abs());
+    let project: Box<dyn Fn(&Vec3) -> Vec2> = if az >= ax && az >= ay {
+        if normal.z >= 0.0 { Box::new(|p| glm::vec2(p.x, p.y)) } else { Box::new(|p| glm::vec2(p.y, p.x)) }
+    } else if ax >= ay {
+        if normal.x >= 0.0 { Box::new(|p| glm::vec2(p.y, p.z)) } else { Box::new(|p| glm::vec2(p.z, p.y)) }
+    } else if normal.y >= 0.0 {
+        Box::new(|p| glm::vec2(p.z, p.x))
+    } else {
+        Box::new(|p| glm::vec2(p.x, p.z))
+    };
+    let flat: Vec<Vec2> = points.iter().map(&*project).collect();
+
+    let mut remaining: Vec<usize> = (0..points.len()).collect();
+    let mut triangles = Vec::with_capacity(points.len() - 2);
+    while remaining.len() > 3 {
+        let n = remaining.len();
+        let ear = (0..n).find(|&i| {
+            let (prev, cur, next) = (remaining[(i + n - 1) % n], remaining[i], remaining[(i + 1) % n]);
+            let (a, b, c) = (flat[prev], flat[cur], flat[next]);
+            cross_2d(b - a, c - b) > 0.0 && !remaining.iter()
+                .filter(|&&j| j != prev && j != cur && j != next)
+                .any(|&j| in_triangle(flat[j], a, b, c))
+        });
+        match ear {
+            Some(i) => {
+                triangles.push([remaining[(i + n - 1) % n], remaining[i], remaining[(i + 1) % n]]);
+                remaining.remove(i);
+            },
+            // Degenerate or self-intersecting, so settle for a fan over what's left.
+            None => break,
+        }
+    }
+    for i in 1..remaining.len() - 1 {
+        triangles.push([remaining[0], remaining[i], remaining[i + 1]]);
+    }
+    triangles
+}
+
+fn cross_2d(a: Vec2, b: Vec2) -> f32 {
+    a.x * b.y - a.y * b.x
+}
+
+// Inclusive of the edges, so ears never swallow a vertex lying on them.
+fn in_triangle(p: Vec2, a: Vec2, b: Vec2, c: Vec2) -> bool {
+    cross_2d(b - a, p - a) >= 0.0 && cross_2d(c - b, p - b) >= 0.0 && cross_2d(a - c, p - c) >= 0.0
+}
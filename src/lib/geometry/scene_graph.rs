@@ -0,0 +1,229 @@
+use geometry::Transform;
+use glm::{
+    self,
+    Mat4,
+};
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct SceneNodeId(usize);
+
+struct SceneNode {
+    local: Transform,
+    parent: Option<SceneNodeId>,
+    children: Vec<SceneNodeId>,
+    world: Mat4,
+    // Set when this node's or any ancestor's transform changed since world was computed.
+    dirty: bool,
+}
+
+// A forest of transforms, e.g. a ball attached to the athlete carrying it. Each node's
+// world matrix is its parent's world matrix times its own local model matrix.
+pub struct SceneGraph {
+    nodes: Vec<SceneNode>,
+}
+
+impl SceneGraph {
+    pub fn new() -> SceneGraph {
+        SceneGraph {
+            nodes: Vec::new(),
+        }
+    }
+
+    pub fn add(&mut self, local: Transform, parent: Option<SceneNodeId>) -> SceneNodeId {
+        let id = SceneNodeId(self.nodes.len());
+        self.nodes.push(SceneNode {
+            local,
+            parent: None,
+            children: Vec::new(),
+            world: identity(),
+            dirty: true,
+        });
+        if parent.is_some() {
+            self.set_parent(id, parent);
+        }
+        id
+    }
+
+    pub fn local(&self, id: SceneNodeId) -> &Transform {
+        &self.nodes[id.0].local
+    }
+
+    pub fn set_local(&mut self, id: SceneNodeId, local: Transform) {
+        self.nodes[id.0].local = local;
+        self.mark_dirty(id);
+    }
+
+    pub fn parent(&self, id: SceneNodeId) -> Option<SceneNodeId> {
+        self.nodes[id.0].parent
+    }
+
+    pub fn children(&self, id: SceneNodeId) -> &[SceneNodeId] {
+        &self.nodes[id.0].children
+    }
+
+    // Reattaches id under parent, or makes it a root with None. The local transform is kept,
+    // so the node moves with its new parent. Returns false, changing nothing, if parent is
+    // id or one of its descendants.
+    pub fn set_parent(&mut self, id: SceneNodeId, parent: Option<SceneNodeId>) -> bool {
+        let mut ancestor = parent;
+        while let Some(ancestor_id) = ancestor {
+            if ancestor_id == id {
+                return false;
+            }
+            ancestor = self.nodes[ancestor_id.0].parent;
+        }
+
+        if let Some(old_parent) = self.nodes[id.0].parent {
+            self.nodes[old_parent.0].children.retain(|child| *child != id);
+        }
+        if let Some(new_parent) = parent {
+            self.nodes[new_parent.0].children.push(id);
+        }
+        self.nodes[id.0].parent = parent;
+        self.mark_dirty(id);
+        true
+    }
+
+    // Flags id and its whole subtree for recomputation. Stops early at subtrees already
+    // dirty, since their descendants were flagged with them.
+    fn mark_dirty(&mut self, id: SceneNodeId) {
+        let mut stack = vec![id];
+        while let Some(node_id) = stack.pop() {
+            let node = &mut self.nodes[node_id.0];
+            if node.dirty && node_id != id {
+                continue;
+            }
+            node.dirty = true;
+            stack.extend_from_slice(&node.children);
+        }
+    }
+
+    pub fn is_dirty(&self, id: SceneNodeId) -> bool {
+        self.nodes[id.0].dirty
+    }
+
+    // id's model matrix in world space, recomputing dirty ancestors on the way.
+    pub fn world_matrix(&mut self, id: SceneNodeId) -> Mat4 {
+        if !self.nodes[id.0].dirty {
+            return self.nodes[id.0].world;
+        }
+        let parent_world = match self.nodes[id.0].parent {
+            Some(parent) => self.world_matrix(parent),
+            None => identity(),
+        };
+        let node = &mut self.nodes[id.0];
+        node.world = parent_world * node.local.model();
+        node.dirty = false;
+        node.world
+    }
+
+    // Brings every node's world matrix up to date.
+    pub fn update(&mut self) {
+        for index in 0..self.nodes.len() {
+            self.world_matrix(SceneNodeId(index));
+        }
+    }
+
+    // The last computed world matrix, which is stale if is_dirty(id).
+    pub fn cached_world_matrix(&self, id: SceneNodeId) -> &Mat4 {
+        &self.nodes[id.0].world
+    }
+}
+
+fn identity() -> Mat4 {
+    glm::mat4(
+        1.0, 0.0, 0.0, 0.0,
+        0.0, 1.0, 0.0, 0.0,
+        0.0, 0.0, 1.0, 0.0,
+        0.0, 0.0, 0.0, 1.0
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::FRAC_PI_2;
+
+    fn translated(x: f32, y: f32, z: f32) -> Transform {
+        let mut transform = Transform::new();
+        transform.position = glm::vec3(x, y, z);
+        transform
+    }
+
+    fn assert_matrix_eq(actual: &Mat4, expected: &Mat4) {
+        for column in 0..4 {
+            for row in 0..4 {
+                assert!((actual[column][row] - expected[column][row]).abs() < 1e-5,
+                        "column {} row {}: {:?}, expected {:?}", column, row, actual, expected);
+            }
+        }
+    }
+
+    fn apply(matrix: &Mat4, (x, y, z): (f32, f32, f32)) -> Vec<f32> {
+        let point = *matrix * glm::vec4(x, y, z, 1.0);
+        vec![point.x, point.y, point.z]
+    }
+
+    // A translated parent, a child rotated a quarter turn about z, and a scaled grandchild.
+    fn chain(graph: &mut SceneGraph) -> (SceneNodeId, SceneNodeId, SceneNodeId) {
+        let root = graph.add(translated(10.0, 0.0, 0.0), None);
+        let mut turned = translated(0.0, 2.0, 0.0);
+        turned.rotate_local(glm::vec3(0.0, 0.0, 1.0), FRAC_PI_2);
+        let child = graph.add(turned, Some(root));
+        let mut scaled = translated(1.0, 0.0, 0.0);
+        scaled.scale = glm::vec3(2.0, 2.0, 2.0);
+        let grandchild = graph.add(scaled, Some(child));
+        (root, child, grandchild)
+    }
+
+    #[test]
+    fn world_is_parent_world_times_local() {
+        let mut graph = SceneGraph::new();
+        let (root, child, grandchild) = chain(&mut graph);
+        let world = graph.world_matrix(grandchild);
+        let expected = graph.local(root).model() * graph.local(child).model() * graph.local(grandchild).model();
+        assert_matrix_eq(&world, &expected);
+
+        // Scaled to (2, 0, 0), moved to (3, 0, 0), turned to (0, 3, 0), moved to (0, 5, 0)
+        // and then to (10, 5, 0).
+        let point = apply(&world, (1.0, 0.0, 0.0));
+        for (actual, expected) in point.iter().zip([10.0, 5.0, 0.0].iter()) {
+            assert!((actual - expected).abs() < 1e-5, "{:?}", point);
+        }
+        assert!(!graph.is_dirty(root) && !graph.is_dirty(child) && !graph.is_dirty(grandchild));
+    }
+
+    #[test]
+    fn changing_a_node_dirties_only_its_subtree() {
+        let mut graph = SceneGraph::new();
+        let (root, child, grandchild) = chain(&mut graph);
+        let sibling = graph.add(translated(0.0, 0.0, 1.0), Some(root));
+        let sibling_child = graph.add(translated(0.0, 0.0, 1.0), Some(sibling));
+        graph.update();
+
+        graph.set_local(child, translated(0.0, -2.0, 0.0));
+        assert!(!graph.is_dirty(root));
+        assert!(graph.is_dirty(child) && graph.is_dirty(grandchild));
+        assert!(!graph.is_dirty(sibling) && !graph.is_dirty(sibling_child));
+
+        let world = graph.world_matrix(grandchild);
+        assert_eq!(apply(&world, (0.0, 0.0, 0.0)), vec![11.0, -2.0, 0.0]);
+        assert!(!graph.is_dirty(child) && !graph.is_dirty(grandchild));
+    }
+
+    #[test]
+    fn set_parent_rejects_cycles() {
+        let mut graph = SceneGraph::new();
+        let (root, child, grandchild) = chain(&mut graph);
+        assert!(!graph.set_parent(root, Some(grandchild)));
+        assert!(!graph.set_parent(child, Some(child)));
+        assert_eq!(graph.parent(root), None);
+        assert_eq!(graph.parent(child), Some(root));
+        assert!(graph.children(grandchild).is_empty());
+
+        // Moving the grandchild up to the root is fine, and leaves the child childless.
+        assert!(graph.set_parent(grandchild, Some(root)));
+        assert_eq!(graph.children(root), &[child, grandchild]);
+        assert!(graph.children(child).is_empty());
+    }
+}
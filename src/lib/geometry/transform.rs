@@ -3,10 +3,18 @@ use glm::{
     Mat4,
     Vec3
 };
+use nalgebra::{
+    self,
+    Isometry3,
+    Translation3,
+    Unit,
+    UnitQuaternion,
+    Vector3,
+};
 
+#[derive(Clone, Copy)]
 pub struct Transform {
-    pub roll: f32,
-    pub yaw: f32,
+    pub rotation: UnitQuaternion<f32>,
     pub position: Vec3,
     pub scale: Vec3,
 }
@@ -14,24 +22,121 @@ pub struct Transform {
 impl Transform {
     pub fn new() -> Transform {
         Transform {
-            roll: 0.0,
-            yaw: 0.0,
+            rotation: UnitQuaternion::identity(),
             position: glm::vec3(0.0, 0.0, 0.0),
             scale: glm::vec3(1.0, 1.0, 1.0),
         }
     }
 
+    // Takes position and rotation from isometry, which carries no scale.
+    pub fn from_isometry(isometry: &Isometry3<f32>, scale: Vec3) -> Transform {
+        let translation = isometry.translation.vector;
+        Transform {
+            rotation: isometry.rotation,
+            position: glm::vec3(translation.x, translation.y, translation.z),
+            scale,
+        }
+    }
+
+    // Position and rotation as nphysics sees them. Scale is dropped.
+    pub fn to_isometry(&self) -> Isometry3<f32> {
+        let translation = Translation3::new(self.position.x, self.position.y, self.position.z);
+        Isometry3::from_parts(translation, self.rotation)
+    }
+
+    // Copies position and rotation from isometry, keeping scale.
+    pub fn set_isometry(&mut self, isometry: &Isometry3<f32>) {
+        *self = Transform::from_isometry(isometry, self.scale);
+    }
+
+    // yaw about +y, pitch about +x, roll about +z, applied roll first: R = yaw * pitch * roll.
+    pub fn set_yaw_pitch_roll(&mut self, yaw: f32, pitch: f32, roll: f32) {
+        let axis_angle = |x: f32, y: f32, z: f32, angle: f32|
+            UnitQuaternion::from_axis_angle(&Unit::new_normalize(Vector3::new(x, y, z)), angle);
+        self.rotation = axis_angle(0.0, 1.0, 0.0, yaw) * axis_angle(1.0, 0.0, 0.0, pitch) * axis_angle(0.0, 0.0, 1.0, roll);
+    }
+
+    // Rotates by angle radians about axis, in the transform's own frame.
+    pub fn rotate_local(&mut self, axis: Vec3, angle: f32) {
+        let axis = Unit::new_normalize(Vector3::new(axis.x, axis.y, axis.z));
+        self.rotation = self.rotation * UnitQuaternion::from_axis_angle(&axis, angle);
+    }
+
+    // Blends two transforms, e.g. the last two physics steps. alpha = 0 gives previous.
+    pub fn interpolate(previous: &Transform, current: &Transform, alpha: f32) -> Transform {
+        // nlerp is close enough to slerp between physics steps. q and -q are the same rotation,
+        // so blend towards whichever is in previous's hemisphere to take the short way round
+        // and never normalize a near-zero quaternion.
+        let current_rotation = if previous.rotation.coords.dot(&current.rotation.coords) < 0.0 {
+            UnitQuaternion::new_unchecked(-current.rotation.unwrap())
+        } else {
+            current.rotation
+        };
+        Transform {
+            rotation: previous.rotation.nlerp(&current_rotation, alpha),
+            position: glm::mix(previous.position, current.position, glm::vec3(alpha, alpha, alpha)),
+            scale: glm::mix(previous.scale, current.scale, glm::vec3(alpha, alpha, alpha)),
+        }
+    }
+
     pub fn model(&self) -> Mat4 {
-        // Model*v = (T * R.yaw * R.roll * S)*v
+        // Model*v = (T * R * S)*v
+        let rotation = to_glm(&self.rotation.to_homogeneous());
         let model = glm::mat4(
             1.0, 0.0, 0.0, 0.0,
             0.0, 1.0, 0.0, 0.0,
             0.0, 0.0, 1.0, 0.0,
             0.0, 0.0, 0.0, 1.0
         );
-        let model = glm::ext::translate(&model, self.position);
-        let model = glm::ext::rotate(&model, self.yaw, glm::vec3(0.0, 1.0, 0.0));
-        let model = glm::ext::rotate(&model, self.roll, glm::vec3(0.0, 0.0, 1.0));
+        let model = glm::ext::translate(&model, self.position) * rotation;
         glm::ext::scale(&model, self.scale)
     }
 }
+
+fn to_glm(m: &nalgebra::Matrix4<f32>) -> Mat4 {
+    // Both are column major.
+    glm::mat4(
+        m[(0, 0)], m[(1, 0)], m[(2, 0)], m[(3, 0)],
+        m[(0, 1)], m[(1, 1)], m[(2, 1)], m[(3, 1)],
+        m[(0, 2)], m[(1, 2)], m[(2, 2)], m[(3, 2)],
+        m[(0, 3)], m[(1, 3)], m[(2, 3)], m[(3, 3)],
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interpolate_takes_the_short_way_round_opposite_quaternions() {
+        let mut previous = Transform::new();
+        previous.rotate_local(glm::vec3(0.0, 1.0, 0.0), 0.5);
+        let mut current = previous;
+        current.rotate_local(glm::vec3(0.0, 1.0, 0.0), 0.1);
+        // The same rotation, stored in the other hemisphere.
+        current.rotation = UnitQuaternion::new_unchecked(-current.rotation.unwrap());
+
+        let halfway = Transform::interpolate(&previous, &current, 0.5).rotation;
+        assert!(halfway.coords.iter().all(|c| c.is_finite()));
+        let mut expected = previous;
+        expected.rotate_local(glm::vec3(0.0, 1.0, 0.0), 0.05);
+        assert!(halfway.angle_to(&expected.rotation) < 1e-2, "off by {}", halfway.angle_to(&expected.rotation));
+
+        let same = Transform::interpolate(&previous, &previous, 0.5).rotation;
+        assert!(same.angle_to(&previous.rotation) < 1e-2);
+    }
+
+    #[test]
+    fn isometry_round_trip_is_lossless() {
+        let mut transform = Transform::new();
+        transform.position = glm::vec3(1.25, -3.5, 130.0);
+        transform.scale = glm::vec3(0.5, 0.5, 5.0);
+        transform.set_yaw_pitch_roll(0.3, -1.2, 2.9);
+
+        let round_trip = Transform::from_isometry(&transform.to_isometry(), transform.scale);
+        assert_eq!(round_trip.position, transform.position);
+        assert_eq!(round_trip.scale, transform.scale);
+        let bits = |rotation: &UnitQuaternion<f32>| rotation.coords.iter().map(|c| c.to_bits()).collect::<Vec<_>>();
+        assert_eq!(bits(&round_trip.rotation), bits(&transform.rotation));
+    }
+}
@@ -0,0 +1,517 @@
+use app::{
+    Error,
+    StatusOr,
+};
+use color::Rgba;
+use dimensions::Pixels;
+use file::bytes::ByteReader;
+use image::Png;
+use lodepng;
+use std::{
+    fs,
+    path::{
+        Path,
+        PathBuf,
+    },
+    ptr,
+    slice,
+};
+
+const FILE_MAGIC: u16 = 0xa5e0;
+const FRAME_MAGIC: u16 = 0xf1fa;
+const HEADER_SIZE: usize = 128;
+const FRAME_HEADER_SIZE: usize = 16;
+
+const OLD_PALETTE_CHUNK: u16 = 0x0004;
+const OLD_SMALL_PALETTE_CHUNK: u16 = 0x0011;
+const LAYER_CHUNK: u16 = 0x2004;
+const CEL_CHUNK: u16 = 0x2005;
+const TAGS_CHUNK: u16 = 0x2018;
+const PALETTE_CHUNK: u16 = 0x2019;
+
+// Header flag: layer opacity fields are meaningful.
+const LAYER_OPACITY_VALID: u32 = 1;
+// Layer flags.
+const LAYER_VISIBLE: u16 = 1;
+const LAYER_BACKGROUND: u16 = 8;
+const LAYER_REFERENCE: u16 = 64;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ColorMode {
+    Rgba,
+    Grayscale,
+    Indexed,
+}
+
+impl ColorMode {
+    fn bytes_per_pixel(&self) -> usize {
+        match *self {
+            ColorMode::Rgba => 4,
+            ColorMode::Grayscale => 2,
+            ColorMode::Indexed => 1,
+        }
+    }
+}
+
+// How a tag plays its frames, as picked in Aseprite's tag properties.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TagDirection {
+    Forward,
+    Reverse,
+    PingPong,
+    PingPongReverse,
+}
+
+// A named run of frames, from and to inclusive.
+#[derive(Clone, Debug)]
+pub struct AsepriteTag {
+    pub name: String,
+    pub from: usize,
+    pub to: usize,
+    pub direction: TagDirection,
+}
+
+impl AsepriteTag {
+    // The frames of one pass through the tag. Ping-pong passes don't repeat their ends,
+    // so looping them doesn't hold the end frames twice as long.
+    pub fn frames(&self) -> Vec<usize> {
+        let forward: Vec<usize> = (self.from..self.to + 1).collect();
+        let backward: Vec<usize> = forward.iter().rev().cloned().collect();
+        let inner = |frames: &[usize]| if frames.len() > 2 { frames[1..frames.len() - 1].to_vec() } else { Vec::new() };
+        match self.direction {
+            TagDirection::Forward => forward,
+            TagDirection::Reverse => backward,
+            TagDirection::PingPong => [forward.clone(), inner(&backward)].concat(),
+            TagDirection::PingPongReverse => [backward.clone(), inner(&forward)].concat(),
+        }
+    }
+}
+
+struct Layer {
+    name: String,
+    flags: u16,
+    // 0 is a normal layer, 1 a group and 2 a tilemap.
+    layer_type: u16,
+    child_level: u16,
+    opacity: u8,
+}
+
+// Pixels in the file's color mode, row by row, possibly zlib compressed.
+#[derive(Clone)]
+enum CelData {
+    Raw(Vec<u8>),
+    Compressed(Vec<u8>),
+}
+
+#[derive(Clone)]
+struct Cel {
+    layer: usize,
+    x: i32,
+    y: i32,
+    width: usize,
+    height: usize,
+    opacity: u8,
+    z_index: i32,
+    data: CelData,
+}
+
+enum CelChunk {
+    Cel(Cel),
+    // Shares the cel on the same layer in another frame.
+    Linked { layer: usize, frame: usize },
+    Tilemap,
+}
+
+// A sprite drawn in Aseprite, read from its .ase or .aseprite file. Cels are only
+// decompressed by sprite_sheet, so the frame size, durations and tags are cheap to look up.
+// Every layer composites in normal blend mode.
+pub struct Aseprite {
+    path: PathBuf,
+    width: Pixels,
+    height: Pixels,
+    color_mode: ColorMode,
+    // The index that's transparent outside of the background layer, in indexed mode.
+    transparent_index: u8,
+    palette: Vec<Rgba>,
+    layers: Vec<Layer>,
+    // In milliseconds.
+    frame_durations: Vec<i64>,
+    // Per frame.
+    cels: Vec<Vec<Cel>>,
+    tags: Vec<AsepriteTag>,
+}
+
+impl Aseprite {
+    pub fn from_file(path: &Path) -> StatusOr<Aseprite> {
+        let bytes = fs::read(path).map_err(|err| Error::io(path.to_path_buf(), err))?;
+        let invalid = |reason: String| Error::ImageImport { path: path.to_path_buf(), reason };
+        let truncated = || invalid("The file is truncated.".to_string());
+        let mut reader = ByteReader::new(&bytes);
+
+        let mut header = ByteReader::new(reader.take(HEADER_SIZE).ok_or_else(truncated)?);
+        let (_file_size, magic) = (header.u32(), header.u16());
+        if magic != Some(FILE_MAGIC) {
+            return Err(invalid("This isn't an Aseprite file.".to_string()));
+        }
+        let num_frames = header.u16().ok_or_else(truncated)? as usize;
+        let width = header.u16().ok_or_else(truncated)? as Pixels;
+        let height = header.u16().ok_or_else(truncated)? as Pixels;
+        let color_mode = match header.u16().ok_or_else(truncated)? {
+            32 => ColorMode::Rgba,
+            16 => ColorMode::Grayscale,
+            8 => ColorMode::Indexed,
+            depth => return Err(invalid(format!("Color depth {} is not supported.", depth))),
+        };
+        let flags = header.u32().ok_or_else(truncated)?;
+        // Skip the deprecated speed and two reserved words.
+        header.take(10).ok_or_else(truncated)?;
+        let transparent_index = header.u8().ok_or_else(truncated)?;
+        if width == 0 || height == 0 || num_frames == 0 {
+            return Err(invalid(format!("The sprite is {}x{} with {} frames.", width, height, num_frames)));
+        }
+
+        let mut aseprite = Aseprite {
+            path: path.to_path_buf(),
+            width,
+            height,
+            color_mode,
+            transparent_index,
+            palette: Vec::new(),
+            layers: Vec::new(),
+            frame_durations: Vec::with_capacity(num_frames),
+            cels: Vec::with_capacity(num_frames),
+            tags: Vec::new(),
+        };
+        // Old palette chunks only count in files without a new one.
+        let mut old_palette = Vec::new();
+        for frame in 0..num_frames {
+            let mut frame_header = ByteReader::new(reader.take(FRAME_HEADER_SIZE).ok_or_else(truncated)?);
+            let frame_size = frame_header.u32().ok_or_else(truncated)? as usize;
+            if frame_header.u16() != Some(FRAME_MAGIC) || frame_size < FRAME_HEADER_SIZE {
+                return Err(invalid(format!("Frame {} has a bad header.", frame)));
+            }
+            let old_num_chunks = frame_header.u16().ok_or_else(truncated)?;
+            aseprite.frame_durations.push(frame_header.u16().ok_or_else(truncated)? as i64);
+            frame_header.take(2).ok_or_else(truncated)?;
+            let num_chunks = match frame_header.u32().ok_or_else(truncated)? {
+                0 => old_num_chunks as usize,
+                num_chunks => num_chunks as usize,
+            };
+
+            let mut frame_reader = ByteReader::new(reader.take(frame_size - FRAME_HEADER_SIZE).ok_or_else(truncated)?);
+            let mut cels = Vec::new();
+            for _ in 0..num_chunks {
+                let chunk_size = frame_reader.u32().ok_or_else(truncated)? as usize;
+                let chunk_type = frame_reader.u16().ok_or_else(truncated)?;
+                let mut chunk = ByteReader::new(frame_reader.take(chunk_size.saturating_sub(6)).ok_or_else(truncated)?);
+                match chunk_type {
+                    LAYER_CHUNK => {
+                        let layer = read_layer(&mut chunk, flags).ok_or_else(truncated)?;
+                        if layer.layer_type == 2 {
+                            return Err(invalid(format!("Layer {} is a tilemap, which is not supported.", layer.name)));
+                        }
+                        aseprite.layers.push(layer);
+                    },
+                    CEL_CHUNK => match read_cel(&mut chunk).ok_or_else(truncated)? {
+                        CelChunk::Cel(cel) => cels.push(cel),
+                        CelChunk::Linked { layer, frame: linked_frame } => {
+                            // Links only ever point back to earlier frames.
+                            let linked = aseprite.cels.get(linked_frame)
+                                .and_then(|linked_cels| linked_cels.iter().find(|cel| cel.layer == layer))
+                                .ok_or_else(|| invalid(format!("A cel in frame {} links to nothing.", frame)))?;
+                            cels.push(linked.clone());
+                        },
+                        CelChunk::Tilemap =>
+                            return Err(invalid(format!("Frame {} has a tilemap cel, which is not supported.", frame))),
+                    },
+                    TAGS_CHUNK => aseprite.tags = read_tags(&mut chunk).ok_or_else(truncated)?,
+                    PALETTE_CHUNK => read_palette(&mut chunk, &mut aseprite.palette).ok_or_else(truncated)?,
+                    OLD_PALETTE_CHUNK | OLD_SMALL_PALETTE_CHUNK =>
+                        read_old_palette(&mut chunk, chunk_type == OLD_SMALL_PALETTE_CHUNK, &mut old_palette)
+                            .ok_or_else(truncated)?,
+                    // Color profiles, user data, slices and the like don't affect the pixels.
+                    _ => {},
+                }
+            }
+            aseprite.cels.push(cels);
+        }
+        if aseprite.palette.is_empty() {
+            aseprite.palette = old_palette;
+        }
+
+        for frame_cels in aseprite.cels.iter() {
+            if let Some(cel) = frame_cels.iter().find(|cel| cel.layer >= aseprite.layers.len()) {
+                return Err(invalid(format!("A cel is on layer {}, which doesn't exist.", cel.layer)));
+            }
+        }
+        if let Some(tag) = aseprite.tags.iter().find(|tag| tag.from > tag.to || tag.to >= num_frames) {
+            return Err(invalid(format!("Tag {} runs from frame {} to {}, out of {}.", tag.name, tag.from, tag.to, num_frames)));
+        }
+        Ok(aseprite)
+    }
+
+    pub fn frame_size(&self) -> (Pixels, Pixels) {
+        (self.width, self.height)
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.frame_durations.len()
+    }
+
+    // How long each frame shows for, in milliseconds.
+    pub fn frame_durations(&self) -> &[i64] {
+        &self.frame_durations
+    }
+
+    pub fn tags(&self) -> &[AsepriteTag] {
+        &self.tags
+    }
+
+    // Composites each frame's visible cels in layer order, and lays the frames side by side
+    // in one row, like Aseprite's horizontal strip export.
+    pub fn sprite_sheet(&self) -> StatusOr<Png> {
+        let transparent = Rgba::new(0.0, 0.0, 0.0, 0.0);
+        let mut sheet = Png::new(self.width * self.frame_count(), self.height, &transparent);
+        let visible = self.visible_layers();
+        for (frame, frame_cels) in self.cels.iter().enumerate() {
+            // Cels stack by layer, moved up or down by their z index, with ties going to the
+            // higher z index.
+            let mut ordered: Vec<&Cel> = frame_cels.iter().filter(|cel| visible[cel.layer]).collect();
+            ordered.sort_by_key(|cel| (cel.layer as i32 + cel.z_index, cel.z_index));
+            for cel in ordered {
+                let layer = &self.layers[cel.layer];
+                let pixels = self.cel_pixels(cel)?;
+                let opacity = (cel.opacity as f32 / 255.0) * (layer.opacity as f32 / 255.0);
+                let is_background = layer.flags & LAYER_BACKGROUND != 0;
+                for row in 0..cel.height {
+                    for column in 0..cel.width {
+                        let (x, y) = (cel.x + column as i32, cel.y + row as i32);
+                        if x < 0 || y < 0 || x as usize >= self.width || y as usize >= self.height {
+                            continue;
+                        }
+                        let pixel = self.pixel(&pixels[(row * cel.width + column) * self.color_mode.bytes_per_pixel()..],
+                                               is_background);
+                        let below = &mut sheet.img[y as usize][frame * self.width + x as usize];
+                        *below = pixel.blend_over(below, opacity);
+                    }
+                }
+            }
+        }
+        Ok(sheet)
+    }
+
+    // Hidden and reference layers are skipped, along with everything in a hidden group.
+    fn visible_layers(&self) -> Vec<bool> {
+        let mut visible = Vec::with_capacity(self.layers.len());
+        // The visibility of the groups enclosing the current layer, by child level.
+        let mut group_visible: Vec<bool> = Vec::new();
+        for layer in self.layers.iter() {
+            group_visible.truncate(layer.child_level as usize);
+            let parent_visible = group_visible.last().cloned().unwrap_or(true);
+            let shown = parent_visible && layer.flags & LAYER_VISIBLE != 0 && layer.flags & LAYER_REFERENCE == 0;
+            if layer.layer_type == 1 {
+                group_visible.resize(layer.child_level as usize, parent_visible);
+                group_visible.push(shown);
+            }
+            visible.push(shown && layer.layer_type != 1);
+        }
+        visible
+    }
+
+    // The cel's pixels in the file's color mode, decompressed.
+    fn cel_pixels(&self, cel: &Cel) -> StatusOr<Vec<u8>> {
+        let invalid = |reason: String| Error::ImageImport { path: self.path.clone(), reason };
+        let data = match cel.data {
+            CelData::Raw(ref bytes) => bytes.clone(),
+            CelData::Compressed(ref bytes) => zlib_decompress(bytes)
+                .map_err(|err| invalid(format!("A cel on layer {} doesn't decompress: {}", cel.layer, err)))?,
+        };
+        if data.len() < cel.width * cel.height * self.color_mode.bytes_per_pixel() {
+            return Err(invalid(format!("A cel on layer {} is missing pixels.", cel.layer)));
+        }
+        Ok(data)
+    }
+
+    fn pixel(&self, bytes: &[u8], is_background: bool) -> Rgba {
+        match self.color_mode {
+            ColorMode::Rgba => Rgba::from_bytes(bytes[0], bytes[1], bytes[2], bytes[3]),
+            ColorMode::Grayscale => Rgba::from_bytes(bytes[0], bytes[0], bytes[0], bytes[1]),
+            ColorMode::Indexed if bytes[0] == self.transparent_index && !is_background => Rgba::new(0.0, 0.0, 0.0, 0.0),
+            ColorMode::Indexed => self.palette.get(bytes[0] as usize).cloned().unwrap_or_else(|| Rgba::new(0.0, 0.0, 0.0, 0.0)),
+        }
+    }
+}
+
+fn read_layer(chunk: &mut ByteReader, header_flags: u32) -> Option<Layer> {
+    let flags = chunk.u16()?;
+    let layer_type = chunk.u16()?;
+    let child_level = chunk.u16()?;
+    // Skip the default width and height, and the blend mode.
+    chunk.take(6)?;
+    let opacity = chunk.u8()?;
+    chunk.take(3)?;
+    let name = chunk.string()?;
+    Some(Layer {
+        name,
+        flags,
+        layer_type,
+        child_level,
+        opacity: if header_flags & LAYER_OPACITY_VALID != 0 { opacity } else { 255 },
+    })
+}
+
+fn read_cel(chunk: &mut ByteReader) -> Option<CelChunk> {
+    let layer = chunk.u16()? as usize;
+    let x = chunk.i16()? as i32;
+    let y = chunk.i16()? as i32;
+    let opacity = chunk.u8()?;
+    let cel_type = chunk.u16()?;
+    let z_index = chunk.i16()? as i32;
+    chunk.take(5)?;
+    let (width, height, data) = match cel_type {
+        0 => {
+            let (width, height) = (chunk.u16()? as usize, chunk.u16()? as usize);
+            (width, height, CelData::Raw(chunk.rest().to_vec()))
+        },
+        1 => return Some(CelChunk::Linked { layer, frame: chunk.u16()? as usize }),
+        2 => {
+            let (width, height) = (chunk.u16()? as usize, chunk.u16()? as usize);
+            (width, height, CelData::Compressed(chunk.rest().to_vec()))
+        },
+        _ => return Some(CelChunk::Tilemap),
+    };
+    Some(CelChunk::Cel(Cel { layer, x, y, width, height, opacity, z_index, data }))
+}
+
+fn read_tags(chunk: &mut ByteReader) -> Option<Vec<AsepriteTag>> {
+    let num_tags = chunk.u16()?;
+    chunk.take(8)?;
+    let mut tags = Vec::with_capacity(num_tags as usize);
+    for _ in 0..num_tags {
+        let from = chunk.u16()? as usize;
+        let to = chunk.u16()? as usize;
+        let direction = match chunk.u8()? {
+            1 => TagDirection::Reverse,
+            2 => TagDirection::PingPong,
+            3 => TagDirection::PingPongReverse,
+            _ => TagDirection::Forward,
+        };
+        // Skip the repeat count, reserved bytes and the deprecated tag color.
+        chunk.take(2 + 6 + 4)?;
+        let name = chunk.string()?;
+        tags.push(AsepriteTag { name, from, to, direction });
+    }
+    Some(tags)
+}
+
+fn read_palette(chunk: &mut ByteReader, palette: &mut Vec<Rgba>) -> Option<()> {
+    let size = chunk.u32()? as usize;
+    let first = chunk.u32()? as usize;
+    let last = chunk.u32()? as usize;
+    chunk.take(8)?;
+    if first > last || last >= size {
+        return None;
+    }
+    palette.resize(size, Rgba::new(0.0, 0.0, 0.0, 1.0));
+    for entry in palette[first..last + 1].iter_mut() {
+        let flags = chunk.u16()?;
+        let rgba = chunk.take(4)?;
+        *entry = Rgba::from_bytes(rgba[0], rgba[1], rgba[2], rgba[3]);
+        if flags & 1 != 0 {
+            chunk.string()?;
+        }
+    }
+    Some(())
+}
+
+// Old palettes are packets of opaque RGB colors, each after a number of entries to skip.
+// Small palettes' channels run from 0 to 63.
+fn read_old_palette(chunk: &mut ByteReader, small: bool, palette: &mut Vec<Rgba>) -> Option<()> {
+    let num_packets = chunk.u16()?;
+    let mut entry = 0;
+    for _ in 0..num_packets {
+        entry += chunk.u8()? as usize;
+        let num_colors = match chunk.u8()? {
+            0 => 256,
+            num_colors => num_colors as usize,
+        };
+        for _ in 0..num_colors {
+            let rgb = chunk.take(3)?;
+            let channel = |value: u8| if small { (value.min(63) as u32 * 255 / 63) as u8 } else { value };
+            if palette.len() <= entry {
+                palette.resize(entry + 1, Rgba::new(0.0, 0.0, 0.0, 1.0));
+            }
+            palette[entry] = Rgba::from_bytes(channel(rgb[0]), channel(rgb[1]), channel(rgb[2]), 255);
+            entry += 1;
+        }
+    }
+    Some(())
+}
+
+// Compressed cels are zlib streams, which lodepng decodes for PNG's own IDAT chunks.
+fn zlib_decompress(bytes: &[u8]) -> Result<Vec<u8>, lodepng::Error> {
+    let settings = lodepng::DecompressSettings::new();
+    let mut out: *mut u8 = ptr::null_mut();
+    let mut out_size = 0;
+    unsafe {
+        let error = lodepng::ffi::zlib_decompress(&mut out, &mut out_size, bytes.as_ptr(), bytes.len(), &settings);
+        if out.is_null() {
+            return if error.0 == 0 { Ok(Vec::new()) } else { Err(error) };
+        }
+        // lodepng allocates out with malloc, so it has to free it too.
+        let decompressed = slice::from_raw_parts(out, out_size).to_vec();
+        lodepng::ffi::lodepng_free(out as *mut _);
+        if error.0 == 0 { Ok(decompressed) } else { Err(error) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 4x3 RGBA, three frames of 100, 150 and 200ms. Layer bg is opaque red throughout, its
+    // later cels linked to the first. Layer fg has a half-transparent blue 2x1 cel at (1, 1)
+    // in frame 0, an opaque green pixel at (0, 0) in frame 1, and nothing in frame 2.
+    // Tags: walk runs 0 to 1 forwards, bounce 0 to 2 ping-pong.
+    fn fixture() -> Aseprite {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("src/lib/image/test_data/two_layers.aseprite");
+        Aseprite::from_file(&path).unwrap()
+    }
+
+    fn assert_pixel(sheet: &Png, (x, y): (usize, usize), expected: [u8; 4]) {
+        let actual = sheet.img[y][x].as_byte_vec();
+        for (channel, (&a, &e)) in actual.iter().zip(expected.iter()).enumerate() {
+            assert!((a as i32 - e as i32).abs() <= 1, "pixel ({}, {}) channel {}: {:?}, expected {:?}", x, y, channel, actual, expected);
+        }
+    }
+
+    #[test]
+    fn reads_frames_durations_and_tags() {
+        let aseprite = fixture();
+        assert_eq!(aseprite.frame_size(), (4, 3));
+        assert_eq!(aseprite.frame_count(), 3);
+        assert_eq!(aseprite.frame_durations(), &[100, 150, 200]);
+        let tags: Vec<_> = aseprite.tags().iter().map(|tag| (tag.name.as_str(), tag.from, tag.to, tag.direction)).collect();
+        assert_eq!(tags, vec![("walk", 0, 1, TagDirection::Forward), ("bounce", 0, 2, TagDirection::PingPong)]);
+        assert_eq!(aseprite.tags()[1].frames(), vec![0, 1, 2, 1]);
+    }
+
+    #[test]
+    fn composites_layers_and_linked_cels() {
+        let sheet = fixture().sprite_sheet().unwrap();
+        assert_eq!(sheet.size(), (12, 3));
+        let red = [255, 0, 0, 255];
+        // Frame 0: blue at half opacity over red.
+        assert_pixel(&sheet, (0, 0), red);
+        assert_pixel(&sheet, (1, 1), [127, 0, 128, 255]);
+        assert_pixel(&sheet, (2, 1), [127, 0, 128, 255]);
+        assert_pixel(&sheet, (3, 1), red);
+        // Frame 1: green over the linked red cel.
+        assert_pixel(&sheet, (4, 0), [0, 255, 0, 255]);
+        assert_pixel(&sheet, (5, 1), red);
+        assert_pixel(&sheet, (7, 2), red);
+        // Frame 2: only the linked red cel.
+        assert_pixel(&sheet, (8, 0), red);
+        assert_pixel(&sheet, (9, 1), red);
+    }
+}
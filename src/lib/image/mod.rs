@@ -1,5 +1,12 @@
+pub mod aseprite;
+pub mod piskel;
 pub mod png;
+pub mod sheet_file;
 pub mod texture;
 
+pub use self::aseprite::Aseprite;
+pub use self::aseprite::AsepriteTag;
+pub use self::aseprite::TagDirection;
+pub use self::piskel::Piskel;
 pub use self::png::Png;
-pub use self::texture::Texture;
\ No newline at end of file
+pub use self::texture::Texture;
@@ -0,0 +1,295 @@
+use app::{
+    Error,
+    StatusOr,
+};
+use color::Rgba;
+use dimensions::Pixels;
+use file;
+use image::{
+    png::ImageRect,
+    Png,
+};
+use serde_json;
+use std::path::{
+    Path,
+    PathBuf,
+};
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PiskelFile {
+    model_version: u32,
+    piskel: PiskelDocument,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PiskelDocument {
+    width: Pixels,
+    height: Pixels,
+    fps: u32,
+    // Each layer is a JSON document of its own, stored as a string.
+    layers: Vec<String>,
+    // Frames Piskel leaves out of its exports.
+    #[serde(default)]
+    hidden_frames: Vec<usize>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PiskelLayer {
+    name: String,
+    opacity: f32,
+    frame_count: usize,
+    #[serde(default)]
+    chunks: Option<Vec<PiskelChunk>>,
+    // Files saved before chunks existed hold one image of every frame side by side.
+    #[serde(default, rename = "base64PNG")]
+    base64_png: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct PiskelChunk {
+    // layout[column][row] is the frame drawn in that cell of the chunk's image.
+    layout: Vec<Vec<usize>>,
+    #[serde(rename = "base64PNG")]
+    base64_png: String,
+}
+
+// A sprite drawn in Piskel, read from its .piskel file (modelVersion 2). Layer images are
+// only decoded by sprite_sheet, so the frame size, count and fps are cheap to look up.
+pub struct Piskel {
+    path: PathBuf,
+    width: Pixels,
+    height: Pixels,
+    fps: u32,
+    layers: Vec<PiskelLayer>,
+    // Indexes of the frames that are exported, in order.
+    visible_frames: Vec<usize>,
+}
+
+impl Piskel {
+    pub fn from_file(path: &Path) -> StatusOr<Piskel> {
+        let data = file::util::slurp_file(path).map_err(|err| Error::io(path.to_path_buf(), err))?;
+        let config_error = |err| Error::ConfigParse { path: path.to_path_buf(), source: err };
+        let piskel_file: PiskelFile = serde_json::from_str(data.as_str()).map_err(config_error)?;
+        let invalid = |reason: String| Error::ImageImport { path: path.to_path_buf(), reason };
+        if piskel_file.model_version != 2 {
+            return Err(invalid(format!("Only modelVersion 2 is supported, not {}.", piskel_file.model_version)));
+        }
+        let document = piskel_file.piskel;
+        if document.width == 0 || document.height == 0 {
+            return Err(invalid(format!("Frames are {}x{}.", document.width, document.height)));
+        }
+        let mut layers: Vec<PiskelLayer> = Vec::with_capacity(document.layers.len());
+        for layer in document.layers.iter() {
+            layers.push(serde_json::from_str(layer.as_str()).map_err(config_error)?);
+        }
+        let frame_count = match layers.first() {
+            Some(layer) => layer.frame_count,
+            None => return Err(invalid("There are no layers.".to_string())),
+        };
+        if let Some(layer) = layers.iter().find(|layer| layer.frame_count != frame_count) {
+            return Err(invalid(format!("Layer {} has {} frames, not {}.", layer.name, layer.frame_count, frame_count)));
+        }
+        let visible_frames: Vec<usize> = (0..frame_count).filter(|frame| !document.hidden_frames.contains(frame)).collect();
+        if visible_frames.is_empty() {
+            return Err(invalid("Every frame is hidden.".to_string()));
+        }
+        Ok(Piskel {
+            path: path.to_path_buf(),
+            width: document.width,
+            height: document.height,
+            fps: document.fps,
+            layers,
+            visible_frames,
+        })
+    }
+
+    pub fn frame_size(&self) -> (Pixels, Pixels) {
+        (self.width, self.height)
+    }
+
+    // Hidden frames aren't counted.
+    pub fn frame_count(&self) -> usize {
+        self.visible_frames.len()
+    }
+
+    pub fn fps(&self) -> u32 {
+        self.fps
+    }
+
+    // Decodes every layer and composites them, bottom layer first, each scaled by its
+    // opacity. Like Piskel's PNG export, the visible frames are laid side by side in one row.
+    pub fn sprite_sheet(&self) -> StatusOr<Png> {
+        let transparent = Rgba::new(0.0, 0.0, 0.0, 0.0);
+        let mut sheet = Png::new(self.width * self.frame_count(), self.height, &transparent);
+        for layer in self.layers.iter() {
+            let frames = self.layer_frames(layer)?;
+            for (slot, &frame) in self.visible_frames.iter().enumerate() {
+                for (row, frame_row) in frames[frame].img.iter().enumerate() {
+                    for (column, pixel) in frame_row.iter().enumerate() {
+                        let below = &mut sheet.img[row][slot * self.width + column];
+                        *below = pixel.blend_over(below, layer.opacity);
+                    }
+                }
+            }
+        }
+        Ok(sheet)
+    }
+
+    // Every frame of layer, in order.
+    fn layer_frames(&self, layer: &PiskelLayer) -> StatusOr<Vec<Png>> {
+        let invalid = |reason: String| Error::ImageImport {
+            path: self.path.clone(),
+            reason: format!("Layer {}: {}", layer.name, reason),
+        };
+        let side_by_side;
+        let chunks = match (&layer.chunks, &layer.base64_png) {
+            (&Some(ref chunks), _) => chunks,
+            (&None, &Some(ref base64_png)) => {
+                side_by_side = vec![PiskelChunk {
+                    layout: (0..layer.frame_count).map(|frame| vec![frame]).collect(),
+                    base64_png: base64_png.clone(),
+                }];
+                &side_by_side
+            },
+            (&None, &None) => return Err(invalid("There is no image data.".to_string())),
+        };
+
+        let mut frames: Vec<Option<Png>> = (0..layer.frame_count).map(|_| None).collect();
+        for chunk in chunks.iter() {
+            let bytes = decode_data_url(&chunk.base64_png)
+                .ok_or_else(|| invalid("An image isn't a base64 data URL.".to_string()))?;
+            let image = Png::from_memory(&bytes, &self.path)?;
+            let (columns, rows) = (chunk.layout.len(), chunk.layout.first().map(|column| column.len()).unwrap_or(0));
+            if chunk.layout.iter().any(|column| column.len() != rows) {
+                return Err(invalid("A chunk's layout isn't rectangular.".to_string()));
+            }
+            if image.size() != (columns * self.width, rows * self.height) {
+                return Err(invalid(format!("A {}x{} image doesn't fit a {}x{} layout of frames.",
+                                           image.size().0, image.size().1, columns, rows)));
+            }
+            for (column, column_frames) in chunk.layout.iter().enumerate() {
+                for (row, &frame) in column_frames.iter().enumerate() {
+                    if frame >= layer.frame_count {
+                        return Err(invalid(format!("Frame {} is out of range.", frame)));
+                    }
+                    frames[frame] = Some(image.copy_sub_image(
+                        ImageRect::new(row * self.height, column * self.width, self.width, self.height)));
+                }
+            }
+        }
+        frames.into_iter()
+            .enumerate()
+            .map(|(frame, image)| image.ok_or_else(|| invalid(format!("Frame {} is missing.", frame))))
+            .collect()
+    }
+}
+
+// The payload of a base64 data URL, such as the data:image/png;base64,... images in a .piskel.
+fn decode_data_url(url: &str) -> Option<Vec<u8>> {
+    let mut parts = url.splitn(2, ',');
+    let header = parts.next()?;
+    if !header.starts_with("data:") || !header.ends_with(";base64") {
+        return None;
+    }
+    decode_base64(parts.next()?)
+}
+
+// Standard base64 with optional padding.
+fn decode_base64(text: &str) -> Option<Vec<u8>> {
+    let unpadded = text.trim_end_matches('=');
+    // A lone trailing character has too few bits for a byte, and padding only fills out the
+    // last group of four.
+    let padding = text.len() - unpadded.len();
+    if unpadded.len() % 4 == 1 || padding > 2 || (padding > 0 && text.len() % 4 != 0) {
+        return None;
+    }
+    let mut bytes = Vec::with_capacity(text.len() / 4 * 3);
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+    for c in unpadded.bytes() {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return None,
+        };
+        buffer = (buffer << 6) | value as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+            buffer &= (1 << bits) - 1;
+        }
+    }
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 2x1 frames at 8 fps, three of them with frame 1 hidden. Layer bg is opaque, with frames
+    // red|blue, green|green and white|black in one row. Layer fg is at half opacity, in two
+    // chunks: one column holding frame 2 (blue|clear) above frame 0 (clear|white), and
+    // frame 1 (yellow|yellow) on its own. The PNGs are indexed, since lodepng reads an RGBA
+    // PNG's empty palette from a null pointer, which debug builds of newer Rust reject.
+    fn fixture() -> Piskel {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("src/lib/image/test_data/two_layers.piskel");
+        Piskel::from_file(&path).unwrap()
+    }
+
+    fn assert_pixel(sheet: &Png, (x, y): (usize, usize), expected: [u8; 4]) {
+        let actual = sheet.img[y][x].as_byte_vec();
+        for (channel, (&a, &e)) in actual.iter().zip(expected.iter()).enumerate() {
+            assert!((a as i32 - e as i32).abs() <= 1, "pixel ({}, {}) channel {}: {:?}, expected {:?}", x, y, channel, actual, expected);
+        }
+    }
+
+    #[test]
+    fn skips_hidden_frames() {
+        let piskel = fixture();
+        assert_eq!(piskel.frame_size(), (2, 1));
+        assert_eq!(piskel.frame_count(), 2);
+        assert_eq!(piskel.fps(), 8);
+    }
+
+    #[test]
+    fn composites_layers_by_opacity() {
+        let sheet = fixture().sprite_sheet().unwrap();
+        assert_eq!(sheet.size(), (4, 1));
+        // Frame 0: bg shows through fg's clear pixel, and white half covers blue.
+        assert_pixel(&sheet, (0, 0), [255, 0, 0, 255]);
+        assert_pixel(&sheet, (1, 0), [127, 127, 255, 255]);
+        // Frame 2, from the top of fg's column: blue half covers white, and black shows through.
+        assert_pixel(&sheet, (2, 0), [127, 127, 255, 255]);
+        assert_pixel(&sheet, (3, 0), [0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn decodes_base64_with_or_without_padding() {
+        assert_eq!(decode_base64(""), Some(Vec::new()));
+        assert_eq!(decode_base64("TWFu"), Some(b"Man".to_vec()));
+        assert_eq!(decode_base64("TWE="), Some(b"Ma".to_vec()));
+        assert_eq!(decode_base64("TWE"), Some(b"Ma".to_vec()));
+        assert_eq!(decode_base64("TQ=="), Some(b"M".to_vec()));
+        assert_eq!(decode_base64("TQ"), Some(b"M".to_vec()));
+        assert_eq!(decode_base64("+/+/"), Some(vec![0xfb, 0xff, 0xbf]));
+    }
+
+    #[test]
+    fn rejects_invalid_base64() {
+        assert_eq!(decode_base64("TW-u"), None);
+        assert_eq!(decode_base64("TW u"), None);
+        assert_eq!(decode_base64("T=Fu"), None);
+        assert_eq!(decode_base64("TWFuT"), None);
+        assert_eq!(decode_base64("TQ==="), None);
+        assert_eq!(decode_base64("TWE=="), None);
+        assert_eq!(decode_data_url("data:image/png,TWFu"), None);
+        assert_eq!(decode_data_url("data:image/png;base64,TWFu"), Some(b"Man".to_vec()));
+    }
+}
@@ -1,7 +1,11 @@
-extern crate lodepng;
+use app::{
+    Error,
+    StatusOr,
+};
 use color::Rgba;
 use dimensions::Pixels;
-use std;
+use lodepng;
+use std::path::Path;
 
 pub struct ImageRect {
     top_left_row: Pixels,
@@ -37,10 +41,21 @@ impl Png {
         }
     }
 
-    pub fn from_file(path: &str) -> std::result::Result<Png, String> {
+    pub fn from_file(path: &Path) -> StatusOr<Png> {
         let bitmap = lodepng::decode32_file(path)
-            .map_err(|err| format!("Failed to open PNG path {}: {}", path, err))?;
+            .map_err(|err| Error::PngDecode { path: path.to_path_buf(), source: err })?;
+        Ok(Png::from_bitmap(bitmap))
+    }
+
+    // Decodes PNG file contents held in memory, e.g. embedded in another file. path only
+    // labels errors.
+    pub fn from_memory(bytes: &[u8], path: &Path) -> StatusOr<Png> {
+        let bitmap = lodepng::decode32(bytes)
+            .map_err(|err| Error::PngDecode { path: path.to_path_buf(), source: err })?;
+        Ok(Png::from_bitmap(bitmap))
+    }
 
+    fn from_bitmap(bitmap: lodepng::Bitmap<lodepng::RGBA>) -> Png {
         let mut img = Vec::with_capacity(bitmap.height);
         for i in 0..bitmap.height {
             let mut row = Vec::with_capacity(bitmap.width);
@@ -51,7 +66,7 @@ impl Png {
             }
             img.push(row);
         }
-        Ok(Png { img })
+        Png { img }
     }
 
     pub fn copy_sub_image(&self, irect: ImageRect) -> Png {
@@ -88,11 +103,18 @@ impl Png {
         out
     }
 
-    pub fn save(&self, path: &'static str) -> std::result::Result<(), String> {
+    pub fn save(&self, path: &Path) -> StatusOr<()> {
         let (width, height) = self.size();
         let flat_rgba = self.flattened_copy();
         lodepng::encode32_file(path, flat_rgba.as_slice(), width, height)
-            .map_err(|err| format!("Problem saving png to {}: {}", path, err))
+            .map_err(|err| Error::PngEncode { path: path.to_path_buf(), source: err })
+    }
+
+    // The image as PNG file contents. path only labels errors.
+    pub fn encode(&self, path: &Path) -> StatusOr<Vec<u8>> {
+        let (width, height) = self.size();
+        lodepng::encode32(self.flattened_copy_bytes().as_slice(), width, height)
+            .map_err(|err| Error::PngEncode { path: path.to_path_buf(), source: err })
     }
 
     pub fn size(&self) -> (Pixels, Pixels) {
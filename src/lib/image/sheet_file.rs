@@ -0,0 +1,38 @@
+use app::{
+    Error,
+    StatusOr,
+};
+use image::{
+    Aseprite,
+    Piskel,
+    Png,
+};
+use std::{
+    fs,
+    path::Path,
+};
+
+fn has_extension(path: &Path, extensions: &[&str]) -> bool {
+    path.extension().map(|extension| extensions.iter().any(|other| extension == *other)).unwrap_or(false)
+}
+
+// Loads a sprite sheet from a PNG, or imports one from an editor's file by its extension.
+pub fn load(path: &Path) -> StatusOr<Png> {
+    if has_extension(path, &["piskel"]) {
+        Piskel::from_file(path)?.sprite_sheet()
+    } else if has_extension(path, &["ase", "aseprite"]) {
+        Aseprite::from_file(path)?.sprite_sheet()
+    } else {
+        Png::from_file(path)
+    }
+}
+
+// The sheet at path as PNG file contents, e.g. to embed in a glTF. PNGs are passed through
+// untouched.
+pub fn png_bytes(path: &Path) -> StatusOr<Vec<u8>> {
+    if has_extension(path, &["png"]) {
+        fs::read(path).map_err(|err| Error::io(path.to_path_buf(), err))
+    } else {
+        load(path)?.encode(path)
+    }
+}
@@ -1,20 +1,25 @@
 extern crate gl;
 extern crate glm;
+extern crate lodepng;
 extern crate nalgebra;
 extern crate ncollide;
 extern crate nphysics3d;
 extern crate num_traits;
+extern crate rusttype;
 extern crate sdl2;
 extern crate serde;
 extern crate serde_json;
+extern crate ttf_parser;
 #[macro_use] extern crate serde_derive;
 
 pub mod app;
+pub mod assets;
 pub mod color;
 pub mod controls;
 pub mod dimensions;
 pub mod entity;
 pub mod file;
+pub mod font;
 pub mod geometry;
 pub mod image;
 pub mod render;
@@ -1,14 +1,14 @@
-use controls::KeyboardControls;
+use controls::Input;
 use dimensions::time::DeltaTime;
 use glm::{
     Mat4,
     self,
     Vec3
 };
-use sdl2::{
-    keyboard::Scancode,
-    render::WindowCanvas,
-};
+use sdl2::render::WindowCanvas;
+
+pub const Z_NEAR: f32 = 0.1;
+pub const Z_FAR: f32 = 1000.0;
 
 pub struct Camera {
     position: Vec3,
@@ -33,42 +33,68 @@ impl Camera {
         self.position
     }
 
+    // Unit vector the camera looks along.
+    pub fn lookat(&self) -> Vec3 {
+        self.lookat
+    }
+
+    // Points the camera from position at target, keeping world up as up as far as possible.
+    pub fn look_at(&mut self, position: Vec3, target: Vec3) {
+        let lookat = target - position;
+        if glm::builtin::length(lookat) < 1e-4 {
+            self.position = position;
+            return;
+        }
+        self.position = position;
+        self.lookat = glm::builtin::normalize(lookat);
+        let world_up = if self.lookat.y.abs() > 0.999 { glm::vec3(0.0, 0.0, -1.0) } else { glm::vec3(0.0, 1.0, 0.0) };
+        let right = glm::builtin::normalize(glm::builtin::cross(self.lookat, world_up));
+        self.up = glm::builtin::cross(right, self.lookat);
+    }
+
     pub fn projection(&self, canvas: &WindowCanvas) -> Mat4 {
+        self.projection_for_range(Camera::aspect(canvas), Z_NEAR, Z_FAR)
+    }
+
+    // The projection clipped to [z_near, z_far], e.g. for fitting a shadow cascade.
+    pub fn projection_for_range(&self, aspect: f32, z_near: f32, z_far: f32) -> Mat4 {
+        glm::ext::perspective(self.zoom, aspect, z_near, z_far)
+    }
+
+    pub fn aspect(canvas: &WindowCanvas) -> f32 {
         let (width, height) = canvas.window().size();
-        let (float_width, float_height) = (width as f32, height as f32);
-        let (z_near, z_far) = (0.1, 1000.0);
-        glm::ext::perspective(self.zoom, float_width / float_height, z_near, z_far)
+        width as f32 / height as f32
     }
 
     pub fn view(&self) -> Mat4 {
         glm::ext::look_at(self.position, self.position + self.lookat, self.up)
     }
 
-    pub fn update(&mut self, keyboard: &KeyboardControls, dt: DeltaTime) {
-        if keyboard.just_pressed(Scancode::M) {
+    pub fn update(&mut self, input: &Input, dt: DeltaTime) {
+        if input.just_pressed("camera_print_pose") {
             println!("Cam Position {:?}, Lookat {:?}, Up {:?}", self.position, self.lookat, self.up);
         }
         let cam_speed : f32 = 8e-6;
         let movement = (dt.as_microseconds() as f32) * cam_speed;
-        if keyboard.is_pressed(Scancode::D) {
+        if input.is_pressed("camera_right") {
             self.position.x += movement;
         }
-        if keyboard.is_pressed(Scancode::A) {
+        if input.is_pressed("camera_left") {
             self.position.x -= movement;
         }
-        if keyboard.is_pressed(Scancode::W) {
+        if input.is_pressed("camera_up") {
             self.position.y += movement;
         }
-        if keyboard.is_pressed(Scancode::S) {
+        if input.is_pressed("camera_down") {
             self.position.y -= movement;
         }
-        if keyboard.is_pressed(Scancode::F) {
+        if input.is_pressed("camera_backward") {
             self.position.z += movement;
         }
-        if keyboard.is_pressed(Scancode::R) {
+        if input.is_pressed("camera_forward") {
             self.position.z -= movement;
         }
-        if keyboard.is_pressed(Scancode::I) {
+        if input.is_pressed("camera_pitch_up") {
             let right = glm::vec3(1.0, 0.0, 0.0);
             let eye = glm::mat4(
                 1.0, 0.0, 0.0, 0.0,
@@ -81,7 +107,7 @@ impl Camera {
             self.lookat = glm::ext::normalize_to(glm::vec3(lookat4.x, lookat4.y, lookat4.z), 1.0);
             self.up = glm::builtin::cross(right, self.lookat);
         }
-        if keyboard.is_pressed(Scancode::K) {
+        if input.is_pressed("camera_pitch_down") {
             let right = glm::vec3(1.0, 0.0, 0.0);
             let eye = glm::mat4(
                 1.0, 0.0, 0.0, 0.0,
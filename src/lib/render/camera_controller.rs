@@ -0,0 +1,307 @@
+use app::StatusOr;
+use controls::Input;
+use dimensions::time::DeltaTime;
+use entity::PhysicalWorld;
+use file;
+use glm::{
+    self,
+    Vec3,
+};
+use render::Camera;
+
+// How far ahead of the free-fly camera its look-at point is taken to be when blending out of it.
+const FREE_FLY_TARGET_DISTANCE: f32 = 50.0;
+// Below this speed the player-locked camera keeps its last heading.
+const MIN_HEADING_SPEED: f32 = 0.5;
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum CameraMode {
+    BallFollow,
+    Sideline,
+    PlayerLocked,
+    FreeFly,
+}
+
+impl CameraMode {
+    // The order camera_next_mode cycles through. FreeFly is a debug mode with its own toggle.
+    fn next(self) -> CameraMode {
+        match self {
+            CameraMode::BallFollow => CameraMode::Sideline,
+            CameraMode::Sideline => CameraMode::PlayerLocked,
+            CameraMode::PlayerLocked => CameraMode::BallFollow,
+            CameraMode::FreeFly => CameraMode::BallFollow,
+        }
+    }
+}
+
+// Trails the ball from a fixed offset, aimed where the ball is heading.
+#[derive(Clone, Debug, Deserialize)]
+pub struct BallFollowConfig {
+    pub offset: [f32; 3],
+    // Exponential smoothing rate per second; higher catches up faster.
+    pub smoothing: f32,
+    // Aim at where the ball will be this far in the future.
+    pub look_ahead_seconds: f32,
+}
+
+// Slides along the rail from rail_start to rail_end to stay level with the ball.
+#[derive(Clone, Debug, Deserialize)]
+pub struct SidelineConfig {
+    pub rail_start: [f32; 3],
+    pub rail_end: [f32; 3],
+    pub smoothing: f32,
+}
+
+// Sits behind and above the athlete, facing where they run.
+#[derive(Clone, Debug, Deserialize)]
+pub struct PlayerLockedConfig {
+    pub distance: f32,
+    pub height: f32,
+    // Height above the athlete to aim at.
+    pub look_height: f32,
+    // Heading used until the athlete first moves.
+    pub initial_heading: [f32; 3],
+    pub smoothing: f32,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct CameraConfig {
+    pub initial_mode: CameraMode,
+    // Time taken to ease from one mode's shot into the next.
+    pub blend_seconds: f32,
+    pub ball_follow: BallFollowConfig,
+    pub sideline: SidelineConfig,
+    pub player_locked: PlayerLockedConfig,
+}
+
+impl CameraConfig {
+    // Reads the "camera" section of res/config/<scene_name>, so each arena frames its own shots.
+    pub fn read(scene_name: &str) -> StatusOr<CameraConfig> {
+        let scene: SceneConfig = file::util::read_config(scene_name)?;
+        Ok(scene.camera)
+    }
+}
+
+// The parts of a scene file the camera cares about.
+#[derive(Deserialize)]
+struct SceneConfig {
+    camera: CameraConfig,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct CameraPose {
+    position: Vec3,
+    target: Vec3,
+}
+
+impl CameraPose {
+    fn lerp(&self, other: &CameraPose, t: f32) -> CameraPose {
+        CameraPose {
+            position: self.position + (other.position - self.position) * t,
+            target: self.target + (other.target - self.target) * t,
+        }
+    }
+
+    // Moves towards goal at the given rate per second, independent of step size.
+    fn smooth_towards(&self, goal: &CameraPose, rate: f32, dt: DeltaTime) -> CameraPose {
+        self.lerp(goal, 1.0 - (-rate * dt.as_f32_seconds()).exp())
+    }
+}
+
+struct Blend {
+    from: CameraPose,
+    elapsed: f32,
+}
+
+// Drives the Camera from the world each step according to the current mode.
+pub struct CameraController {
+    config: CameraConfig,
+    camera: Camera,
+    mode: CameraMode,
+    // The mode to return to when leaving free-fly.
+    broadcast_mode: CameraMode,
+    blend: Option<Blend>,
+    pose: CameraPose,
+    // Each smoothed mode's own pose, so they keep tracking while another mode is shown.
+    ball_follow_pose: Option<CameraPose>,
+    sideline_pose: Option<CameraPose>,
+    player_locked_pose: Option<CameraPose>,
+    heading: Vec3,
+    previous_ball_position: Option<Vec3>,
+    previous_athlete_position: Option<Vec3>,
+}
+
+impl CameraController {
+    pub fn new(config: CameraConfig) -> CameraController {
+        let camera = Camera::new();
+        let pose = CameraController::free_fly_pose(&camera);
+        let heading = to_vec3(config.player_locked.initial_heading);
+        let mode = config.initial_mode;
+        CameraController {
+            config,
+            camera,
+            mode,
+            broadcast_mode: if mode == CameraMode::FreeFly { CameraMode::BallFollow } else { mode },
+            blend: None,
+            pose,
+            ball_follow_pose: None,
+            sideline_pose: None,
+            player_locked_pose: None,
+            heading: glm::builtin::normalize(heading),
+            previous_ball_position: None,
+            previous_athlete_position: None,
+        }
+    }
+
+    pub fn read(scene_name: &str) -> StatusOr<CameraController> {
+        Ok(CameraController::new(CameraConfig::read(scene_name)?))
+    }
+
+    pub fn camera(&self) -> &Camera {
+        &self.camera
+    }
+
+    pub fn mode(&self) -> CameraMode {
+        self.mode
+    }
+
+    // Switches to mode, easing from the current shot over blend_seconds.
+    pub fn set_mode(&mut self, mode: CameraMode) {
+        if mode == self.mode {
+            return;
+        }
+        if mode != CameraMode::FreeFly {
+            self.broadcast_mode = mode;
+        }
+        self.mode = mode;
+        // Free-fly takes over from wherever the camera is, so there is nothing to ease.
+        self.blend = if mode == CameraMode::FreeFly || self.config.blend_seconds <= 0.0 {
+            None
+        } else {
+            Some(Blend { from: self.pose, elapsed: 0.0 })
+        };
+    }
+
+    pub fn config(&self) -> &CameraConfig {
+        &self.config
+    }
+
+    pub fn set_config(&mut self, config: CameraConfig) {
+        self.config = config;
+    }
+
+    // Call after the world steps, so the shot frames where things are now.
+    pub fn update(&mut self, input: &Input, world: &PhysicalWorld, dt: DeltaTime) {
+        if input.just_pressed("camera_next_mode") {
+            let mode = self.broadcast_mode.next();
+            self.set_mode(mode);
+        }
+        if input.just_pressed("camera_free_fly") {
+            let mode = if self.mode == CameraMode::FreeFly { self.broadcast_mode } else { CameraMode::FreeFly };
+            self.set_mode(mode);
+        }
+
+        let seconds = dt.as_f32_seconds();
+        let ball_position = world.ball().position();
+        let ball_velocity = velocity(self.previous_ball_position, ball_position, seconds);
+        self.previous_ball_position = Some(ball_position);
+        let athlete_position = world.athlete().position();
+        let athlete_velocity = velocity(self.previous_athlete_position, athlete_position, seconds);
+        self.previous_athlete_position = Some(athlete_position);
+        let horizontal_velocity = glm::vec3(athlete_velocity.x, 0.0, athlete_velocity.z);
+        if glm::builtin::length(horizontal_velocity) > MIN_HEADING_SPEED {
+            self.heading = glm::builtin::normalize(horizontal_velocity);
+        }
+
+        let ball_follow = {
+            let config = &self.config.ball_follow;
+            let goal = CameraPose {
+                position: ball_position + to_vec3(config.offset),
+                target: ball_position + ball_velocity * config.look_ahead_seconds,
+            };
+            smooth(&mut self.ball_follow_pose, &goal, config.smoothing, dt)
+        };
+        let sideline = {
+            let config = &self.config.sideline;
+            let goal = CameraPose {
+                position: closest_on_segment(to_vec3(config.rail_start), to_vec3(config.rail_end), ball_position),
+                target: ball_position,
+            };
+            smooth(&mut self.sideline_pose, &goal, config.smoothing, dt)
+        };
+        let player_locked = {
+            let config = &self.config.player_locked;
+            let up = glm::vec3(0.0, 1.0, 0.0);
+            let goal = CameraPose {
+                position: athlete_position - self.heading * config.distance + up * config.height,
+                target: athlete_position + up * config.look_height,
+            };
+            smooth(&mut self.player_locked_pose, &goal, config.smoothing, dt)
+        };
+
+        let shot = match self.mode {
+            CameraMode::BallFollow => ball_follow,
+            CameraMode::Sideline => sideline,
+            CameraMode::PlayerLocked => player_locked,
+            CameraMode::FreeFly => {
+                self.camera.update(input, dt);
+                self.pose = CameraController::free_fly_pose(&self.camera);
+                return;
+            },
+        };
+
+        let blend_seconds = self.config.blend_seconds;
+        self.pose = match self.blend.take() {
+            Some(Blend { from, elapsed }) => {
+                let elapsed = elapsed + seconds;
+                let t = (elapsed / blend_seconds).min(1.0);
+                if t < 1.0 {
+                    self.blend = Some(Blend { from, elapsed });
+                }
+                // Smoothstep, so the camera eases out of one shot and into the next.
+                from.lerp(&shot, t * t * (3.0 - 2.0 * t))
+            },
+            None => shot,
+        };
+        self.camera.look_at(self.pose.position, self.pose.target);
+    }
+
+    fn free_fly_pose(camera: &Camera) -> CameraPose {
+        CameraPose {
+            position: camera.position(),
+            target: camera.position() + camera.lookat() * FREE_FLY_TARGET_DISTANCE,
+        }
+    }
+}
+
+// Advances a mode's smoothed pose, snapping to goal the first time.
+fn smooth(pose: &mut Option<CameraPose>, goal: &CameraPose, rate: f32, dt: DeltaTime) -> CameraPose {
+    let next = match *pose {
+        Some(ref current) => current.smooth_towards(goal, rate, dt),
+        None => *goal,
+    };
+    *pose = Some(next);
+    next
+}
+
+fn velocity(previous: Option<Vec3>, current: Vec3, seconds: f32) -> Vec3 {
+    match previous {
+        Some(previous) if seconds > 0.0 => (current - previous) / seconds,
+        _ => glm::vec3(0.0, 0.0, 0.0),
+    }
+}
+
+fn closest_on_segment(start: Vec3, end: Vec3, point: Vec3) -> Vec3 {
+    let segment = end - start;
+    let length_squared = glm::builtin::dot(segment, segment);
+    if length_squared <= 0.0 {
+        return start;
+    }
+    let t = (glm::builtin::dot(point - start, segment) / length_squared).max(0.0).min(1.0);
+    start + segment * t
+}
+
+fn to_vec3(v: [f32; 3]) -> Vec3 {
+    glm::vec3(v[0], v[1], v[2])
+}
@@ -0,0 +1,114 @@
+use app::StatusOr;
+use assets::{
+    AssetManager,
+    Handle,
+};
+use gl::{
+    self,
+    types::*,
+};
+use glm;
+use render::{
+    ForwardBlend,
+    ForwardContext,
+    ForwardDrawable,
+};
+use shader::ShaderProgram;
+use std;
+
+// Corners of the cube [-1, 1]^3, paired into its 12 edges.
+const CUBE_EDGES: [f32; 72] = [
+    -1.0, -1.0, -1.0,   1.0, -1.0, -1.0,
+    -1.0,  1.0, -1.0,   1.0,  1.0, -1.0,
+    -1.0, -1.0,  1.0,   1.0, -1.0,  1.0,
+    -1.0,  1.0,  1.0,   1.0,  1.0,  1.0,
+    -1.0, -1.0, -1.0,  -1.0,  1.0, -1.0,
+     1.0, -1.0, -1.0,   1.0,  1.0, -1.0,
+    -1.0, -1.0,  1.0,  -1.0,  1.0,  1.0,
+     1.0, -1.0,  1.0,   1.0,  1.0,  1.0,
+    -1.0, -1.0, -1.0,  -1.0, -1.0,  1.0,
+     1.0, -1.0, -1.0,   1.0, -1.0,  1.0,
+    -1.0,  1.0, -1.0,  -1.0,  1.0,  1.0,
+     1.0,  1.0, -1.0,   1.0,  1.0,  1.0,
+];
+
+// Wireframes of the athlete's and ball's physics colliders, for checking they match
+// their sprites.
+pub struct DebugBounds {
+    shader: Handle<ShaderProgram>,
+    vao: GLuint,
+    vbo: GLuint,
+}
+
+impl DebugBounds {
+    pub fn new(assets: &mut AssetManager) -> StatusOr<DebugBounds> {
+        let mut debug_bounds = DebugBounds {
+            shader: assets.shader("forward_unlit_vert.glsl", None, "forward_unlit_frag.glsl")?,
+            vao: 0,
+            vbo: 0,
+        };
+        unsafe {
+            gl::GenVertexArrays(1, &mut debug_bounds.vao);
+            gl::GenBuffers(1, &mut debug_bounds.vbo);
+            gl::BindVertexArray(debug_bounds.vao);
+            gl::BindBuffer(gl::ARRAY_BUFFER, debug_bounds.vbo);
+            let float_size = std::mem::size_of::<f32>() as isize;
+            let vertex_array_byte_size = CUBE_EDGES.len() as isize * float_size;
+            gl::BufferData(gl::ARRAY_BUFFER, vertex_array_byte_size, CUBE_EDGES.as_ptr() as *const GLvoid, gl::STATIC_DRAW);
+            // Vertex positions goes into attrib array = 0
+            gl::EnableVertexAttribArray(0);
+            gl::VertexAttribPointer(0, 3, gl::FLOAT, gl::FALSE, 3 * float_size as i32, std::ptr::null());
+            gl::BindVertexArray(0);
+        }
+        Ok(debug_bounds)
+    }
+
+    fn draw_box(&self, center: glm::Vec3, half_extents: glm::Vec3, color: &glm::Vec4) {
+        let identity = glm::mat4(
+            1.0, 0.0, 0.0, 0.0,
+            0.0, 1.0, 0.0, 0.0,
+            0.0, 0.0, 1.0, 0.0,
+            0.0, 0.0, 0.0, 1.0
+        );
+        let model = glm::ext::scale(&glm::ext::translate(&identity, center), half_extents);
+        self.shader.set_mat4("model", &model);
+        self.shader.set_vec4("color", color);
+        unsafe {
+            gl::BindVertexArray(self.vao);
+            gl::DrawArrays(gl::LINES, 0, (CUBE_EDGES.len() / 3) as i32);
+            gl::BindVertexArray(0);
+        }
+    }
+}
+
+impl ForwardDrawable for DebugBounds {
+    fn blend(&self) -> ForwardBlend {
+        ForwardBlend::Unlit
+    }
+
+    fn sort_position(&self, context: &ForwardContext) -> glm::Vec3 {
+        context.world.athlete().position()
+    }
+
+    fn draw(&self, context: &ForwardContext) {
+        self.shader.activate();
+        self.shader.set_mat4("projection_view", context.projection_view);
+        let (center, half_extents) = context.world.athlete().collider_bounds(context.alpha);
+        self.draw_box(center, half_extents, &glm::vec4(1.0, 0.2, 0.2, 1.0));
+        let (center, half_extents) = context.world.ball().collider_bounds(context.alpha);
+        self.draw_box(center, half_extents, &glm::vec4(0.2, 0.6, 1.0, 1.0));
+    }
+}
+
+impl Drop for DebugBounds {
+    fn drop(&mut self) {
+        unsafe {
+            if self.vao != 0 {
+                gl::DeleteVertexArrays(1, &self.vao);
+            }
+            if self.vbo != 0 {
+                gl::DeleteBuffers(1, &self.vbo);
+            }
+        }
+    }
+}
@@ -1,5 +1,8 @@
 use app::StatusOr;
-use file;
+use assets::{
+    AssetManager,
+    Handle,
+};
 use geometry::Transform;
 use gl::{
     self,
@@ -10,17 +13,15 @@ use shader::ShaderProgram;
 use std;
 
 pub struct DumbQuad {
-    shader: ShaderProgram,
+    shader: Handle<ShaderProgram>,
     transform: Transform,
     quad_vao: GLuint,
     quad_vbo: GLuint,
 }
 
 impl DumbQuad {
-    pub fn new() -> StatusOr<DumbQuad> {
-        let vert_path = file::util::resource_path("shaders", "dumb_quad_vert.glsl");
-        let frag_path = file::util::resource_path("shaders", "dumb_quad_frag.glsl");
-        let shader = ShaderProgram::from_short_pipeline(vert_path.as_str(), frag_path.as_str())?;
+    pub fn new(assets: &mut AssetManager) -> StatusOr<DumbQuad> {
+        let shader = assets.shader("dumb_quad_vert.glsl", None, "dumb_quad_frag.glsl")?;
         let mut dumb_quad = DumbQuad {
             shader,
             transform: Transform::new(),
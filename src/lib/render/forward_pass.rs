@@ -0,0 +1,95 @@
+use entity::PhysicalWorld;
+use gl;
+use glm;
+
+// Everything a forward draw might need for the current frame.
+pub struct ForwardContext<'a> {
+    pub world: &'a PhysicalWorld,
+    pub projection_view: &'a glm::Mat4,
+    pub camera_position: glm::Vec3,
+    // How far between the last two physics steps to draw entities.
+    pub alpha: f32,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ForwardBlend {
+    // Opaque and unaffected by scene lights, e.g. debug lines. Writes depth.
+    Unlit,
+    // Alpha blended over the scene, back to front. Tests depth but doesn't write it.
+    Transparent,
+}
+
+// Something drawn after the deferred lighting pass, straight into the default framebuffer.
+pub trait ForwardDrawable {
+    fn blend(&self) -> ForwardBlend;
+
+    // World-space point transparent draws are sorted by. Unused for unlit draws.
+    fn sort_position(&self, context: &ForwardContext) -> glm::Vec3;
+
+    fn draw(&self, context: &ForwardContext);
+}
+
+pub type ForwardDrawableId = usize;
+
+// Forward-rendered draws layered over the deferred scene. Expects the G-buffer's depth to
+// already be in the default framebuffer, so they depth-test against scene geometry.
+pub struct ForwardPass {
+    next_id: ForwardDrawableId,
+    drawables: Vec<(ForwardDrawableId, Box<dyn ForwardDrawable>)>,
+}
+
+impl ForwardPass {
+    pub fn new() -> ForwardPass {
+        ForwardPass {
+            next_id: 0,
+            drawables: Vec::new(),
+        }
+    }
+
+    // Draws drawable every frame until it's removed.
+    pub fn add(&mut self, drawable: Box<dyn ForwardDrawable>) -> ForwardDrawableId {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.drawables.push((id, drawable));
+        id
+    }
+
+    pub fn remove(&mut self, id: ForwardDrawableId) -> Option<Box<dyn ForwardDrawable>> {
+        let index = self.drawables.iter().position(|&(drawable_id, _)| drawable_id == id)?;
+        Some(self.drawables.remove(index).1)
+    }
+
+    pub fn draw(&self, context: &ForwardContext) {
+        for &(_, ref drawable) in self.drawables.iter() {
+            if drawable.blend() == ForwardBlend::Unlit {
+                drawable.draw(context);
+            }
+        }
+
+        let distance = |drawable: &Box<dyn ForwardDrawable>| {
+            glm::builtin::distance(drawable.sort_position(context), context.camera_position)
+        };
+        let mut transparent: Vec<_> = self.drawables.iter()
+            .map(|&(_, ref drawable)| drawable)
+            .filter(|drawable| drawable.blend() == ForwardBlend::Transparent)
+            .map(|drawable| (distance(drawable), drawable))
+            .collect();
+        if transparent.is_empty() {
+            return;
+        }
+        // Farthest first.
+        transparent.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(::std::cmp::Ordering::Equal));
+        unsafe {
+            gl::Enable(gl::BLEND);
+            gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+            gl::DepthMask(gl::FALSE);
+        }
+        for &(_, drawable) in transparent.iter() {
+            drawable.draw(context);
+        }
+        unsafe {
+            gl::DepthMask(gl::TRUE);
+            gl::Disable(gl::BLEND);
+        }
+    }
+}
@@ -0,0 +1,152 @@
+use app::{
+    FrameStats,
+    StatusOr,
+};
+use assets::{
+    AssetManager,
+    Handle,
+};
+use font::Alignment;
+use gl::{
+    self,
+    types::*,
+};
+use glm;
+use render::TextRenderer;
+use shader::ShaderProgram;
+use std;
+
+// Panel corners, in normalized device coordinates.
+const LEFT: f32 = -0.98;
+const RIGHT: f32 = -0.38;
+const BOTTOM: f32 = -0.98;
+const TOP: f32 = -0.68;
+// A frame this long fills the panel's height.
+const MAX_FRAME_MS: f32 = 50.0;
+const TARGET_60_FPS_MS: f32 = 1000.0 / 60.0;
+const TARGET_30_FPS_MS: f32 = 1000.0 / 30.0;
+// x, y, r, g, b, a
+const FLOATS_PER_VERTEX: usize = 6;
+const TEXT_PIXEL_HEIGHT: f32 = 16.0;
+
+fn push_quad(vertices: &mut Vec<f32>, left: f32, bottom: f32, right: f32, top: f32, color: [f32; 4]) {
+    let corners = [(left, bottom), (right, bottom), (right, top), (left, bottom), (right, top), (left, top)];
+    for &(x, y) in corners.iter() {
+        vertices.extend_from_slice(&[x, y]);
+        vertices.extend_from_slice(&color);
+    }
+}
+
+fn frame_height(frame_ms: f32) -> f32 {
+    (frame_ms / MAX_FRAME_MS).min(1.0) * (TOP - BOTTOM)
+}
+
+// Draws a frame time graph in the bottom left corner: one bar per frame in the window,
+// green under 60 fps, yellow under 30 fps and red past that, with lines marking both. The
+// window's summary is printed above it.
+pub struct FrameStatsOverlay {
+    shader: Handle<ShaderProgram>,
+    vao: GLuint,
+    vbo: GLuint,
+}
+
+impl FrameStatsOverlay {
+    pub fn new(assets: &mut AssetManager) -> StatusOr<FrameStatsOverlay> {
+        let mut overlay = FrameStatsOverlay {
+            shader: assets.shader("overlay_vert.glsl", None, "overlay_frag.glsl")?,
+            vao: 0,
+            vbo: 0,
+        };
+        unsafe {
+            gl::GenVertexArrays(1, &mut overlay.vao);
+            gl::GenBuffers(1, &mut overlay.vbo);
+            gl::BindVertexArray(overlay.vao);
+            gl::BindBuffer(gl::ARRAY_BUFFER, overlay.vbo);
+            let float_size = std::mem::size_of::<f32>() as isize;
+            let stride = FLOATS_PER_VERTEX as i32 * float_size as i32;
+            // Positions go into attrib array = 0
+            gl::EnableVertexAttribArray(0);
+            gl::VertexAttribPointer(0, 2, gl::FLOAT, gl::FALSE, stride, std::ptr::null());
+            // Colors go into attrib array = 1
+            let color_offset = (std::ptr::null() as *const GLvoid).offset(2 * float_size);
+            gl::EnableVertexAttribArray(1);
+            gl::VertexAttribPointer(1, 4, gl::FLOAT, gl::FALSE, stride, color_offset);
+            gl::BindVertexArray(0);
+        }
+        Ok(overlay)
+    }
+
+    fn vertices(frame_stats: &FrameStats) -> Vec<f32> {
+        let mut vertices = Vec::new();
+        push_quad(&mut vertices, LEFT, BOTTOM, RIGHT, TOP, [0.0, 0.0, 0.0, 0.6]);
+
+        let bar_width = (RIGHT - LEFT) / frame_stats.window_size().max(1) as f32;
+        for (i, frame_time) in frame_stats.frame_times().iter().enumerate() {
+            let frame_ms = *frame_time as f32 / 1000.0;
+            let color = if frame_ms <= TARGET_60_FPS_MS {
+                [0.2, 0.9, 0.2, 0.9]
+            } else if frame_ms <= TARGET_30_FPS_MS {
+                [0.9, 0.9, 0.2, 0.9]
+            } else {
+                [0.9, 0.2, 0.2, 0.9]
+            };
+            let left = LEFT + i as f32 * bar_width;
+            push_quad(&mut vertices, left, BOTTOM, left + bar_width, BOTTOM + frame_height(frame_ms), color);
+        }
+
+        let line_thickness = 0.004;
+        for &target_ms in [TARGET_60_FPS_MS, TARGET_30_FPS_MS].iter() {
+            let y = BOTTOM + frame_height(target_ms);
+            push_quad(&mut vertices, LEFT, y, RIGHT, y + line_thickness, [1.0, 1.0, 1.0, 0.5]);
+        }
+        vertices
+    }
+
+    // Draws straight to the bound framebuffer, over whatever is there.
+    pub fn draw(&self, frame_stats: &FrameStats, text_renderer: &TextRenderer, screen_size: (u32, u32)) {
+        self.draw_graph(frame_stats);
+        if let Some(summary) = frame_stats.summary() {
+            let text = format!("{:.1} fps\nframe ms: min {:.2}  avg {:.2}  max {:.2}  p99 {:.2}\nphysics ms: avg {:.2}  max {:.2}",
+                               summary.fps, summary.min_frame_ms, summary.avg_frame_ms, summary.max_frame_ms,
+                               summary.p99_frame_ms, summary.avg_physics_step_ms, summary.max_physics_step_ms);
+            let line_height = text_renderer.atlas().line_height() * TEXT_PIXEL_HEIGHT / text_renderer.atlas().pixel_height();
+            let x = (LEFT + 1.0) / 2.0 * screen_size.0 as f32;
+            let graph_top = (1.0 - TOP) / 2.0 * screen_size.1 as f32;
+            text_renderer.draw(text.as_str(), x, graph_top - 3.0 * line_height, TEXT_PIXEL_HEIGHT,
+                               Alignment::Left, &glm::vec4(1.0, 1.0, 1.0, 1.0), screen_size);
+        }
+    }
+
+    fn draw_graph(&self, frame_stats: &FrameStats) {
+        let vertices = Self::vertices(frame_stats);
+        self.shader.activate();
+        unsafe {
+            gl::Disable(gl::DEPTH_TEST);
+            gl::Enable(gl::BLEND);
+            gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+
+            gl::BindVertexArray(self.vao);
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo);
+            let vertex_array_byte_size = (vertices.len() * std::mem::size_of::<f32>()) as isize;
+            gl::BufferData(gl::ARRAY_BUFFER, vertex_array_byte_size, vertices.as_ptr() as *const GLvoid, gl::STREAM_DRAW);
+            gl::DrawArrays(gl::TRIANGLES, 0, (vertices.len() / FLOATS_PER_VERTEX) as i32);
+            gl::BindVertexArray(0);
+
+            gl::Disable(gl::BLEND);
+            gl::Enable(gl::DEPTH_TEST);
+        }
+    }
+}
+
+impl Drop for FrameStatsOverlay {
+    fn drop(&mut self) {
+        unsafe {
+            if self.vao != 0 {
+                gl::DeleteVertexArrays(1, &self.vao);
+            }
+            if self.vbo != 0 {
+                gl::DeleteBuffers(1, &self.vbo);
+            }
+        }
+    }
+}
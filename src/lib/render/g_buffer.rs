@@ -1,9 +1,21 @@
-use app::StatusOr;
+use app::{
+    Error,
+    StatusOr,
+};
+use assets::{
+    AssetManager,
+    Handle,
+};
 use gl::{
     self,
     types::*,
 };
-use file;
+use glm;
+use render::{
+    lights,
+    Lights,
+    ShadowFrame,
+};
 use shader::ShaderProgram;
 use std;
 
@@ -22,13 +34,13 @@ pub struct GBuffer {
     depth_render_buffer: GLuint,
     quad_vao: GLuint,
     quad_vbo: GLuint,
-    lighting_pass_shader: ShaderProgram,
+    lighting_pass_shader: Handle<ShaderProgram>,
+    width: GLsizei,
+    height: GLsizei,
 }
 
 impl GBuffer {
-    pub fn new() -> StatusOr<GBuffer> {
-        let vert_path = file::util::resource_path("shaders", "deferred_lighting_vert.glsl");
-        let frag_path = file::util::resource_path("shaders", "deferred_lighting_frag.glsl");
+    pub fn new(assets: &mut AssetManager) -> StatusOr<GBuffer> {
         let g_buffer = GBuffer {
             frame_buffer: 0,
             position_texture: 0,
@@ -37,14 +49,17 @@ impl GBuffer {
             depth_render_buffer: 0,
             quad_vao: 0,
             quad_vbo: 0,
-            lighting_pass_shader: ShaderProgram::from_short_pipeline(
-                vert_path.as_str(), frag_path.as_str())?
+            lighting_pass_shader: assets.shader("deferred_lighting_vert.glsl", None, "deferred_lighting_frag.glsl")?,
+            width: 0,
+            height: 0,
         };
         Ok(g_buffer)
     }
 
     pub fn gl_init(&mut self, width: GLsizei, height: GLsizei) -> StatusOr<()> {
         self.clear();
+        self.width = width;
+        self.height = height;
         unsafe {
             gl::GenFramebuffers(1, &mut self.frame_buffer);
             gl::BindFramebuffer(gl::FRAMEBUFFER, self.frame_buffer);
@@ -88,11 +103,15 @@ impl GBuffer {
             gl::DrawBuffers(attachments.len() as i32, attachments.as_ptr());
             gl::GenRenderbuffers(1, &mut self.depth_render_buffer);
             gl::BindRenderbuffer(gl::RENDERBUFFER, self.depth_render_buffer);
-            gl::RenderbufferStorage(gl::RENDERBUFFER, gl::DEPTH_COMPONENT, width, height);
-            gl::FramebufferRenderbuffer(gl::FRAMEBUFFER, gl::DEPTH_ATTACHMENT, gl::RENDERBUFFER, self.depth_render_buffer);
+            // Depth blits need identical formats on both sides. AppRunner asks for a 24 bit depth,
+            // 8 bit stencil default framebuffer, which drivers hand back as DEPTH24_STENCIL8.
+            gl::RenderbufferStorage(gl::RENDERBUFFER, gl::DEPTH24_STENCIL8, width, height);
+            gl::FramebufferRenderbuffer(gl::FRAMEBUFFER, gl::DEPTH_STENCIL_ATTACHMENT, gl::RENDERBUFFER, self.depth_render_buffer);
 
-            if gl::CheckFramebufferStatus(gl::FRAMEBUFFER) != gl::FRAMEBUFFER_COMPLETE {
-                return Err(String::from("Framebuffer not complete!"));
+            let status = gl::CheckFramebufferStatus(gl::FRAMEBUFFER);
+            if status != gl::FRAMEBUFFER_COMPLETE {
+                gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+                return Err(Error::FramebufferIncomplete { status });
             }
             gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
         }
@@ -102,6 +121,7 @@ impl GBuffer {
         self.lighting_pass_shader.set_i32("position_tex", 0); // Attachment values
         self.lighting_pass_shader.set_i32("normal_tex", 1);
         self.lighting_pass_shader.set_i32("color_tex", 2);
+        self.lighting_pass_shader.set_uniform_block_binding("Lights", lights::LIGHTS_BINDING);
 
         // Prepare lighting pass quad.
         let vertices: [f32; 20] = [
@@ -137,12 +157,16 @@ impl GBuffer {
         }
     }
 
-    pub fn lighting_pass(&self) {
+    // Shades the G-buffer with lights, as seen from view_position, occluded by shadows.
+    pub fn lighting_pass(&self, lights: &Lights, shadows: &ShadowFrame, view_position: &glm::Vec3) {
         unsafe {
             gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
             gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
         }
         self.lighting_pass_shader.activate();
+        self.lighting_pass_shader.set_vec3("view_position", view_position);
+        lights.bind();
+        shadows.bind(&self.lighting_pass_shader);
         unsafe {
             gl::ActiveTexture(gl::TEXTURE0);
             gl::BindTexture(gl::TEXTURE_2D, self.position_texture);
@@ -158,6 +182,18 @@ impl GBuffer {
         }
     }
 
+    // Copies the geometry pass's depth into the default framebuffer, so draws after the
+    // lighting pass are occluded by the deferred scene.
+    pub fn blit_depth(&self) {
+        unsafe {
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, self.frame_buffer);
+            gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, 0);
+            gl::BlitFramebuffer(0, 0, self.width, self.height, 0, 0, self.width, self.height,
+                                gl::DEPTH_BUFFER_BIT, gl::NEAREST);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
+    }
+
     fn clear(&mut self) {
         unsafe {
             if self.frame_buffer != 0 {
@@ -0,0 +1,104 @@
+use app::StatusOr;
+use assets::{
+    AssetManager,
+    Handle,
+};
+use entity::{
+    hex_grid::HEXAGON_SCALE,
+    HexGrid,
+};
+use gl::{
+    self,
+    types::*
+};
+use glm;
+use shader::ShaderProgram;
+use std;
+
+// GPU side of a HexGrid. Hexagons are expanded from points in hex_grid_geo.glsl.
+pub struct HexGridRenderer {
+    shader: Handle<ShaderProgram>,
+    vao: GLuint,
+    positions_vbo: GLuint,
+    heights_vbo: GLuint,
+    num_hexagons: usize,
+}
+
+impl HexGridRenderer {
+    pub fn new(hex_grid: &HexGrid, assets: &mut AssetManager) -> StatusOr<HexGridRenderer> {
+        let shader = assets.shader("hex_grid_vert.glsl", Some("hex_grid_geo.glsl"), "hex_grid_frag.glsl")?;
+
+        let mut renderer = HexGridRenderer {
+            shader,
+            vao: 0,
+            positions_vbo: 0,
+            heights_vbo: 0,
+            num_hexagons: hex_grid.positions().len(),
+        };
+        renderer.gl_init(hex_grid.positions(), hex_grid.heights());
+        Ok(renderer)
+    }
+
+    fn gl_init(&mut self, positions: &[glm::Vec2], heights: &[f32]) {
+        unsafe {
+            // Setup vao.
+            gl::GenVertexArrays(1, &mut self.vao);
+            gl::BindVertexArray(self.vao);
+
+            // Attribute 0 --> positions.
+            gl::GenBuffers(1, &mut self.positions_vbo);
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.positions_vbo);
+            let vec2_size = std::mem::size_of::<glm::Vec2>() as isize;
+            let positions_size = positions.len() as isize * vec2_size;
+            gl::BufferData(gl::ARRAY_BUFFER, positions_size, positions.as_ptr() as *const GLvoid, gl::STATIC_DRAW);
+            gl::EnableVertexAttribArray(0);
+            gl::VertexAttribPointer(0, 2, gl::FLOAT, gl::FALSE, vec2_size as i32, std::ptr::null());
+            gl::VertexAttribDivisor(0, 1);
+
+            // Attribute 1 --> heights.
+            gl::GenBuffers(1, &mut self.heights_vbo);
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.heights_vbo);
+            let f32_size = std::mem::size_of::<f32>() as isize;
+            let heights_size = heights.len() as isize * f32_size;
+            gl::BufferData(gl::ARRAY_BUFFER, heights_size, heights.as_ptr() as *const GLvoid, gl::STATIC_DRAW);
+            gl::EnableVertexAttribArray(1);
+            gl::VertexAttribPointer(1, 2, gl::FLOAT, gl::FALSE, f32_size as i32, std::ptr::null());
+            gl::VertexAttribDivisor(1, 1);
+
+            // Cleanup
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+            gl::BindVertexArray(0);
+        }
+    }
+
+    pub fn draw(&self, projection_view: &glm::Mat4) {
+        self.shader.activate();
+
+        let axial_to_cartesian_mat = HexGrid::axial_to_cartesian();
+        self.shader.set_f32("hexagon_scale", HEXAGON_SCALE);
+        self.shader.set_mat2("axial_to_cartesian", &axial_to_cartesian_mat);
+        self.shader.set_mat4("projection_view", projection_view);
+
+        unsafe {
+            gl::BindVertexArray(self.vao);
+            gl::DrawArraysInstanced(gl::POINTS, 0, 36, self.num_hexagons as GLsizei);
+            gl::BindVertexArray(0);
+        }
+    }
+}
+
+impl Drop for HexGridRenderer {
+    fn drop(&mut self) {
+        unsafe {
+            if self.vao != 0 {
+                gl::DeleteVertexArrays(1, &self.vao);
+            }
+            if self.positions_vbo != 0 {
+                gl::DeleteBuffers(1, &self.positions_vbo);
+            }
+            if self.heights_vbo != 0 {
+                gl::DeleteBuffers(1, &self.heights_vbo);
+            }
+        }
+    }
+}
@@ -0,0 +1,208 @@
+use app::{
+    Error,
+    StatusOr,
+};
+use file;
+use gl::{
+    self,
+    types::*,
+};
+use render::shadow_maps::{
+    MAX_SPOT_SHADOWS,
+    ShadowConfig,
+};
+use std;
+
+// Must match MAX_LIGHTS in deferred_lighting_frag.glsl.
+pub const MAX_LIGHTS: usize = 32;
+// The uniform buffer binding point the Lights block reads from.
+pub const LIGHTS_BINDING: GLuint = 0;
+
+// Light types, as stored in position_type.w in the shader.
+const POINT_LIGHT: f32 = 0.0;
+const SPOT_LIGHT: f32 = 1.0;
+const DIRECTIONAL_LIGHT: f32 = 2.0;
+// Per light: position_type, direction, color, attenuation, cone.
+const VEC4S_PER_LIGHT: usize = 5;
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub struct Attenuation {
+    pub constant: f32,
+    pub linear: f32,
+    pub quadratic: f32,
+}
+
+// Colors are linear RGB, scaled by intensity in the shader.
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Light {
+    Point {
+        position: [f32; 3],
+        color: [f32; 3],
+        intensity: f32,
+        attenuation: Attenuation,
+    },
+    // Full strength inside inner_cone_degrees, fading out by outer_cone_degrees.
+    Spot {
+        position: [f32; 3],
+        direction: [f32; 3],
+        color: [f32; 3],
+        intensity: f32,
+        attenuation: Attenuation,
+        inner_cone_degrees: f32,
+        outer_cone_degrees: f32,
+        #[serde(default)]
+        casts_shadow: bool,
+    },
+    // The first shadowed directional light is the scene's main light, and gets cascades.
+    Directional {
+        direction: [f32; 3],
+        color: [f32; 3],
+        intensity: f32,
+        #[serde(default)]
+        casts_shadow: bool,
+    },
+}
+
+impl Light {
+    // Appends this light's std140 layout to buffer. shadow_layer is -1 for unshadowed lights.
+    fn pack(&self, shadow_layer: f32, buffer: &mut Vec<f32>) {
+        let no_attenuation = Attenuation { constant: 1.0, linear: 0.0, quadratic: 0.0 };
+        let (kind, position, direction, color, intensity, attenuation, cone) = match *self {
+            Light::Point { position, color, intensity, attenuation, .. } =>
+                (POINT_LIGHT, position, [0.0, -1.0, 0.0], color, intensity, attenuation, [-1.0, -1.0]),
+            Light::Spot { position, direction, color, intensity, attenuation, inner_cone_degrees, outer_cone_degrees, .. } => {
+                let cone = [inner_cone_degrees.to_radians().cos(), outer_cone_degrees.to_radians().cos()];
+                (SPOT_LIGHT, position, direction, color, intensity, attenuation, cone)
+            },
+            Light::Directional { direction, color, intensity, .. } =>
+                (DIRECTIONAL_LIGHT, [0.0; 3], direction, color, intensity, no_attenuation, [-1.0, -1.0]),
+        };
+        let length = (direction[0] * direction[0] + direction[1] * direction[1] + direction[2] * direction[2]).sqrt().max(1e-6);
+        buffer.extend_from_slice(&[position[0], position[1], position[2], kind]);
+        buffer.extend_from_slice(&[direction[0] / length, direction[1] / length, direction[2] / length, 0.0]);
+        buffer.extend_from_slice(&[color[0] * intensity, color[1] * intensity, color[2] * intensity, 0.0]);
+        buffer.extend_from_slice(&[attenuation.constant, attenuation.linear, attenuation.quadratic, 0.0]);
+        buffer.extend_from_slice(&[cone[0], cone[1], shadow_layer, 0.0]);
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct LightingConfig {
+    pub ambient: [f32; 3],
+    pub lights: Vec<Light>,
+    #[serde(default)]
+    pub shadows: ShadowConfig,
+}
+
+// Which lights get shadow maps, as indices into LightingConfig::lights.
+pub struct ShadowCasters {
+    pub main_light: Option<usize>,
+    // At most MAX_SPOT_SHADOWS; later shadowed spots are lit without shadows.
+    pub spots: Vec<usize>,
+}
+
+// The parts of a scene file (res/config/<scene>.config) lighting cares about.
+#[derive(Deserialize)]
+struct SceneConfig {
+    lighting: LightingConfig,
+}
+
+// A scene's lights, mirrored into a uniform buffer for GBuffer::lighting_pass.
+pub struct Lights {
+    config: LightingConfig,
+    ubo: GLuint,
+}
+
+impl Lights {
+    pub fn new(config: LightingConfig) -> StatusOr<Lights> {
+        let mut lights = Lights {
+            config,
+            ubo: 0,
+        };
+        unsafe {
+            gl::GenBuffers(1, &mut lights.ubo);
+        }
+        lights.upload()?;
+        Ok(lights)
+    }
+
+    // Reads the "lighting" section of res/config/<scene_name>.
+    pub fn read(scene_name: &str) -> StatusOr<Lights> {
+        let scene: SceneConfig = file::util::read_config(scene_name)?;
+        Lights::new(scene.lighting)
+    }
+
+    pub fn config(&self) -> &LightingConfig {
+        &self.config
+    }
+
+    // Swaps in new lights, e.g. to switch floodlights on at dusk.
+    pub fn set_config(&mut self, config: LightingConfig) -> StatusOr<()> {
+        self.config = config;
+        self.upload()
+    }
+
+    pub fn shadow_casters(&self) -> ShadowCasters {
+        let mut casters = ShadowCasters {
+            main_light: None,
+            spots: Vec::new(),
+        };
+        for (index, light) in self.config.lights.iter().enumerate() {
+            match *light {
+                Light::Directional { casts_shadow: true, .. } if casters.main_light.is_none() =>
+                    casters.main_light = Some(index),
+                Light::Spot { casts_shadow: true, .. } if casters.spots.len() < MAX_SPOT_SHADOWS =>
+                    casters.spots.push(index),
+                _ => (),
+            }
+        }
+        casters
+    }
+
+    fn upload(&self) -> StatusOr<()> {
+        let num_lights = self.config.lights.len();
+        if num_lights > MAX_LIGHTS {
+            return Err(Error::InvalidArgument(format!("{} lights exceeds the maximum of {}", num_lights, MAX_LIGHTS)));
+        }
+
+        // std140: vec4 ambient_count, then Light lights[MAX_LIGHTS].
+        let mut buffer = Vec::with_capacity(4 + MAX_LIGHTS * VEC4S_PER_LIGHT * 4);
+        let ambient = self.config.ambient;
+        buffer.extend_from_slice(&[ambient[0], ambient[1], ambient[2], num_lights as f32]);
+        // Cascades for the main light, or a layer of the spot shadow array.
+        let casters = self.shadow_casters();
+        for (index, light) in self.config.lights.iter().enumerate() {
+            let shadow_layer = if casters.main_light == Some(index) {
+                0.0
+            } else {
+                casters.spots.iter().position(|spot| *spot == index).map(|layer| layer as f32).unwrap_or(-1.0)
+            };
+            light.pack(shadow_layer, &mut buffer);
+        }
+        buffer.resize(4 + MAX_LIGHTS * VEC4S_PER_LIGHT * 4, 0.0);
+        unsafe {
+            gl::BindBuffer(gl::UNIFORM_BUFFER, self.ubo);
+            let buffer_byte_size = (buffer.len() * std::mem::size_of::<f32>()) as isize;
+            gl::BufferData(gl::UNIFORM_BUFFER, buffer_byte_size, buffer.as_ptr() as *const GLvoid, gl::DYNAMIC_DRAW);
+            gl::BindBuffer(gl::UNIFORM_BUFFER, 0);
+        }
+        Ok(())
+    }
+
+    pub fn bind(&self) {
+        unsafe {
+            gl::BindBufferBase(gl::UNIFORM_BUFFER, LIGHTS_BINDING, self.ubo);
+        }
+    }
+}
+
+impl Drop for Lights {
+    fn drop(&mut self) {
+        if self.ubo != 0 {
+            unsafe {
+                gl::DeleteBuffers(1, &self.ubo);
+            }
+        }
+    }
+}
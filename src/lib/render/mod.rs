@@ -1,11 +1,51 @@
 pub mod camera;
+pub mod camera_controller;
+pub mod debug_bounds;
 pub mod dumb_quad;
+pub mod forward_pass;
+pub mod frame_stats_overlay;
 pub mod g_buffer;
+pub mod hex_grid_renderer;
+pub mod lights;
 pub mod render_state;
+pub mod shadow_maps;
 pub mod sprite_3d;
+pub mod text_renderer;
+pub mod world_renderer;
 
 pub use self::camera::Camera;
+pub use self::camera_controller::CameraConfig;
+pub use self::camera_controller::CameraController;
+pub use self::camera_controller::CameraMode;
+pub use self::debug_bounds::DebugBounds;
+pub use self::forward_pass::ForwardBlend;
+pub use self::forward_pass::ForwardContext;
+pub use self::forward_pass::ForwardDrawable;
+pub use self::forward_pass::ForwardDrawableId;
+pub use self::forward_pass::ForwardPass;
+pub use self::frame_stats_overlay::FrameStatsOverlay;
 pub use self::g_buffer::GBuffer;
+pub use self::hex_grid_renderer::HexGridRenderer;
+pub use self::lights::Light;
+pub use self::lights::Lights;
 pub use self::render_state::RenderState;
+pub use self::shadow_maps::ShadowConfig;
+pub use self::shadow_maps::ShadowFrame;
+pub use self::shadow_maps::ShadowMaps;
+pub use self::sprite_3d::AnimationConfig;
+pub use self::sprite_3d::AnimationEvent;
+pub use self::sprite_3d::DepthMapConfig;
+pub use self::sprite_3d::FrameRef;
+pub use self::sprite_3d::DepthMapInfo;
+pub use self::sprite_3d::Playback;
+pub use self::sprite_3d::ReifyBackend;
+pub use self::sprite_3d::REIFY_VERSION;
+pub use self::sprite_3d::SheetLayout;
 pub use self::sprite_3d::Sprite3D;
 pub use self::sprite_3d::Sprite3DSheetInfo;
+pub use self::sprite_3d::SpriteAnimation;
+pub use self::sprite_3d::SpriteFrame;
+pub use self::sprite_3d::SpriteSheet;
+pub use self::sprite_3d::StateConfig;
+pub use self::text_renderer::TextRenderer;
+pub use self::world_renderer::WorldRenderer;
@@ -1,38 +1,128 @@
-use app::StatusOr;
-use controls::KeyboardControls;
+use app::{
+    frame_stats,
+    FrameStats,
+    StatusOr,
+};
+use assets::AssetManager;
+use controls::Input;
 use dimensions::time::DeltaTime;
 use entity::PhysicalWorld;
 use gl;
 use render::{
     Camera,
+    CameraController,
+    DebugBounds,
+    ForwardContext,
+    ForwardDrawable,
+    ForwardDrawableId,
+    ForwardPass,
+    FrameStatsOverlay,
     GBuffer,
+    Lights,
+    ShadowMaps,
+    TextRenderer,
+    WorldRenderer,
 };
 use sdl2::render::WindowCanvas;
+use std::time::Instant;
 
 pub struct RenderState {
-    camera: Camera,
+    // Kept for the life of the renderer, so whatever is created later shares what's loaded.
+    assets: AssetManager,
+    camera_controller: CameraController,
     g_buffer: GBuffer,
+    lights: Lights,
+    shadow_maps: ShadowMaps,
     physical_world: PhysicalWorld,
-    // std::unique_ptr<frame_rate> render_frames;
+    world_renderer: WorldRenderer,
+    frame_stats: FrameStats,
+    frame_stats_overlay: FrameStatsOverlay,
+    show_frame_stats: bool,
+    text_renderer: TextRenderer,
+    forward_pass: ForwardPass,
+    // Parked here while it isn't in forward_pass.
+    debug_bounds: Option<Box<dyn ForwardDrawable>>,
+    debug_bounds_id: Option<ForwardDrawableId>,
 }
 
 impl RenderState {
     pub fn new(width: i32, height: i32) -> StatusOr<RenderState> {
         unsafe { gl::Enable(gl::DEPTH_TEST); }
-        // render_frames.reset(new frame_rate);
+        let mut assets = AssetManager::new();
+        let physical_world = PhysicalWorld::new()?;
+        let world_renderer = WorldRenderer::new(&physical_world, &mut assets)?;
+        let g_buffer = GBuffer::new(&mut assets)?;
+        let frame_stats_overlay = FrameStatsOverlay::new(&mut assets)?;
+        let text_renderer = TextRenderer::new("aileron.otf", 32, &mut assets)?;
+        let lights = Lights::read("scene.config")?;
+        let shadow_maps = ShadowMaps::new(&lights.config().shadows)?;
         let mut render = RenderState {
-            camera: Camera::new(),
-            g_buffer: GBuffer::new()?,
-            physical_world: PhysicalWorld::new()?,
+            camera_controller: CameraController::read("scene.config")?,
+            g_buffer,
+            lights,
+            shadow_maps,
+            physical_world,
+            world_renderer,
+            frame_stats: FrameStats::new(frame_stats::DEFAULT_WINDOW_SIZE),
+            frame_stats_overlay,
+            show_frame_stats: false,
+            text_renderer,
+            forward_pass: ForwardPass::new(),
+            debug_bounds: Some(Box::new(DebugBounds::new(&mut assets)?)),
+            debug_bounds_id: None,
+            assets,
         };
         render.resize(width, height)?;
         Ok(render)
     }
 
-    pub fn update(&mut self, keyboard: &KeyboardControls, dt: DeltaTime) {
-        self.camera.update(keyboard, dt);
-        self.physical_world.update(keyboard, dt);
-        // render_frames->Update(dt);
+    pub fn update(&mut self, input: &Input, dt: DeltaTime) {
+        if input.just_pressed("toggle_frame_stats") {
+            self.show_frame_stats = !self.show_frame_stats;
+        }
+        if input.just_pressed("toggle_debug_bounds") {
+            self.toggle_debug_bounds();
+        }
+        let physics_start = Instant::now();
+        self.physical_world.update(input, dt);
+        self.frame_stats.record_physics_step(DeltaTime::new(physics_start.elapsed()));
+        self.camera_controller.update(input, &self.physical_world, dt);
+    }
+
+    // dt is the wall time since the previous frame.
+    pub fn record_frame(&mut self, dt: DeltaTime) {
+        self.frame_stats.record_frame(dt);
+    }
+
+    pub fn frame_stats(&self) -> &FrameStats {
+        &self.frame_stats
+    }
+
+    pub fn camera_controller_mut(&mut self) -> &mut CameraController {
+        &mut self.camera_controller
+    }
+
+    pub fn lights_mut(&mut self) -> &mut Lights {
+        &mut self.lights
+    }
+
+    // For creating sprites and renderers after startup, e.g. for another athlete.
+    pub fn assets_mut(&mut self) -> &mut AssetManager {
+        &mut self.assets
+    }
+
+    // For adding transparent and unlit draws that depth-test against the deferred scene.
+    pub fn forward_pass_mut(&mut self) -> &mut ForwardPass {
+        &mut self.forward_pass
+    }
+
+    fn toggle_debug_bounds(&mut self) {
+        match self.debug_bounds_id.take() {
+            Some(id) => self.debug_bounds = self.forward_pass.remove(id),
+            None => if let Some(debug_bounds) = self.debug_bounds.take() {
+                self.debug_bounds_id = Some(self.forward_pass.add(debug_bounds));
+            },
+        }
     }
 
     pub fn resize(&mut self, width: i32, height: i32) -> StatusOr<()> {
@@ -43,24 +133,42 @@ impl RenderState {
         Ok(())
     }
 
-    pub fn draw(&self, canvas: &mut WindowCanvas) {
+    pub fn draw(&self, canvas: &mut WindowCanvas, alpha: f32) {
         unsafe {
             gl::ClearColor(0.0177, 0.0177, 0.0477, 1.0);
             gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
         }
 
+        let camera = self.camera_controller.camera();
+        let projection_view = camera.projection(canvas) * camera.view();
+
+        // 0. Shadow maps, from the same geometry.
+        let (width, height) = canvas.window().size();
+        let shadows = self.shadow_maps.render(&self.lights, camera, Camera::aspect(canvas), (width as i32, height as i32),
+                                              |light_projection_view| self.world_renderer.draw(&self.physical_world, light_projection_view, alpha));
+
         // 1. Draw all geometry.
         self.g_buffer.geometry_pass(); {
-            let projection_view = self.camera.projection(canvas) * self.camera.view();
-            self.physical_world.draw(&projection_view);
+            self.world_renderer.draw(&self.physical_world, &projection_view, alpha);
         }
 
         // 2. Lighting pass
-        self.g_buffer.lighting_pass();
+        self.g_buffer.lighting_pass(&self.lights, &shadows, &camera.position());
 
-        // 3. Non-geometric superimposed draw calls.
-        // TODO: Copy depth buffer.
-        // render_frames->Draw(window);
+        // 3. Forward pass, depth-tested against the scene.
+        self.g_buffer.blit_depth();
+        self.forward_pass.draw(&ForwardContext {
+            world: &self.physical_world,
+            projection_view: &projection_view,
+            camera_position: camera.position(),
+            alpha,
+        });
+
+        // 4. Non-geometric superimposed draw calls.
+        if self.show_frame_stats {
+            let screen_size = canvas.window().size();
+            self.frame_stats_overlay.draw(&self.frame_stats, &self.text_renderer, screen_size);
+        }
 
         canvas.present();
     }
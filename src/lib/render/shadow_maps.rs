@@ -0,0 +1,321 @@
+use app::{
+    Error,
+    StatusOr,
+};
+use gl::{
+    self,
+    types::*,
+};
+use glm;
+use render::{
+    camera,
+    lights::ShadowCasters,
+    Camera,
+    Light,
+    Lights,
+};
+use shader::ShaderProgram;
+use std;
+
+// Must match deferred_lighting_frag.glsl.
+pub const MAX_CASCADES: usize = 4;
+pub const MAX_SPOT_SHADOWS: usize = 4;
+
+// Texture units used by the lighting pass, after the three G-buffer textures.
+const CASCADE_TEXTURE_UNIT: u32 = 3;
+const SPOT_TEXTURE_UNIT: u32 = 4;
+
+// How far behind a cascade's bounds to start its light frustum, so that tall casters
+// outside the camera's view still shadow what is inside it.
+const CASCADE_CASTER_MARGIN: f32 = 150.0;
+const SPOT_Z_NEAR: f32 = 1.0;
+
+const CASCADE_MATRIX_NAMES: [&'static str; MAX_CASCADES] = [
+    "cascade_matrices[0]", "cascade_matrices[1]", "cascade_matrices[2]", "cascade_matrices[3]",
+];
+const CASCADE_FAR_DEPTH_NAMES: [&'static str; MAX_CASCADES] = [
+    "cascade_far_depths[0]", "cascade_far_depths[1]", "cascade_far_depths[2]", "cascade_far_depths[3]",
+];
+const SPOT_MATRIX_NAMES: [&'static str; MAX_SPOT_SHADOWS] = [
+    "spot_shadow_matrices[0]", "spot_shadow_matrices[1]", "spot_shadow_matrices[2]", "spot_shadow_matrices[3]",
+];
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct ShadowConfig {
+    // Side length in texels of each cascade of the main light.
+    pub resolution: u32,
+    pub spot_resolution: u32,
+    // Subtracted from the fragment's light space depth before comparing.
+    pub depth_bias: f32,
+    // glPolygonOffset factor, pushing back steep surfaces while rendering shadow maps.
+    pub slope_bias: f32,
+    // The lighting pass averages a (2r + 1)^2 texel kernel.
+    pub pcf_radius: i32,
+    // View space depth at which each cascade ends; at most MAX_CASCADES.
+    pub cascade_splits: Vec<f32>,
+    pub spot_far: f32,
+}
+
+impl Default for ShadowConfig {
+    fn default() -> ShadowConfig {
+        ShadowConfig {
+            resolution: 2048,
+            spot_resolution: 1024,
+            depth_bias: 0.0005,
+            slope_bias: 2.0,
+            pcf_radius: 1,
+            cascade_splits: vec![40.0, 100.0, 250.0],
+            spot_far: 400.0,
+        }
+    }
+}
+
+// One depth texture array per light kind; each layer is a cascade or a spot light.
+pub struct ShadowMaps {
+    frame_buffer: GLuint,
+    cascade_texture: GLuint,
+    spot_texture: GLuint,
+    config: ShadowConfig,
+}
+
+impl ShadowMaps {
+    // Resolutions and cascades are fixed here; biases and PCF are read from lights every frame.
+    pub fn new(config: &ShadowConfig) -> StatusOr<ShadowMaps> {
+        let mut shadow_maps = ShadowMaps {
+            frame_buffer: 0,
+            cascade_texture: 0,
+            spot_texture: 0,
+            config: config.clone(),
+        };
+        let num_cascades = shadow_maps.num_cascades().max(1);
+        shadow_maps.cascade_texture = ShadowMaps::depth_texture_array(config.resolution, num_cascades);
+        shadow_maps.spot_texture = ShadowMaps::depth_texture_array(config.spot_resolution, MAX_SPOT_SHADOWS);
+        unsafe {
+            gl::GenFramebuffers(1, &mut shadow_maps.frame_buffer);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, shadow_maps.frame_buffer);
+            gl::FramebufferTextureLayer(gl::FRAMEBUFFER, gl::DEPTH_ATTACHMENT, shadow_maps.cascade_texture, 0, 0);
+            // Depth only.
+            gl::DrawBuffer(gl::NONE);
+            gl::ReadBuffer(gl::NONE);
+            let status = gl::CheckFramebufferStatus(gl::FRAMEBUFFER);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+            if status != gl::FRAMEBUFFER_COMPLETE {
+                return Err(Error::FramebufferIncomplete { status });
+            }
+        }
+        Ok(shadow_maps)
+    }
+
+    fn depth_texture_array(resolution: u32, layers: usize) -> GLuint {
+        let mut texture = 0;
+        let border: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+        unsafe {
+            gl::GenTextures(1, &mut texture);
+            gl::BindTexture(gl::TEXTURE_2D_ARRAY, texture);
+            gl::TexImage3D(gl::TEXTURE_2D_ARRAY, 0, gl::DEPTH_COMPONENT24 as i32,
+                           resolution as i32, resolution as i32, layers as i32, 0,
+                           gl::DEPTH_COMPONENT, gl::FLOAT, std::ptr::null());
+            // LINEAR with a compare mode gives a free 2x2 PCF tap per sample.
+            gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
+            gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
+            // Everything outside a shadow map is lit.
+            gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_BORDER as i32);
+            gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_BORDER as i32);
+            gl::TexParameterfv(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_BORDER_COLOR, border.as_ptr());
+            gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_COMPARE_MODE, gl::COMPARE_REF_TO_TEXTURE as i32);
+            gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_COMPARE_FUNC, gl::LEQUAL as i32);
+            gl::BindTexture(gl::TEXTURE_2D_ARRAY, 0);
+        }
+        texture
+    }
+
+    pub fn config(&self) -> &ShadowConfig {
+        &self.config
+    }
+
+    fn num_cascades(&self) -> usize {
+        self.config.cascade_splits.len().min(MAX_CASCADES)
+    }
+
+    // Renders every shadow map with draw_scene, which should draw the same geometry as the
+    // geometry pass using the given projection-view. Leaves the default framebuffer bound
+    // with its viewport set to screen_size.
+    pub fn render<F>(&self, lights: &Lights, camera: &Camera, aspect: f32, screen_size: (i32, i32), draw_scene: F)
+        -> ShadowFrame where F: Fn(&glm::Mat4) {
+        let ShadowCasters { main_light, spots } = lights.shadow_casters();
+        let light_list = &lights.config().lights;
+        let shadows = &lights.config().shadows;
+        let mut frame = ShadowFrame {
+            cascade_texture: self.cascade_texture,
+            spot_texture: self.spot_texture,
+            view: camera.view(),
+            cascade_matrices: Vec::new(),
+            cascade_far_depths: Vec::new(),
+            spot_matrices: Vec::new(),
+            depth_bias: shadows.depth_bias,
+            pcf_radius: shadows.pcf_radius,
+        };
+
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.frame_buffer);
+            gl::Enable(gl::POLYGON_OFFSET_FILL);
+            gl::PolygonOffset(shadows.slope_bias, 1.0);
+        }
+
+        if let Some(&Light::Directional { direction, .. }) = main_light.map(|index| &light_list[index]) {
+            let direction = glm::builtin::normalize(glm::vec3(direction[0], direction[1], direction[2]));
+            let mut z_near = camera::Z_NEAR;
+            for (layer, &z_far) in self.config.cascade_splits.iter().take(MAX_CASCADES).enumerate() {
+                let light_pv = self.cascade_matrix(camera, aspect, z_near, z_far, direction);
+                self.render_layer(self.cascade_texture, layer, self.config.resolution, &light_pv, &draw_scene);
+                frame.cascade_matrices.push(light_pv);
+                frame.cascade_far_depths.push(z_far);
+                z_near = z_far;
+            }
+        }
+
+        for (layer, &index) in spots.iter().enumerate() {
+            if let Light::Spot { position, direction, outer_cone_degrees, .. } = light_list[index] {
+                let position = glm::vec3(position[0], position[1], position[2]);
+                let direction = glm::builtin::normalize(glm::vec3(direction[0], direction[1], direction[2]));
+                let fov = (2.0 * outer_cone_degrees).to_radians();
+                let projection = glm::ext::perspective(fov, 1.0, SPOT_Z_NEAR, shadows.spot_far);
+                let view = glm::ext::look_at(position, position + direction, ShadowMaps::up_for(direction));
+                let light_pv = projection * view;
+                self.render_layer(self.spot_texture, layer, self.config.spot_resolution, &light_pv, &draw_scene);
+                frame.spot_matrices.push(light_pv);
+            }
+        }
+
+        unsafe {
+            gl::Disable(gl::POLYGON_OFFSET_FILL);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+            gl::Viewport(0, 0, screen_size.0, screen_size.1);
+        }
+        frame
+    }
+
+    fn render_layer<F>(&self, texture: GLuint, layer: usize, resolution: u32, light_pv: &glm::Mat4, draw_scene: &F)
+        where F: Fn(&glm::Mat4) {
+        unsafe {
+            gl::FramebufferTextureLayer(gl::FRAMEBUFFER, gl::DEPTH_ATTACHMENT, texture, 0, layer as i32);
+            gl::Viewport(0, 0, resolution as i32, resolution as i32);
+            gl::Clear(gl::DEPTH_BUFFER_BIT);
+        }
+        draw_scene(light_pv);
+    }
+
+    // Fits an orthographic light frustum around the bounding sphere of the camera's view
+    // between z_near and z_far. A sphere keeps the cascade's size constant as the camera
+    // turns, and snapping to whole texels stops its edges from shimmering as it moves.
+    fn cascade_matrix(&self, camera: &Camera, aspect: f32, z_near: f32, z_far: f32, direction: glm::Vec3) -> glm::Mat4 {
+        let inverse_pv = glm::builtin::inverse(&(camera.projection_for_range(aspect, z_near, z_far) * camera.view()));
+        let mut corners = Vec::with_capacity(8);
+        for &x in [-1.0, 1.0].iter() {
+            for &y in [-1.0, 1.0].iter() {
+                for &z in [-1.0, 1.0].iter() {
+                    let corner = inverse_pv * glm::vec4(x, y, z, 1.0);
+                    corners.push(glm::vec3(corner.x, corner.y, corner.z) / corner.w);
+                }
+            }
+        }
+        let center = corners.iter().fold(glm::vec3(0.0, 0.0, 0.0), |sum, corner| sum + *corner) / corners.len() as f32;
+        let radius = corners.iter()
+            .map(|corner| glm::builtin::distance(*corner, center))
+            .fold(0.0, f32::max)
+            .ceil();
+
+        let eye = center - direction * (radius + CASCADE_CASTER_MARGIN);
+        let view = glm::ext::look_at(eye, center, ShadowMaps::up_for(direction));
+        let projection = orthographic(-radius, radius, -radius, radius, 0.0, 2.0 * radius + CASCADE_CASTER_MARGIN);
+        let mut light_pv = projection * view;
+
+        let half_resolution = self.config.resolution as f32 / 2.0;
+        let origin = light_pv * glm::vec4(0.0, 0.0, 0.0, 1.0);
+        let (texel_x, texel_y) = (origin.x * half_resolution, origin.y * half_resolution);
+        light_pv[3].x += (texel_x.round() - texel_x) / half_resolution;
+        light_pv[3].y += (texel_y.round() - texel_y) / half_resolution;
+        light_pv
+    }
+
+    // Any up vector not parallel to direction works for a light's view.
+    fn up_for(direction: glm::Vec3) -> glm::Vec3 {
+        if direction.y.abs() > 0.99 {
+            glm::vec3(0.0, 0.0, 1.0)
+        } else {
+            glm::vec3(0.0, 1.0, 0.0)
+        }
+    }
+
+    fn clear(&mut self) {
+        unsafe {
+            if self.frame_buffer != 0 {
+                gl::DeleteFramebuffers(1, &self.frame_buffer);
+                self.frame_buffer = 0;
+            }
+            if self.cascade_texture != 0 {
+                gl::DeleteTextures(1, &self.cascade_texture);
+                self.cascade_texture = 0;
+            }
+            if self.spot_texture != 0 {
+                gl::DeleteTextures(1, &self.spot_texture);
+                self.spot_texture = 0;
+            }
+        }
+    }
+}
+
+impl Drop for ShadowMaps {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+// The light space matrices of one frame's shadow maps, for the lighting pass.
+pub struct ShadowFrame {
+    cascade_texture: GLuint,
+    spot_texture: GLuint,
+    view: glm::Mat4,
+    cascade_matrices: Vec<glm::Mat4>,
+    cascade_far_depths: Vec<f32>,
+    spot_matrices: Vec<glm::Mat4>,
+    depth_bias: f32,
+    pcf_radius: i32,
+}
+
+impl ShadowFrame {
+    // Sets shader's shadow uniforms and binds the shadow maps to their texture units.
+    pub fn bind(&self, shader: &ShaderProgram) {
+        shader.set_i32("cascade_shadows", CASCADE_TEXTURE_UNIT as i32);
+        shader.set_i32("spot_shadows", SPOT_TEXTURE_UNIT as i32);
+        shader.set_mat4("view", &self.view);
+        shader.set_i32("num_cascades", self.cascade_matrices.len() as i32);
+        for (index, (matrix, far_depth)) in self.cascade_matrices.iter().zip(self.cascade_far_depths.iter()).enumerate() {
+            shader.set_mat4(CASCADE_MATRIX_NAMES[index], matrix);
+            shader.set_f32(CASCADE_FAR_DEPTH_NAMES[index], *far_depth);
+        }
+        for (index, matrix) in self.spot_matrices.iter().enumerate() {
+            shader.set_mat4(SPOT_MATRIX_NAMES[index], matrix);
+        }
+        shader.set_f32("shadow_depth_bias", self.depth_bias);
+        shader.set_i32("pcf_radius", self.pcf_radius);
+        unsafe {
+            gl::ActiveTexture(gl::TEXTURE0 + CASCADE_TEXTURE_UNIT);
+            gl::BindTexture(gl::TEXTURE_2D_ARRAY, self.cascade_texture);
+            gl::ActiveTexture(gl::TEXTURE0 + SPOT_TEXTURE_UNIT);
+            gl::BindTexture(gl::TEXTURE_2D_ARRAY, self.spot_texture);
+            gl::ActiveTexture(gl::TEXTURE0);
+        }
+    }
+}
+
+// glm 0.2 has no glm::ext::ortho.
+fn orthographic(left: f32, right: f32, bottom: f32, top: f32, z_near: f32, z_far: f32) -> glm::Mat4 {
+    glm::mat4(
+        2.0 / (right - left), 0.0, 0.0, 0.0,
+        0.0, 2.0 / (top - bottom), 0.0, 0.0,
+        0.0, 0.0, -2.0 / (z_far - z_near), 0.0,
+        -(right + left) / (right - left), -(top + bottom) / (top - bottom), -(z_far + z_near) / (z_far - z_near), 1.0,
+    )
+}
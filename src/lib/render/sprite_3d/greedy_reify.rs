@@ -0,0 +1,372 @@
+use geometry::{
+    polygon,
+    Mesh,
+    TriMeshBuilder,
+    UvMapping,
+};
+use glm;
+use render::sprite_3d::reify_sprite_3d::{
+    depth_at,
+    depth_span,
+};
+use std::collections::HashSet;
+
+// A pixel corner at the z of one of the mesh's distinct z levels.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct LatticePoint {
+    row: i64,
+    col: i64,
+    level: usize,
+}
+
+// Where a maximal rectangle of cells lies. Face planes hold pixels of one depth at one z,
+// row and column walls hold (pixel, z slab) cells along one pixel edge line.
+#[derive(Clone, Copy)]
+enum Plane {
+    Front { level: usize },
+    Back { level: usize },
+    // Between pixel rows row - 1 and row, solid on the side above_solid says.
+    RowWall { row: i64, above_solid: bool },
+    // Between pixel columns col - 1 and col.
+    ColumnWall { col: i64, left_solid: bool },
+}
+
+impl Plane {
+    fn outward(&self) -> glm::Vec3 {
+        match *self {
+            Plane::Front { .. } => glm::vec3(0.0, 0.0, 1.0),
+            Plane::Back { .. } => glm::vec3(0.0, 0.0, -1.0),
+            // Rows run down the image, against +y.
+            Plane::RowWall { above_solid, .. } => glm::vec3(0.0, if above_solid { -1.0 } else { 1.0 }, 0.0),
+            Plane::ColumnWall { left_solid, .. } => glm::vec3(if left_solid { 1.0 } else { -1.0 }, 0.0, 0.0),
+        }
+    }
+
+    // The lattice point at (u, v) in the plane's own coordinates.
+    fn point(&self, u: i64, v: i64) -> LatticePoint {
+        match *self {
+            Plane::Front { level, .. } | Plane::Back { level, .. } => LatticePoint { row: v, col: u, level },
+            Plane::RowWall { row, .. } => LatticePoint { row, col: u, level: v as usize },
+            Plane::ColumnWall { col, .. } => LatticePoint { row: u, col, level: v as usize },
+        }
+    }
+
+    // The solid pixel behind cell (u, v) of the plane.
+    fn pixel(&self, u: i64, v: i64) -> (i64, i64) {
+        match *self {
+            Plane::Front { .. } | Plane::Back { .. } => (v, u),
+            Plane::RowWall { row, above_solid } => (if above_solid { row - 1 } else { row }, u),
+            Plane::ColumnWall { col, left_solid } => (u, if left_solid { col - 1 } else { col }),
+        }
+    }
+}
+
+struct Rect {
+    plane: Plane,
+    u0: i64,
+    v0: i64,
+    u1: i64,
+    v1: i64,
+}
+
+// The extruded sprite as solid slabs, one per opaque pixel.
+struct Slabs<'a> {
+    depths: &'a [Vec<f32>],
+    // Every slab's front and back z, descending.
+    levels: Vec<f32>,
+}
+
+impl <'a> Slabs<'a> {
+    fn new(depths: &'a [Vec<f32>]) -> Slabs<'a> {
+        let mut levels = Vec::new();
+        for depth in depths.iter().flat_map(|row| row.iter()).filter(|&&depth| depth > 0.0) {
+            let (front, back) = depth_span(*depth);
+            levels.push(front);
+            levels.push(back);
+        }
+        levels.sort_by(|a, b| b.partial_cmp(a).unwrap());
+        levels.dedup();
+        Slabs { depths, levels }
+    }
+
+    fn contains(&self, (row, col): (i64, i64), z: f32) -> bool {
+        let depth = depth_at(self.depths, row, col);
+        let (front, back) = depth_span(depth);
+        depth > 0.0 && back <= z && z <= front
+    }
+
+    // Whether pixel spans the whole slab between levels[slab] and levels[slab + 1].
+    fn covers_slab(&self, (row, col): (i64, i64), slab: usize) -> bool {
+        let depth = depth_at(self.depths, row, col);
+        let (front, back) = depth_span(depth);
+        depth > 0.0 && back <= self.levels[slab + 1] && self.levels[slab] <= front
+    }
+
+    // The pixels around point that contain its z and connect to pixel through shared edges,
+    // as bits in depth_corner's order: above left, above, left, and the pixel itself.
+    // Pixels touching only diagonally get a vertex each, keeping the mesh manifold.
+    fn component(&self, point: LatticePoint, pixel: (i64, i64)) -> u8 {
+        let z = self.levels[point.level];
+        let mut solid = 0;
+        let mut own = 0;
+        for (bit, &(dr, dc)) in [(-1, -1), (-1, 0), (0, -1), (0, 0)].iter().enumerate() {
+            let neighbour = (point.row + dr, point.col + dc);
+            if self.contains(neighbour, z) {
+                solid |= 1 << bit;
+            }
+            if neighbour == pixel {
+                own = 1 << bit;
+            }
+        }
+        if solid == 0b1001 || solid == 0b0110 {
+            own
+        } else {
+            solid
+        }
+    }
+
+    // point's exact position, before depth_corner's nudge.
+    fn exact(&self, point: LatticePoint) -> glm::Vec3 {
+        glm::vec3(point.col as f32, (self.depths.len() as i64 - point.row) as f32, self.levels[point.level])
+    }
+
+    // Like depth_corner, pulled 0.001 towards each pixel of component, so textures sample
+    // inside the sprite.
+    fn position(&self, point: LatticePoint, component: u8) -> glm::Vec3 {
+        let mut offset: (i64, i64) = (0, 0);
+        for (bit, &(dr, dc)) in [(-1, -1), (-1, 0), (0, -1), (0, 0)].iter().enumerate() {
+            if component & (1 << bit) != 0 {
+                offset.0 += 2 * dr + 1;
+                offset.1 += 2 * dc + 1;
+            }
+        }
+        let exact = self.exact(point);
+        glm::vec3(exact.x + offset.1 as f32 * 0.001, exact.y - offset.0 as f32 * 0.001, exact.z)
+    }
+}
+
+// Splits the cells where filled(u, v) holds into maximal rectangles, each grown as wide and
+// then as tall as it can go from the first unclaimed cell in scan order.
+fn greedy_rects<F: Fn(i64, i64) -> bool>(plane: Plane, width: i64, height: i64, filled: F, rects: &mut Vec<Rect>) {
+    let mut claimed = vec![vec![false; width as usize]; height as usize];
+    let open = |claimed: &Vec<Vec<bool>>, u: i64, v: i64| filled(u, v) && !claimed[v as usize][u as usize];
+    for v0 in 0..height {
+        for u0 in 0..width {
+            if !open(&claimed, u0, v0) {
+                continue;
+            }
+            let mut u1 = u0 + 1;
+            while u1 < width && open(&claimed, u1, v0) {
+                u1 += 1;
+            }
+            let mut v1 = v0 + 1;
+            while v1 < height && (u0..u1).all(|u| open(&claimed, u, v1)) {
+                v1 += 1;
+            }
+            for v in v0..v1 {
+                for u in u0..u1 {
+                    claimed[v as usize][u as usize] = true;
+                }
+            }
+            rects.push(Rect { plane, u0, v0, u1, v1 });
+        }
+    }
+}
+
+// An alternative to from_image_with_depths that greedily merges each plane's coplanar,
+// same-facing cells into maximal rectangles, so large areas of one depth cost two triangles.
+// Rectangles get a vertex wherever another one's corner touches their edges, so there are
+// no T-junctions and the mesh is closed and manifold. Opaque pixels cover the same area.
+pub fn from_depths(depths: &[Vec<f32>], uv_mapping: UvMapping) -> Mesh {
+    let slabs = Slabs::new(depths);
+    let (width, height) = (depths[0].len() as i64, depths.len() as i64);
+    let num_slabs = slabs.levels.len().saturating_sub(1) as i64;
+    let level_of = |z: f32| slabs.levels.iter().position(|&level| level == z).unwrap();
+
+    let mut rects = Vec::new();
+    let mut distinct_depths: Vec<f32> = depths.iter().flat_map(|row| row.iter().cloned()).filter(|&depth| depth > 0.0).collect();
+    distinct_depths.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    distinct_depths.dedup();
+    for &depth in distinct_depths.iter() {
+        let (front, back) = depth_span(depth);
+        let has_depth = |u: i64, v: i64| depth_at(depths, v, u) == depth;
+        greedy_rects(Plane::Front { level: level_of(front) }, width, height, has_depth, &mut rects);
+        greedy_rects(Plane::Back { level: level_of(back) }, width, height, has_depth, &mut rects);
+    }
+    for row in 0..height + 1 {
+        for &above_solid in [true, false].iter() {
+            let (solid, open) = if above_solid { (row - 1, row) } else { (row, row - 1) };
+            let exposed = |u: i64, v: i64| slabs.covers_slab((solid, u), v as usize) && !slabs.covers_slab((open, u), v as usize);
+            greedy_rects(Plane::RowWall { row, above_solid }, width, num_slabs, exposed, &mut rects);
+        }
+    }
+    for col in 0..width + 1 {
+        for &left_solid in [true, false].iter() {
+            let (solid, open) = if left_solid { (col - 1, col) } else { (col, col - 1) };
+            let exposed = |u: i64, v: i64| slabs.covers_slab((u, solid), v as usize) && !slabs.covers_slab((u, open), v as usize);
+            greedy_rects(Plane::ColumnWall { col, left_solid }, height, num_slabs, exposed, &mut rects);
+        }
+    }
+
+    let corners: HashSet<LatticePoint> = rects.iter()
+        .flat_map(|rect| vec![(rect.u0, rect.v0), (rect.u1, rect.v0), (rect.u1, rect.v1), (rect.u0, rect.v1)]
+            .into_iter()
+            .map(move |(u, v)| rect.plane.point(u, v)))
+        .collect();
+
+    let mut builder = TriMeshBuilder::with_uv_mapping(uv_mapping);
+    for rect in rects.iter() {
+        // Walk the boundary, keeping the corners and any other rectangle's corners on it.
+        let mut ring: Vec<(i64, i64)> = Vec::new();
+        ring.extend((rect.u0..rect.u1).map(|u| (u, rect.v0)));
+        ring.extend((rect.v0..rect.v1).map(|v| (rect.u1, v)));
+        ring.extend((rect.u0 + 1..rect.u1 + 1).rev().map(|u| (u, rect.v1)));
+        ring.extend((rect.v0 + 1..rect.v1 + 1).rev().map(|v| (rect.u0, v)));
+        let is_corner = |u: i64, v: i64| (u == rect.u0 || u == rect.u1) && (v == rect.v0 || v == rect.v1);
+        ring.retain(|&(u, v)| is_corner(u, v) || corners.contains(&rect.plane.point(u, v)));
+
+        let mut exact: Vec<glm::Vec3> = ring.iter().map(|&(u, v)| slabs.exact(rect.plane.point(u, v))).collect();
+        let mut positions: Vec<glm::Vec3> = ring.iter()
+            .map(|&(u, v)| {
+                let point = rect.plane.point(u, v);
+                let pixel = rect.plane.pixel(u.max(rect.u0).min(rect.u1 - 1), v.max(rect.v0).min(rect.v1 - 1));
+                slabs.position(point, slabs.component(point, pixel))
+            })
+            .collect();
+        // Walking (u0, v0), (u1, v0), (u1, v1) turns one way or the other depending on the plane.
+        let corner = |u: i64, v: i64| slabs.exact(rect.plane.point(u, v));
+        let (a, b, c) = (corner(rect.u0, rect.v0), corner(rect.u1, rect.v0), corner(rect.u1, rect.v1));
+        if glm::builtin::dot(glm::builtin::cross(b - a, c - a), rect.plane.outward()) < 0.0 {
+            exact.reverse();
+            positions.reverse();
+        }
+        for triangle in polygon::triangulate(&exact).iter() {
+            builder.add_triangle(positions[triangle[0]], positions[triangle[1]], positions[triangle[2]]);
+        }
+    }
+    builder.build()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use render::sprite_3d::reify_sprite_3d;
+    use std::collections::{
+        BTreeSet,
+        HashMap,
+    };
+
+    type Key = [u32; 3];
+
+    fn key(position: glm::Vec3) -> Key {
+        [position.x.to_bits(), position.y.to_bits(), position.z.to_bits()]
+    }
+
+    fn triangles(mesh: &Mesh) -> Vec<[glm::Vec3; 3]> {
+        mesh.faces.chunks(3)
+            .map(|face| [mesh.vertices[face[0] as usize], mesh.vertices[face[1] as usize], mesh.vertices[face[2] as usize]])
+            .collect()
+    }
+
+    // The (row, col) pixels whose centers the mesh's triangles facing towards or away from
+    // the viewer cover, in a depths-sized image.
+    fn covered_pixels(mesh: &Mesh, depths: &[Vec<f32>], facing: f32) -> BTreeSet<(usize, usize)> {
+        let height = depths.len();
+        let mut covered = BTreeSet::new();
+        for [a, b, c] in triangles(mesh) {
+            let normal_z = glm::builtin::cross(b - a, c - a).z * facing;
+            if normal_z <= 0.0 {
+                continue;
+            }
+            let side = |p: glm::Vec2, from: glm::Vec3, to: glm::Vec3| (to.x - from.x) * (p.y - from.y) - (to.y - from.y) * (p.x - from.x);
+            for row in 0..height {
+                for col in 0..depths[0].len() {
+                    let center = glm::vec2(col as f32 + 0.5, (height - row) as f32 - 0.5);
+                    let sides = [side(center, a, b), side(center, b, c), side(center, c, a)];
+                    if sides.iter().all(|&s| s * facing >= 0.0) {
+                        covered.insert((row, col));
+                    }
+                }
+            }
+        }
+        covered
+    }
+
+    fn opaque_pixels(depths: &[Vec<f32>]) -> BTreeSet<(usize, usize)> {
+        let mut opaque = BTreeSet::new();
+        for (row, depth_row) in depths.iter().enumerate() {
+            for (col, &depth) in depth_row.iter().enumerate() {
+                if depth > 0.0 {
+                    opaque.insert((row, col));
+                }
+            }
+        }
+        opaque
+    }
+
+    fn assert_same_silhouette(depths: &[Vec<f32>]) {
+        let greedy = from_depths(depths, UvMapping::PerFace);
+        let edges = reify_sprite_3d::from_image_with_depths(depths, UvMapping::PerFace);
+        let opaque = opaque_pixels(depths);
+        for &facing in [1.0, -1.0].iter() {
+            assert_eq!(covered_pixels(&greedy, depths, facing), opaque);
+            assert_eq!(covered_pixels(&edges, depths, facing), opaque);
+        }
+    }
+
+    // Every edge is shared by exactly two triangles, which walk it in opposite directions.
+    fn assert_closed_manifold(mesh: &Mesh) {
+        let mut directed: HashMap<(Key, Key), usize> = HashMap::new();
+        for [a, b, c] in triangles(mesh) {
+            for &(from, to) in [(a, b), (b, c), (c, a)].iter() {
+                *directed.entry((key(from), key(to))).or_insert(0) += 1;
+            }
+        }
+        assert!(!directed.is_empty());
+        for (&(from, to), &count) in directed.iter() {
+            assert_eq!(count, 1, "edge {:?} -> {:?} is walked {} times", from, to, count);
+            assert_eq!(directed.get(&(to, from)), Some(&1), "edge {:?} -> {:?} has no opposite", from, to);
+        }
+    }
+
+    fn check(depths: Vec<Vec<f32>>) {
+        assert_same_silhouette(&depths);
+        assert_closed_manifold(&from_depths(&depths, UvMapping::PerFace));
+    }
+
+    #[test]
+    fn solid_square() {
+        check(vec![vec![1.0; 4]; 4]);
+    }
+
+    #[test]
+    fn l_shape_with_hole() {
+        check(vec![
+            vec![1.0, 1.0, 1.0, 0.0, 0.0],
+            vec![1.0, 0.0, 1.0, 0.0, 0.0],
+            vec![1.0, 1.0, 1.0, 0.0, 0.0],
+            vec![1.0, 1.0, 1.0, 1.0, 1.0],
+            vec![1.0, 1.0, 1.0, 1.0, 1.0],
+        ]);
+    }
+
+    #[test]
+    fn varied_depths() {
+        check(vec![
+            vec![0.5, 0.5, 2.0, 2.0, 0.0],
+            vec![0.5, 1.0, 2.0, 1.0, 0.0],
+            vec![0.0, 1.0, 1.0, 1.0, 0.5],
+            vec![0.3, 0.0, 1.0, 0.0, 0.5],
+        ]);
+    }
+
+    #[test]
+    fn diagonal_neighbours() {
+        check(vec![
+            vec![1.0, 0.0, 1.0],
+            vec![0.0, 1.0, 0.0],
+            vec![1.0, 0.0, 2.0],
+        ]);
+    }
+}
@@ -1,5 +1,22 @@
+mod greedy_reify;
 mod reify_sprite_3d;
 pub mod sprite_3d;
+pub mod sprite_animation;
+pub mod sprite_sheet;
 
+pub use self::sprite_3d::reify_sheet;
+pub use self::sprite_3d::DepthMapConfig;
+pub use self::sprite_3d::DepthMapInfo;
+pub use self::sprite_3d::ReifyBackend;
+pub use self::sprite_3d::REIFY_VERSION;
 pub use self::sprite_3d::Sprite3D;
 pub use self::sprite_3d::Sprite3DSheetInfo;
+pub use self::sprite_animation::AnimationConfig;
+pub use self::sprite_animation::AnimationEvent;
+pub use self::sprite_animation::Playback;
+pub use self::sprite_animation::SpriteAnimation;
+pub use self::sprite_sheet::FrameRef;
+pub use self::sprite_sheet::SheetLayout;
+pub use self::sprite_sheet::SpriteFrame;
+pub use self::sprite_sheet::SpriteSheet;
+pub use self::sprite_sheet::StateConfig;
@@ -1,7 +1,8 @@
 use dimensions::Pixels;
 use geometry::{
     Mesh,
-    QuadMeshBuilder
+    QuadMeshBuilder,
+    UvMapping,
 };
 use glm;
 use image::Png;
@@ -227,8 +228,8 @@ fn basic_cartesian(png: &Png, z_value: f32, edge: EdgeCoord) -> glm::Vec3 {
               z_value)
 }
 
-fn finalize(png: Png, features: BTreeSet<EdgeCoord>) -> Mesh {
-    let mut builder = QuadMeshBuilder::new();
+fn finalize(png: Png, features: BTreeSet<EdgeCoord>, uv_mapping: UvMapping) -> Mesh {
+    let mut builder = QuadMeshBuilder::with_uv_mapping(uv_mapping);
     for feature in features.iter() {
         let bottom = try_find_bottom(&png, &features, *feature);
         let right = try_find_right(&png, &features, *feature);
@@ -276,9 +277,165 @@ fn finalize(png: Png, features: BTreeSet<EdgeCoord>) -> Mesh {
     builder.build()
 }
 
-pub fn from_image(png: Png) -> Mesh {
+// Thinner than this and an opaque pixel's faces would z-fight.
+const MIN_DEPTH: f32 = 0.05;
+
+// Per-pixel extrusion depths for one segment: 0 for empty pixels, else the depth map's red
+// channel times max_depth.
+pub fn pixel_depths(png: &Png, depth_map: &Png, max_depth: f32) -> Vec<Vec<f32>> {
+    png.img.iter().zip(depth_map.img.iter())
+        .map(|(row, depth_row)| row.iter().zip(depth_row.iter())
+            .map(|(pixel, depth)| if pixel.a < 0.00001 { 0.0 } else { (depth.r * max_depth).max(MIN_DEPTH) })
+            .collect())
+        .collect()
+}
+
+pub fn depth_at(depths: &[Vec<f32>], row: i64, col: i64) -> f32 {
+    if row < 0 || col < 0 || row as usize >= depths.len() || col as usize >= depths[0].len() {
+        return 0.0;
+    }
+    depths[row as usize][col as usize]
+}
+
+// The near and far z of a pixel extruded to depth. Slabs nest around z = -0.5, so a depth of
+// 1 spans z = 0 to -1 like from_image, and thicker regions bulge out of both sides.
+pub fn depth_span(depth: f32) -> (f32, f32) {
+    (0.5 * depth - 0.5, -0.5 * depth - 0.5)
+}
+
+// The corner at the top left of pixel (row, col), on the surfaces of a slab of depth level.
+// Like basic_cartesian, it is pulled 0.001 towards each surrounding pixel at least that
+// deep, so textures sample inside the sprite, and faces and walls meeting there agree.
+fn depth_corner(depths: &[Vec<f32>], row: i64, col: i64, level: f32, z: f32) -> glm::Vec3 {
+    let mut offset: (i64, i64) = (0, 0);
+    for &(dr, dc) in [(-1, -1), (-1, 0), (0, -1), (0, 0)].iter() {
+        if depth_at(depths, row + dr, col + dc) >= level {
+            offset.0 += 2 * dr + 1;
+            offset.1 += 2 * dc + 1;
+        }
+    }
+    let height = depths.len() as f32;
+    glm::vec3(col as f32 + offset.1 as f32 * 0.001,
+              height - row as f32 - offset.0 as f32 * 0.001,
+              z)
+}
+
+// Adds the quad with edges start-end at two z values, wound to face outward.
+fn add_oriented_face(builder: &mut QuadMeshBuilder,
+                     top_left: glm::Vec3, top_right: glm::Vec3,
+                     bottom_left: glm::Vec3, bottom_right: glm::Vec3,
+                     outward: glm::Vec3) {
+    let normal = glm::builtin::cross(bottom_left - top_left, bottom_right - top_left);
+    if glm::builtin::dot(normal, outward) < 0.0 {
+        builder.add_face(top_right, top_left, bottom_right, bottom_left);
+    } else {
+        builder.add_face(top_left, top_right, bottom_left, bottom_right);
+    }
+}
+
+// The walls where a pixel of depth outer stands proud of its neighbour of depth inner, along
+// the pixel edge from corner start to corner end.
+fn add_walls(builder: &mut QuadMeshBuilder, depths: &[Vec<f32>],
+             start: (i64, i64), end: (i64, i64), outer: f32, inner: f32, outward: glm::Vec3) {
+    let corner = |(row, col): (i64, i64), level: f32, z: f32| depth_corner(depths, row, col, level, z);
+    let (outer_front, outer_back) = depth_span(outer);
+    if inner <= 0.0 {
+        add_oriented_face(builder,
+                          corner(start, outer, outer_front), corner(end, outer, outer_front),
+                          corner(start, outer, outer_back), corner(end, outer, outer_back),
+                          outward);
+        return;
+    }
+    let (inner_front, inner_back) = depth_span(inner);
+    add_oriented_face(builder,
+                      corner(start, outer, outer_front), corner(end, outer, outer_front),
+                      corner(start, inner, inner_front), corner(end, inner, inner_front),
+                      outward);
+    add_oriented_face(builder,
+                      corner(start, inner, inner_back), corner(end, inner, inner_back),
+                      corner(start, outer, outer_back), corner(end, outer, outer_back),
+                      outward);
+}
+
+// Like from_image, but extrudes each pixel to its own depth (see pixel_depths) and closes the
+// back. Faces are merged along rows of equal depth, and walls along runs of equal depth pairs.
+pub fn from_image_with_depths(depths: &[Vec<f32>], uv_mapping: UvMapping) -> Mesh {
+    let mut builder = QuadMeshBuilder::with_uv_mapping(uv_mapping);
+    let (width, height) = (depths[0].len() as i64, depths.len() as i64);
+
+    // Front and back faces.
+    for row in 0..height {
+        let mut col = 0;
+        while col < width {
+            let depth = depth_at(depths, row, col);
+            let start = col;
+            while col < width && depth_at(depths, row, col) == depth {
+                col += 1;
+            }
+            if depth <= 0.0 {
+                continue;
+            }
+            let (front, back) = depth_span(depth);
+            let corner = |row, col, z| depth_corner(depths, row, col, depth, z);
+            add_oriented_face(&mut builder,
+                              corner(row, start, front), corner(row, col, front),
+                              corner(row + 1, start, front), corner(row + 1, col, front),
+                              glm::vec3(0.0, 0.0, 1.0));
+            add_oriented_face(&mut builder,
+                              corner(row, start, back), corner(row, col, back),
+                              corner(row + 1, start, back), corner(row + 1, col, back),
+                              glm::vec3(0.0, 0.0, -1.0));
+        }
+    }
+
+    // Walls along horizontal pixel edges, between row - 1 above and row below.
+    for row in 0..height + 1 {
+        let mut col = 0;
+        while col < width {
+            let pair = (depth_at(depths, row - 1, col), depth_at(depths, row, col));
+            let start = col;
+            while col < width && (depth_at(depths, row - 1, col), depth_at(depths, row, col)) == pair {
+                col += 1;
+            }
+            let (above, below) = pair;
+            if above > below {
+                add_walls(&mut builder, depths, (row, start), (row, col), above, below, glm::vec3(0.0, -1.0, 0.0));
+            } else if below > above {
+                add_walls(&mut builder, depths, (row, start), (row, col), below, above, glm::vec3(0.0, 1.0, 0.0));
+            }
+        }
+    }
+
+    // Walls along vertical pixel edges, between col - 1 on the left and col on the right.
+    for col in 0..width + 1 {
+        let mut row = 0;
+        while row < height {
+            let pair = (depth_at(depths, row, col - 1), depth_at(depths, row, col));
+            let start = row;
+            while row < height && (depth_at(depths, row, col - 1), depth_at(depths, row, col)) == pair {
+                row += 1;
+            }
+            let (left, right) = pair;
+            if left > right {
+                add_walls(&mut builder, depths, (start, col), (row, col), left, right, glm::vec3(1.0, 0.0, 0.0));
+            } else if right > left {
+                add_walls(&mut builder, depths, (start, col), (row, col), right, left, glm::vec3(-1.0, 0.0, 0.0));
+            }
+        }
+    }
+    builder.build()
+}
+
+// Depths for a sprite without a depth map: 1 for opaque pixels, like from_image's extrusion.
+pub fn unit_depths(png: &Png) -> Vec<Vec<f32>> {
+    png.img.iter()
+        .map(|row| row.iter().map(|pixel| if pixel.a < 0.00001 { 0.0 } else { 1.0 }).collect())
+        .collect()
+}
+
+pub fn from_image(png: Png, uv_mapping: UvMapping) -> Mesh {
     let border_cells = border_cells(&png);
     let walls = cell_walls(&png, border_cells);
     let features = edge_points(walls);
-    finalize(png, features)
+    finalize(png, features, uv_mapping)
 }
\ No newline at end of file
@@ -1,119 +1,194 @@
-use app::{
-    StatusOr,
-    Ticker
+use app::StatusOr;
+use assets::{
+    AssetManager,
+    Handle,
 };
-use dimensions::{
-    Pixels,
-    time::{
-        DeltaTime,
-        Microseconds
-    },
+use geometry::{
+    GltfBuilder,
+    Mesh,
+    UvMapping,
 };
-use file;
-use geometry::Mesh;
 use gl;
 use glm;
 use image::{
-    png::ImageRect,
+    sheet_file,
     Png,
     Texture,
 };
-use render::sprite_3d::reify_sprite_3d;
+use render::sprite_3d::{
+    greedy_reify,
+    reify_sprite_3d,
+    Playback,
+    SpriteAnimation,
+    SpriteFrame,
+};
 use shader::ShaderProgram;
+use std::path::{
+    Path,
+    PathBuf,
+};
+
+// How thick to extrude each pixel of a sprite sheet, as configured in e.g. athlete.config.
+#[derive(Clone, Debug, Deserialize)]
+pub struct DepthMapConfig {
+    // A grayscale image in res/images, the same size as the sheet.
+    pub image: String,
+    // The depth of a white pixel, in pixels. Black pixels are nearly flat.
+    pub max_depth: f32,
+}
+
+// Bump whenever either backend's meshes change, so meshes cached on disk by older builds
+// aren't loaded.
+pub const REIFY_VERSION: u32 = 1;
+
+// Which algorithm turns sheet frames into meshes, as configured in e.g. ball.config.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReifyBackend {
+    // Traces the outline's feature points, or with a depth map merges faces along rows.
+    Edges,
+    // Merges coplanar faces into maximal rectangles. Closed and watertight, and usually fewer
+    // triangles, though sprites with many distinct depths can come out larger.
+    Greedy,
+}
+
+impl Default for ReifyBackend {
+    fn default() -> ReifyBackend {
+        ReifyBackend::Edges
+    }
+}
+
+pub struct DepthMapInfo<'a> {
+    pub filepath: &'a Path,
+    pub max_depth: f32,
+}
 
 pub struct Sprite3DSheetInfo<'a> {
-    pub filepath: &'a str,
-    // Expects spritesheets to be 1 row by N images
-    pub segment_width: Pixels,
-    pub time_per_segment: Microseconds,
-    pub all_states: Vec<Vec<usize>>,
+    pub filepath: &'a Path,
+    // One mesh is reified per frame, see SpriteSheet.
+    pub frames: &'a [SpriteFrame],
+    // Without one, every pixel is extruded 1 unit deep.
+    pub depth_map: Option<DepthMapInfo<'a>>,
+    pub backend: ReifyBackend,
+}
+
+// Reifies each frame of a sprite sheet into its own mesh. depth_map, if any, is the same
+// size as png and its red channel scales max_depth. Every frame must fit inside png.
+pub fn reify_sheet(png: &Png, frames: &[SpriteFrame], depth_map: Option<&Png>, max_depth: f32,
+                   backend: ReifyBackend) -> Vec<Mesh> {
+    let (sheet_width, sheet_height) = png.size();
+    let mut meshes = Vec::with_capacity(frames.len());
+    for frame in frames.iter() {
+        let frame_image = png.copy_sub_image(frame.image_rect());
+        // Frame meshes are built at the origin with y up from the frame's bottom edge, so
+        // shift their uvs over to the frame.
+        let uv_mapping = UvMapping::PlanarXY {
+            offset: glm::vec2(frame.x as f32, (sheet_height - frame.y - frame.height) as f32),
+            scale: glm::vec2(sheet_width as f32, sheet_height as f32),
+        };
+        let depths = depth_map.map(|depth_map|
+            reify_sprite_3d::pixel_depths(&frame_image, &depth_map.copy_sub_image(frame.image_rect()), max_depth));
+        let mesh = match (backend, depths) {
+            (ReifyBackend::Edges, Some(depths)) => reify_sprite_3d::from_image_with_depths(&depths, uv_mapping),
+            (ReifyBackend::Edges, None) => reify_sprite_3d::from_image(frame_image, uv_mapping),
+            (ReifyBackend::Greedy, Some(depths)) => greedy_reify::from_depths(&depths, uv_mapping),
+            (ReifyBackend::Greedy, None) => greedy_reify::from_depths(&reify_sprite_3d::unit_depths(&frame_image), uv_mapping),
+        };
+        meshes.push(mesh);
+    }
+    meshes
 }
 
 pub struct Sprite3D {
-    segment_width: Pixels,
-    meshes: Vec<Mesh>,
-    current_mesh_ticker: Ticker,
-    current_state: usize,
-    all_states: Vec<Vec<usize>>,
-    texture_3d: Texture,
-    shader_3d: ShaderProgram,
+    meshes: Handle<Vec<Mesh>>,
+    texture_3d: Handle<Texture>,
+    shader_3d: Handle<ShaderProgram>,
+    sheet_filepath: PathBuf,
+    frame_names: Vec<Option<String>>,
     pub object_center: glm::Vec3,
 }
 
 impl Sprite3D {
-    pub fn new(options: Sprite3DSheetInfo) -> StatusOr<Sprite3D> {
-        let png = Png::from_file(options.filepath)?;
-        let texture_3d = Texture::new(&png);
-        let (sheet_width, sheet_height) = (texture_3d.width, texture_3d.height);
-        let segment_width = options.segment_width;
-
-        let num_segments = sheet_width / segment_width;
-        let mut meshes = Vec::with_capacity(num_segments);
-        for i in 0..num_segments {
-            let segment_image = png.copy_sub_image(ImageRect::new(0, i * segment_width, segment_width, sheet_height));
-            let mut mesh = reify_sprite_3d::from_image(segment_image);
-            mesh.gl_init();
-            meshes.push(mesh);
-        }
-        let current_mesh_ticker = Ticker::new(options.time_per_segment);
-
-        let vert_path = file::util::resource_path("shaders", "sprite_3d_vert.glsl");
-        let geo_path = file::util::resource_path("shaders", "sprite_3d_geo.glsl");
-        let frag_path = file::util::resource_path("shaders", "sprite_3d_frag.glsl");
-        let shader_3d = ShaderProgram::from_long_pipeline(vert_path.as_str(), geo_path.as_str(), frag_path.as_str())?;
-
+    pub fn new(options: Sprite3DSheetInfo, assets: &mut AssetManager) -> StatusOr<Sprite3D> {
         Ok(Sprite3D {
-            segment_width,
-            meshes,
-            current_mesh_ticker,
-            current_state: 0,
-            all_states: options.all_states,
-            texture_3d,
-            shader_3d,
+            meshes: assets.sprite_meshes(&options)?,
+            texture_3d: assets.texture(options.filepath)?,
+            shader_3d: assets.shader("sprite_3d_vert.glsl", None, "sprite_3d_frag.glsl")?,
+            sheet_filepath: options.filepath.to_path_buf(),
+            frame_names: options.frames.iter().map(|frame| frame.name.clone()).collect(),
             object_center: glm::vec3(0.0, 0.0, 0.0),
         })
     }
 
-    pub fn draw(&self, projection_view: &glm::Mat4, model: &glm::Mat4) {
+    // mesh_index picks the sheet frame to draw, see SpriteAnimation::current_mesh_index.
+    pub fn draw(&self, projection_view: &glm::Mat4, model: &glm::Mat4, mesh_index: usize) {
         self.shader_3d.activate();
         unsafe {
             gl::ActiveTexture(gl::TEXTURE0);
             gl::BindTexture(gl::TEXTURE_2D, self.texture_3d.texture_id);
         }
-        // let animation_index = self.current_mesh_ticker.get_tick() % self.meshes.len();
-        let current_mesh_indexes = &self.all_states[self.current_state];
-        let animation_index = self.current_mesh_ticker.get_tick() % current_mesh_indexes.len();
-        let mesh_index = current_mesh_indexes[animation_index];
-        let material_scale = glm::vec2(self.texture_3d.width as f32, self.texture_3d.height as f32);
         self.shader_3d.set_vec3("center", &self.object_center);
-        self.shader_3d.set_vec2("scale", &material_scale);
         self.shader_3d.set_mat4("model", model);
         self.shader_3d.set_mat4("projection_view", projection_view);
-        self.shader_3d.set_f32("window_x_offset", (mesh_index * self.segment_width) as f32);
         self.shader_3d.set_i32("material", 0);
         self.meshes[mesh_index].draw();
     }
 
-    pub fn write_to_objs(&self, path_prefix: &str) -> StatusOr<()> {
+    // Writes <out_dir>/<name><i>_<num meshes>.obj for each mesh.
+    pub fn write_to_objs(&self, out_dir: &Path, name: &str) -> StatusOr<()> {
         let num_meshes = self.meshes.len();
         for i in 0..num_meshes {
-            let out_path = format!("{}{}_{}.obj", path_prefix, i, num_meshes);
-            self.meshes[i].write_to_obj(out_path.as_str())?;
+            let out_path = out_dir.join(format!("{}{}_{}.obj", name, i, num_meshes));
+            self.meshes[i].write_to_obj(&out_path)?;
         }
         Ok(())
     }
 
-    pub fn update(&mut self, dt: DeltaTime) {
-        self.current_mesh_ticker.update(dt);
-    }
+    // Writes a .glb, or a .gltf with a .bin beside it, by out_path's extension. The sheet is
+    // embedded as the texture, each frame is a child node, and each of animation's states
+    // is an animation of one pass through it, showing each frame for its duration. Frames differ
+    // in topology, so frames switch by scaling nodes to zero rather than by morph targets.
+    pub fn write_to_gltf(&self, out_path: &Path, animation: &SpriteAnimation) -> StatusOr<()> {
+        let sheet_bytes = sheet_file::png_bytes(&self.sheet_filepath)?;
+        let name = self.sheet_filepath.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+        let states = animation.states();
+        let first_frame = animation.current_mesh_index();
+
+        let mut gltf = GltfBuilder::new();
+        let material = gltf.add_png_material(&name, &sheet_bytes);
+        let root = gltf.add_node(&name, None);
+        // Matches the sprite shader, which draws position - center.
+        gltf.set_translation(root, -self.object_center);
+        gltf.add_root(root);
+        let mut frame_nodes = Vec::with_capacity(self.meshes.len());
+        for (i, mesh) in self.meshes.iter().enumerate() {
+            let frame_name = self.frame_names[i].clone().unwrap_or_else(|| format!("{}_{}", name, i));
+            // Empty frames still get a node, without a mesh, so animations can show them.
+            let mesh = gltf.add_mesh(&frame_name, mesh, Some(material));
+            let node = gltf.add_node(&frame_name, mesh);
+            // Viewers that don't play animations show animation's current frame.
+            if i != first_frame {
+                gltf.set_scale(node, glm::vec3(0.0, 0.0, 0.0));
+            }
+            gltf.add_child(root, node);
+            frame_nodes.push(node);
+        }
 
-    pub fn set_state_index(&mut self, state: usize) -> StatusOr<()> {
-       if state >= self.all_states.len() {
-           Err(format!("Only have {} states. Trying to set state to {}", self.all_states.len(), state))
-       } else {
-           self.current_state = state;
-           self.current_mesh_ticker.clear();
-           Ok(())
-       }
+        for state in states.iter() {
+            let cycle = state.cycle();
+            // One key as each frame starts plus a closing key, so the last frame is held for its duration.
+            // Looping states close on their first frame and once states on their last.
+            let mut times = vec![0.0];
+            for &frame in cycle.iter() {
+                let start = times[times.len() - 1];
+                times.push(start + animation.frame_duration(frame) as f32 / 1_000_000.0);
+            }
+            let closing_frame = if state.playback == Playback::Once { cycle[cycle.len() - 1] } else { cycle[0] };
+            let tracks: Vec<(usize, Vec<bool>)> = frame_nodes.iter().enumerate()
+                .map(|(frame, &node)| (node, cycle.iter().chain(Some(&closing_frame)).map(|&shown| shown == frame).collect()))
+                .collect();
+            gltf.add_visibility_animation(&state.name, &times, &tracks);
+        }
+        gltf.write(out_path)
     }
-}
\ No newline at end of file
+}
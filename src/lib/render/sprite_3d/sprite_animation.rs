@@ -0,0 +1,481 @@
+use app::{
+    Error,
+    StatusOr,
+};
+use dimensions::time::{
+    DeltaTime,
+    Microseconds
+};
+use render::sprite_3d::sprite_sheet::{
+    SpriteSheet,
+    StateConfig,
+};
+use std::{
+    collections::HashMap,
+    mem,
+};
+
+// How a state plays its frames.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Playback {
+    Loop,
+    // Forwards then backwards, without repeating the first and last frames.
+    PingPong,
+    // Through once, then on to the state's then state, or else holding the last frame.
+    Once,
+}
+
+impl Default for Playback {
+    fn default() -> Playback {
+        Playback::Loop
+    }
+}
+
+// An event to emit as a state's frame starts showing, as configured in e.g. athlete.config.
+#[derive(Clone, Debug, Deserialize)]
+pub struct FrameEventConfig {
+    // Position in the state's frames, not a mesh index.
+    pub frame: usize,
+    pub event: String,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct AnimationStateConfig {
+    pub name: String,
+    pub frames: StateConfig,
+    #[serde(default)]
+    pub playback: Playback,
+    // Only for once states.
+    #[serde(default)]
+    pub then: Option<String>,
+    #[serde(default)]
+    pub events: Vec<FrameEventConfig>,
+}
+
+// Holds when the parameter passes every comparison given. Parameters never set are 0.
+#[derive(Clone, Debug, Deserialize)]
+pub struct Condition {
+    pub parameter: String,
+    #[serde(default)]
+    pub above: Option<f32>,
+    #[serde(default)]
+    pub below: Option<f32>,
+    // Whether the parameter is non-zero, for flags.
+    #[serde(default)]
+    pub is: Option<bool>,
+}
+
+impl Condition {
+    fn holds(&self, value: f32) -> bool {
+        self.above.map_or(true, |above| value > above)
+            && self.below.map_or(true, |below| value < below)
+            && self.is.map_or(true, |is| (value != 0.0) == is)
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct TransitionConfig {
+    // Any state but to, if not given.
+    #[serde(default)]
+    pub from: Option<String>,
+    pub to: String,
+    #[serde(default)]
+    pub when: Vec<Condition>,
+}
+
+// An entity's animation state machine, as configured in e.g. athlete.config.
+#[derive(Clone, Debug, Deserialize)]
+pub struct AnimationConfig {
+    // Defaults to the first state.
+    #[serde(default)]
+    pub initial_state: Option<String>,
+    pub states: Vec<AnimationStateConfig>,
+    // Checked in order each update, and the first that applies is taken.
+    #[serde(default)]
+    pub transitions: Vec<TransitionConfig>,
+}
+
+#[derive(Clone, Debug)]
+pub struct AnimationState {
+    pub name: String,
+    // Mesh indexes, in the order configured.
+    pub frames: Vec<usize>,
+    pub playback: Playback,
+    then: Option<usize>,
+    // (position in frames, event name)
+    events: Vec<(usize, String)>,
+}
+
+impl AnimationState {
+    // How many frames one pass through the state shows.
+    fn cycle_len(&self) -> usize {
+        match self.playback {
+            Playback::PingPong if self.frames.len() > 1 => 2 * self.frames.len() - 2,
+            _ => self.frames.len(),
+        }
+    }
+
+    // Which of frames the step'th frame of a pass shows.
+    fn position(&self, step: usize) -> usize {
+        if step < self.frames.len() {
+            step
+        } else {
+            2 * self.frames.len() - 2 - step
+        }
+    }
+
+    // The mesh indexes one pass through the state shows.
+    pub fn cycle(&self) -> Vec<usize> {
+        (0..self.cycle_len()).map(|step| self.frames[self.position(step)]).collect()
+    }
+}
+
+struct Transition {
+    from: Option<usize>,
+    to: usize,
+    when: Vec<Condition>,
+}
+
+// Emitted as the frame it's configured on starts showing.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AnimationEvent {
+    pub state: String,
+    pub event: String,
+}
+
+// Which frame of a Sprite3D sheet to show. Holds no GPU resources, so it can be
+// simulated without a Sprite3D existing.
+pub struct SpriteAnimation {
+    // How long each sheet frame shows for, by mesh index.
+    frame_durations: Vec<Microseconds>,
+    states: Vec<AnimationState>,
+    transitions: Vec<Transition>,
+    parameters: HashMap<String, f32>,
+    current_state: usize,
+    // Where in a pass through the current state the animation is.
+    current_step: usize,
+    elapsed_in_frame: Microseconds,
+    // Set once a once state without a then state shows its last frame for its duration.
+    finished: bool,
+    // Emitted since the last update returned them.
+    events: Vec<AnimationEvent>,
+}
+
+impl SpriteAnimation {
+    pub fn new(config: &AnimationConfig, sheet: &SpriteSheet, frame_durations: Vec<Microseconds>) -> StatusOr<SpriteAnimation> {
+        let state_index = |name: &str| config.states.iter()
+            .position(|state| state.name == name)
+            .ok_or_else(|| Error::InvalidArgument(format!("No animation state named {}.", name)));
+
+        let mut states = Vec::with_capacity(config.states.len());
+        for (i, state) in config.states.iter().enumerate() {
+            let invalid = |reason: String| Error::InvalidArgument(format!("Animation state {}: {}", state.name, reason));
+            if config.states[..i].iter().any(|other| other.name == state.name) {
+                return Err(invalid("the name is already taken.".to_string()));
+            }
+            let frames = sheet.state_indexes(&state.frames)?;
+            if frames.is_empty() {
+                return Err(invalid("it has no frames.".to_string()));
+            }
+            if state.then.is_some() && state.playback != Playback::Once {
+                return Err(invalid("only once states can have a then state.".to_string()));
+            }
+            let mut events = Vec::with_capacity(state.events.len());
+            for event in state.events.iter() {
+                if event.frame >= frames.len() {
+                    return Err(invalid(format!("only has {} frames. No frame {} for event {}.",
+                                               frames.len(), event.frame, event.event)));
+                }
+                events.push((event.frame, event.event.clone()));
+            }
+            states.push(AnimationState {
+                name: state.name.clone(),
+                frames,
+                playback: state.playback,
+                then: match state.then {
+                    Some(ref then) => Some(state_index(then)?),
+                    None => None,
+                },
+                events,
+            });
+        }
+        if states.is_empty() {
+            return Err(Error::InvalidArgument("An animation needs at least one state.".to_string()));
+        }
+
+        let mut transitions = Vec::with_capacity(config.transitions.len());
+        for transition in config.transitions.iter() {
+            transitions.push(Transition {
+                from: match transition.from {
+                    Some(ref from) => Some(state_index(from)?),
+                    None => None,
+                },
+                to: state_index(&transition.to)?,
+                when: transition.when.clone(),
+            });
+        }
+
+        let mut animation = SpriteAnimation {
+            frame_durations,
+            states,
+            transitions,
+            parameters: HashMap::new(),
+            current_state: 0,
+            current_step: 0,
+            elapsed_in_frame: 0,
+            finished: false,
+            events: Vec::new(),
+        };
+        let initial_state = match config.initial_state {
+            Some(ref initial_state) => state_index(initial_state)?,
+            None => 0,
+        };
+        animation.enter_state(initial_state);
+        Ok(animation)
+    }
+
+    pub fn set_parameter(&mut self, name: &str, value: f32) {
+        if let Some(parameter) = self.parameters.get_mut(name) {
+            *parameter = value;
+            return;
+        }
+        self.parameters.insert(name.to_string(), value);
+    }
+
+    pub fn set_flag(&mut self, name: &str, value: bool) {
+        self.set_parameter(name, if value { 1.0 } else { 0.0 });
+    }
+
+    pub fn parameter(&self, name: &str) -> f32 {
+        self.parameters.get(name).cloned().unwrap_or(0.0)
+    }
+
+    // Takes the first transition whose conditions hold, then advances by dt. Returns the
+    // events emitted since the last update, in order.
+    pub fn update(&mut self, dt: DeltaTime) -> Vec<AnimationEvent> {
+        let taken = self.transitions.iter()
+            .find(|transition| transition.to != self.current_state
+                && transition.from.map_or(true, |from| from == self.current_state)
+                && transition.when.iter().all(|condition| condition.holds(self.parameter(&condition.parameter))))
+            .map(|transition| transition.to);
+        if let Some(to) = taken {
+            self.elapsed_in_frame = 0;
+            self.enter_state(to);
+        }
+
+        self.elapsed_in_frame += dt.as_microseconds();
+        while !self.finished && self.state_duration() > 0 && self.elapsed_in_frame > self.current_frame_duration() {
+            self.elapsed_in_frame -= self.current_frame_duration();
+            self.advance();
+        }
+        mem::replace(&mut self.events, Vec::new())
+    }
+
+    // Switches straight to the named state, whatever the transitions say.
+    pub fn set_state(&mut self, name: &str) -> StatusOr<()> {
+        match self.states.iter().position(|state| state.name == name) {
+            Some(state) => {
+                self.elapsed_in_frame = 0;
+                self.enter_state(state);
+                Ok(())
+            },
+            None => Err(Error::InvalidArgument(format!("No animation state named {}.", name))),
+        }
+    }
+
+    pub fn current_state(&self) -> &str {
+        &self.states[self.current_state].name
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    pub fn frame_duration(&self, mesh_index: usize) -> Microseconds {
+        self.frame_durations[mesh_index]
+    }
+
+    pub fn states(&self) -> &[AnimationState] {
+        &self.states
+    }
+
+    pub fn current_mesh_index(&self) -> usize {
+        let state = &self.states[self.current_state];
+        state.frames[state.position(self.current_step)]
+    }
+
+    fn state_duration(&self) -> Microseconds {
+        self.states[self.current_state].frames.iter().map(|&mesh_index| self.frame_durations[mesh_index]).sum()
+    }
+
+    fn current_frame_duration(&self) -> Microseconds {
+        self.frame_durations[self.current_mesh_index()]
+    }
+
+    // Leaves elapsed_in_frame alone, so time left over from a once state carries into its then state.
+    fn enter_state(&mut self, state: usize) {
+        self.current_state = state;
+        self.current_step = 0;
+        self.finished = false;
+        self.emit_frame_events();
+    }
+
+    fn advance(&mut self) {
+        let (cycle_len, playback, then) = {
+            let state = &self.states[self.current_state];
+            (state.cycle_len(), state.playback, state.then)
+        };
+        if self.current_step + 1 < cycle_len {
+            self.current_step += 1;
+            self.emit_frame_events();
+            return;
+        }
+        match (playback, then) {
+            (Playback::Once, Some(then)) => self.enter_state(then),
+            (Playback::Once, None) => self.finished = true,
+            _ => {
+                self.current_step = 0;
+                self.emit_frame_events();
+            },
+        }
+    }
+
+    fn emit_frame_events(&mut self) {
+        let state = &self.states[self.current_state];
+        let position = state.position(self.current_step);
+        for &(frame, ref event) in state.events.iter() {
+            if frame == position {
+                self.events.push(AnimationEvent { state: state.name.clone(), event: event.clone() });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use render::sprite_3d::sprite_sheet::SheetLayout;
+    use serde_json;
+
+    const FRAME: Microseconds = 10_000;
+
+    // Five 10ms frames, 0 to 4.
+    fn animation(config: &str) -> SpriteAnimation {
+        let sheet = SpriteSheet::from_layout(&SheetLayout::Grid {
+            image: "ball.png".to_string(), frame_width: 4, frame_height: 4, rows: 1, columns: 5, margin: 0, padding: 0,
+        }).unwrap();
+        let config: AnimationConfig = serde_json::from_str(config).unwrap();
+        SpriteAnimation::new(&config, &sheet, vec![FRAME; 5]).unwrap()
+    }
+
+    fn step(animation: &mut SpriteAnimation, dt: Microseconds) -> Vec<String> {
+        animation.update(DeltaTime::from_microseconds(dt)).into_iter().map(|event| event.event).collect()
+    }
+
+    // The mesh index after each of count steps of dt.
+    fn mesh_indexes(animation: &mut SpriteAnimation, dt: Microseconds, count: usize) -> Vec<usize> {
+        (0..count).map(|_| {
+            step(animation, dt);
+            animation.current_mesh_index()
+        }).collect()
+    }
+
+    #[test]
+    fn loop_wraps_around() {
+        let mut animation = animation(r#"{"states": [{"name": "run", "frames": [1, 2, 3]}]}"#);
+        assert_eq!(animation.current_mesh_index(), 1);
+        // A frame shows for its whole duration, and moves on once time passes it.
+        assert_eq!(mesh_indexes(&mut animation, FRAME, 7), vec![1, 2, 3, 1, 2, 3, 1]);
+        assert!(!animation.is_finished());
+    }
+
+    #[test]
+    fn ping_pong_does_not_repeat_end_frames() {
+        let mut animation = animation(r#"{"states": [{"name": "run", "frames": [0, 1, 2, 3], "playback": "ping_pong"}]}"#);
+        step(&mut animation, 1);
+        assert_eq!(mesh_indexes(&mut animation, FRAME, 8), vec![1, 2, 3, 2, 1, 0, 1, 2]);
+        assert_eq!(animation.states()[0].cycle(), vec![0, 1, 2, 3, 2, 1]);
+    }
+
+    #[test]
+    fn once_hands_off_to_then_with_leftover_time() {
+        let mut animation = animation(r#"{"states": [
+            {"name": "idle", "frames": [0, 1]},
+            {"name": "kick", "frames": [3, 4], "playback": "once", "then": "idle"}
+        ]}"#);
+        animation.set_state("kick").unwrap();
+        // 2.5 frames in: through kick's two frames, then half way into idle's first.
+        step(&mut animation, 5 * FRAME / 2);
+        assert_eq!(animation.current_state(), "idle");
+        assert_eq!(animation.current_mesh_index(), 0);
+        // Only the other half of idle's first frame is left.
+        step(&mut animation, FRAME / 2 + 1);
+        assert_eq!(animation.current_mesh_index(), 1);
+    }
+
+    #[test]
+    fn once_without_then_holds_last_frame() {
+        let mut animation = animation(r#"{"states": [{"name": "fall", "frames": [2, 3, 4], "playback": "once"}]}"#);
+        assert_eq!(mesh_indexes(&mut animation, FRAME, 2), vec![2, 3]);
+        assert!(!animation.is_finished());
+        assert_eq!(mesh_indexes(&mut animation, FRAME, 3), vec![4, 4, 4]);
+        assert!(animation.is_finished());
+        animation.set_state("fall").unwrap();
+        assert!(!animation.is_finished());
+        assert_eq!(animation.current_mesh_index(), 2);
+    }
+
+    #[test]
+    fn first_matching_transition_wins() {
+        let mut animation = animation(r#"{
+            "states": [
+                {"name": "idle", "frames": [0]},
+                {"name": "run", "frames": [1]},
+                {"name": "sprint", "frames": [2]},
+                {"name": "celebrate", "frames": [3]}
+            ],
+            "transitions": [
+                {"from": "idle", "to": "sprint", "when": [{"parameter": "speed", "above": 8.0}]},
+                {"from": "idle", "to": "run", "when": [{"parameter": "speed", "above": 1.0}]},
+                {"to": "celebrate", "when": [{"parameter": "scored", "is": true}]},
+                {"to": "idle", "when": [{"parameter": "speed", "below": 1.0}]}
+            ]
+        }"#);
+        animation.set_parameter("speed", 10.0);
+        step(&mut animation, 1);
+        assert_eq!(animation.current_state(), "sprint");
+
+        animation.set_state("idle").unwrap();
+        animation.set_parameter("speed", 5.0);
+        step(&mut animation, 1);
+        assert_eq!(animation.current_state(), "run");
+
+        // Both scoring and slowing down apply, and scoring is listed first.
+        animation.set_parameter("speed", 0.0);
+        animation.set_flag("scored", true);
+        step(&mut animation, 1);
+        assert_eq!(animation.current_state(), "celebrate");
+        animation.set_flag("scored", false);
+        step(&mut animation, 1);
+        assert_eq!(animation.current_state(), "idle");
+    }
+
+    #[test]
+    fn large_dt_still_fires_skipped_frame_events() {
+        let mut animation = animation(r#"{"states": [
+            {"name": "idle", "frames": [0]},
+            {"name": "kick", "frames": [1, 2, 3], "playback": "once", "then": "idle",
+             "events": [{"frame": 0, "event": "wind_up"}, {"frame": 1, "event": "kick_contact"}, {"frame": 2, "event": "follow_through"}]},
+            {"name": "run", "frames": [0, 1, 2, 3], "events": [{"frame": 1, "event": "foot_plant"}, {"frame": 3, "event": "foot_plant"}]}
+        ]}"#);
+        animation.set_state("kick").unwrap();
+        assert_eq!(step(&mut animation, 10 * FRAME), vec!["wind_up", "kick_contact", "follow_through"]);
+        assert_eq!(animation.current_state(), "idle");
+
+        animation.set_state("run").unwrap();
+        // Two and a half passes: plants on frames 1 and 3 of each.
+        assert_eq!(step(&mut animation, 10 * FRAME + FRAME / 2), vec!["foot_plant"; 5]);
+        assert_eq!(animation.current_mesh_index(), 2);
+    }
+}
@@ -0,0 +1,350 @@
+use app::{
+    Error,
+    StatusOr,
+};
+use dimensions::{
+    time::{
+        self,
+        Microseconds,
+    },
+    Pixels,
+};
+use file;
+use image::{
+    png::ImageRect,
+    Aseprite,
+    Piskel,
+};
+use serde_json;
+use std::path::{
+    Path,
+    PathBuf,
+};
+
+// How a sprite sheet is cut into frames, as configured in e.g. athlete.config.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SheetLayout {
+    // rows x columns frames of frame_width x frame_height of image, a PNG in res/images,
+    // numbered across each row in turn. margin pixels surround the grid and padding pixels
+    // separate neighbouring frames.
+    Grid {
+        image: String,
+        frame_width: Pixels,
+        frame_height: Pixels,
+        rows: usize,
+        columns: usize,
+        #[serde(default)]
+        margin: Pixels,
+        #[serde(default)]
+        padding: Pixels,
+    },
+    // Named frame rectangles listed by a JSON atlas in res/images, of the image its meta names.
+    Atlas {
+        atlas: String,
+    },
+    // A .piskel file in res/misc, used as the sheet itself. Its frames are unnamed and
+    // play at its fps unless the config says otherwise.
+    Piskel {
+        piskel: String,
+    },
+    // An .ase or .aseprite file in res/misc, used as the sheet itself. Its frames are
+    // unnamed, keep their own durations unless the config says otherwise, and its tags
+    // can be used as states.
+    Aseprite {
+        aseprite: String,
+    },
+}
+
+// A rectangle of a sprite sheet that reifies into one mesh.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SpriteFrame {
+    // Only atlas frames are named.
+    pub name: Option<String>,
+    pub x: Pixels,
+    pub y: Pixels,
+    pub width: Pixels,
+    pub height: Pixels,
+}
+
+impl SpriteFrame {
+    pub fn image_rect(&self) -> ImageRect {
+        ImageRect::new(self.y, self.x, self.width, self.height)
+    }
+
+    pub fn fits_in(&self, (width, height): (Pixels, Pixels)) -> bool {
+        self.width > 0 && self.height > 0 && self.x + self.width <= width && self.y + self.height <= height
+    }
+}
+
+// A frame in an animation state, as configured in e.g. athlete.config.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(untagged)]
+pub enum FrameRef {
+    Index(usize),
+    Name(String),
+}
+
+// The frames of an animation state, as configured in e.g. athlete.config: a list of frames,
+// or the name of a tag in the sheet's file.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(untagged)]
+pub enum StateConfig {
+    Frames(Vec<FrameRef>),
+    Tag(String),
+}
+
+// The atlas format is TexturePacker's "JSON (Array)" export. Only the frame rectangles and
+// the image are read, and rotated or trimmed frames are rejected.
+#[derive(Deserialize)]
+struct AtlasFile {
+    frames: Vec<AtlasFrame>,
+    meta: AtlasMeta,
+}
+
+#[derive(Deserialize)]
+struct AtlasMeta {
+    // Relative to the atlas.
+    image: String,
+}
+
+#[derive(Deserialize)]
+struct AtlasFrame {
+    filename: String,
+    frame: AtlasRect,
+    #[serde(default)]
+    rotated: bool,
+    #[serde(default)]
+    trimmed: bool,
+}
+
+#[derive(Deserialize)]
+struct AtlasRect {
+    x: Pixels,
+    y: Pixels,
+    w: Pixels,
+    h: Pixels,
+}
+
+// The frames of a sprite sheet, in mesh index order.
+#[derive(Clone, Debug)]
+pub struct SpriteSheet {
+    frames: Vec<SpriteFrame>,
+    // The file holding the sheet's pixels.
+    source: PathBuf,
+    // In milliseconds, one per frame, if the sheet's file has them.
+    frame_millis: Option<Vec<i64>>,
+    // States named in the sheet's file, as mesh indexes.
+    tags: Vec<(String, Vec<usize>)>,
+}
+
+impl SpriteSheet {
+    pub fn from_layout(layout: &SheetLayout) -> StatusOr<SpriteSheet> {
+        match *layout {
+            SheetLayout::Grid { ref image, frame_width, frame_height, rows, columns, margin, padding } => {
+                let mut frames = Vec::with_capacity(rows * columns);
+                for row in 0..rows {
+                    for column in 0..columns {
+                        frames.push(SpriteFrame {
+                            name: None,
+                            x: margin + column * (frame_width + padding),
+                            y: margin + row * (frame_height + padding),
+                            width: frame_width,
+                            height: frame_height,
+                        });
+                    }
+                }
+                Ok(SpriteSheet {
+                    frames,
+                    source: file::util::resource_path("images", image)?,
+                    frame_millis: None,
+                    tags: Vec::new(),
+                })
+            },
+            SheetLayout::Atlas { ref atlas } => SpriteSheet::from_atlas(&file::util::resource_path("images", atlas)?),
+            SheetLayout::Piskel { ref piskel } => SpriteSheet::from_piskel(&file::util::resource_path("misc", piskel)?),
+            SheetLayout::Aseprite { ref aseprite } =>
+                SpriteSheet::from_aseprite(&file::util::resource_path("misc", aseprite)?),
+        }
+    }
+
+    pub fn from_atlas(path: &Path) -> StatusOr<SpriteSheet> {
+        let data = file::util::slurp_file(path).map_err(|err| Error::io(path.to_path_buf(), err))?;
+        let atlas: AtlasFile = serde_json::from_str(data.as_str())
+            .map_err(|err| Error::ConfigParse { path: path.to_path_buf(), source: err })?;
+        let mut frames: Vec<SpriteFrame> = Vec::with_capacity(atlas.frames.len());
+        for frame in atlas.frames.into_iter() {
+            let invalid = |reason: &str| Error::InvalidArgument(
+                format!("Frame {} in {}: {}", frame.filename, path.display(), reason));
+            if frame.rotated || frame.trimmed {
+                return Err(invalid("rotated and trimmed frames are not supported."));
+            }
+            if frames.iter().any(|other| other.name.as_ref() == Some(&frame.filename)) {
+                return Err(invalid("the name is already taken."));
+            }
+            frames.push(SpriteFrame {
+                name: Some(frame.filename),
+                x: frame.frame.x,
+                y: frame.frame.y,
+                width: frame.frame.w,
+                height: frame.frame.h,
+            });
+        }
+        Ok(SpriteSheet { frames, source: path.with_file_name(atlas.meta.image), frame_millis: None, tags: Vec::new() })
+    }
+
+    // Frames are laid out as Piskel::sprite_sheet does.
+    pub fn from_piskel(path: &Path) -> StatusOr<SpriteSheet> {
+        let piskel = Piskel::from_file(path)?;
+        let (width, height) = piskel.frame_size();
+        let frame_millis = if piskel.fps() > 0 {
+            Some(vec![(1000.0 / piskel.fps() as f64).round() as i64; piskel.frame_count()])
+        } else {
+            None
+        };
+        Ok(SpriteSheet {
+            frames: strip_frames(width, height, piskel.frame_count()),
+            source: path.to_path_buf(),
+            frame_millis,
+            tags: Vec::new(),
+        })
+    }
+
+    // Frames are laid out as Aseprite::sprite_sheet does, and each tag becomes a state.
+    pub fn from_aseprite(path: &Path) -> StatusOr<SpriteSheet> {
+        let aseprite = Aseprite::from_file(path)?;
+        let (width, height) = aseprite.frame_size();
+        Ok(SpriteSheet {
+            frames: strip_frames(width, height, aseprite.frame_count()),
+            source: path.to_path_buf(),
+            frame_millis: Some(aseprite.frame_durations().to_vec()),
+            tags: aseprite.tags().iter().map(|tag| (tag.name.clone(), tag.frames())).collect(),
+        })
+    }
+
+    pub fn frames(&self) -> &[SpriteFrame] {
+        &self.frames
+    }
+
+    pub fn source(&self) -> &Path {
+        &self.source
+    }
+
+    // How long each frame shows for: configured_millis if given, or else as the sheet's
+    // file says.
+    pub fn frame_durations(&self, configured_millis: Option<i64>) -> StatusOr<Vec<Microseconds>> {
+        match (configured_millis, &self.frame_millis) {
+            (Some(millis), _) => Ok(vec![time::milliseconds(millis); self.frames.len()]),
+            (None, &Some(ref frame_millis)) => Ok(frame_millis.iter().map(|&millis| time::milliseconds(millis)).collect()),
+            (None, &None) => Err(Error::InvalidArgument(
+                "Frame durations must be configured for sheets whose files don't have them.".to_string())),
+        }
+    }
+
+    pub fn frame_index(&self, frame: &FrameRef) -> StatusOr<usize> {
+        match *frame {
+            FrameRef::Index(index) if index < self.frames.len() => Ok(index),
+            FrameRef::Index(index) =>
+                Err(Error::InvalidArgument(format!("Only have {} frames. No frame {}.", self.frames.len(), index))),
+            FrameRef::Name(ref name) => self.frames.iter()
+                .position(|frame| frame.name.as_ref() == Some(name))
+                .ok_or_else(|| Error::InvalidArgument(format!("No frame named {}.", name))),
+        }
+    }
+
+    // Turns a configured animation state's frames into mesh indexes.
+    pub fn state_indexes(&self, state: &StateConfig) -> StatusOr<Vec<usize>> {
+        match *state {
+            StateConfig::Frames(ref frames) => frames.iter().map(|frame| self.frame_index(frame)).collect(),
+            StateConfig::Tag(ref name) => self.tags.iter()
+                .find(|&&(ref tag, _)| tag == name)
+                .map(|&(_, ref indexes)| indexes.clone())
+                .ok_or_else(|| Error::InvalidArgument(format!("No tag named {}.", name))),
+        }
+    }
+}
+
+// count frames of width x height side by side, as editors export strips.
+fn strip_frames(width: Pixels, height: Pixels, count: usize) -> Vec<SpriteFrame> {
+    (0..count).map(|i| SpriteFrame { name: None, x: i * width, y: 0, width, height }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        env,
+        fs,
+        process,
+    };
+
+    fn frame(name: &str, x: Pixels, y: Pixels) -> String {
+        format!(r#"{{"filename": "{}", "frame": {{"x": {}, "y": {}, "w": 8, "h": 8}}}}"#, name, x, y)
+    }
+
+    fn load_atlas(frames: &[String], name: &str) -> StatusOr<SpriteSheet> {
+        let path = env::temp_dir().join(format!("sprite_sheet_{}_{}.json", process::id(), name));
+        fs::write(&path, format!(r#"{{"frames": [{}], "meta": {{"image": "sheet.png"}}}}"#, frames.join(","))).unwrap();
+        let sheet = SpriteSheet::from_atlas(&path);
+        fs::remove_file(&path).unwrap();
+        sheet
+    }
+
+    fn rect(frame: &SpriteFrame) -> (Pixels, Pixels, Pixels, Pixels) {
+        (frame.x, frame.y, frame.width, frame.height)
+    }
+
+    #[test]
+    fn grid_frames_skip_margin_and_padding() {
+        let sheet = SpriteSheet::from_layout(&SheetLayout::Grid {
+            image: "ball.png".to_string(), frame_width: 5, frame_height: 3, rows: 2, columns: 3, margin: 2, padding: 1,
+        }).unwrap();
+        let rects: Vec<_> = sheet.frames().iter().map(rect).collect();
+        assert_eq!(rects, vec![
+            (2, 2, 5, 3), (8, 2, 5, 3), (14, 2, 5, 3),
+            (2, 6, 5, 3), (8, 6, 5, 3), (14, 6, 5, 3),
+        ]);
+        assert!(sheet.frames().iter().all(|frame| frame.name.is_none()));
+        // The last frame ends 2 pixels shy of a 21 x 11 sheet's edges, where the margin is.
+        assert!(sheet.frames()[5].fits_in((21, 11)));
+        assert!(sheet.frames()[5].fits_in((19, 9)));
+        assert!(!sheet.frames()[5].fits_in((18, 9)));
+        assert_eq!(sheet.source().file_name().unwrap(), "ball.png");
+    }
+
+    #[test]
+    fn atlas_frames_keep_their_names_and_image() {
+        let sheet = load_atlas(&[frame("run_0", 0, 0), frame("run_1", 8, 0), frame("jump", 0, 8)], "valid").unwrap();
+        let names: Vec<_> = sheet.frames().iter().map(|frame| frame.name.clone().unwrap()).collect();
+        assert_eq!(names, vec!["run_0", "run_1", "jump"]);
+        assert_eq!(rect(&sheet.frames()[2]), (0, 8, 8, 8));
+        assert_eq!(sheet.source(), env::temp_dir().join("sheet.png"));
+    }
+
+    #[test]
+    fn atlas_rejects_rotated_trimmed_and_duplicate_frames() {
+        let rotated = r#"{"filename": "turned", "frame": {"x": 0, "y": 0, "w": 8, "h": 8}, "rotated": true}"#;
+        let trimmed = r#"{"filename": "cropped", "frame": {"x": 0, "y": 0, "w": 8, "h": 8}, "trimmed": true}"#;
+        assert!(load_atlas(&[frame("run_0", 0, 0), rotated.to_string()], "rotated").is_err());
+        assert!(load_atlas(&[trimmed.to_string()], "trimmed").is_err());
+        assert!(load_atlas(&[frame("run_0", 0, 0), frame("run_0", 8, 0)], "duplicate").is_err());
+    }
+
+    #[test]
+    fn frames_by_index_name_or_tag() {
+        let mut sheet = load_atlas(&[frame("run_0", 0, 0), frame("run_1", 8, 0), frame("jump", 0, 8)], "refs").unwrap();
+        sheet.tags = vec![("hop".to_string(), vec![2, 0])];
+
+        assert_eq!(sheet.frame_index(&FrameRef::Index(2)).unwrap(), 2);
+        assert!(sheet.frame_index(&FrameRef::Index(3)).is_err());
+        assert_eq!(sheet.frame_index(&FrameRef::Name("run_1".to_string())).unwrap(), 1);
+        assert!(sheet.frame_index(&FrameRef::Name("walk_0".to_string())).is_err());
+
+        let frames: StateConfig = serde_json::from_str(r#"["jump", 0, "run_1"]"#).unwrap();
+        assert_eq!(sheet.state_indexes(&frames).unwrap(), vec![2, 0, 1]);
+        let tag: StateConfig = serde_json::from_str(r#""hop""#).unwrap();
+        assert_eq!(sheet.state_indexes(&tag).unwrap(), vec![2, 0]);
+        let missing: StateConfig = serde_json::from_str(r#""skip""#).unwrap();
+        assert!(sheet.state_indexes(&missing).is_err());
+    }
+}
@@ -0,0 +1,133 @@
+use app::StatusOr;
+use assets::{
+    AssetManager,
+    Handle,
+};
+use font::{
+    Alignment,
+    FontAtlas,
+};
+use gl::{
+    self,
+    types::*,
+};
+use glm;
+use image::Texture;
+use shader::ShaderProgram;
+use std;
+
+// x, y, u, v
+const FLOATS_PER_VERTEX: usize = 4;
+
+// Draws screen-space text from one font atlas. Positions are in window pixels, y down.
+pub struct TextRenderer {
+    atlas: Handle<FontAtlas>,
+    texture: Texture,
+    shader: Handle<ShaderProgram>,
+    vao: GLuint,
+    vbo: GLuint,
+}
+
+impl TextRenderer {
+    // atlas_pixel_height is the size glyphs are rasterized at; text drawn near it looks sharpest.
+    pub fn new(font_name: &str, atlas_pixel_height: u32, assets: &mut AssetManager) -> StatusOr<TextRenderer> {
+        let atlas = assets.font(font_name, atlas_pixel_height)?;
+        let texture = Texture::new(&atlas.png);
+        let mut text_renderer = TextRenderer {
+            atlas,
+            texture,
+            shader: assets.shader("text_vert.glsl", None, "text_frag.glsl")?,
+            vao: 0,
+            vbo: 0,
+        };
+        unsafe {
+            // Glyphs are usually drawn at fractional positions and scales.
+            gl::BindTexture(gl::TEXTURE_2D, text_renderer.texture.texture_id);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+
+            gl::GenVertexArrays(1, &mut text_renderer.vao);
+            gl::GenBuffers(1, &mut text_renderer.vbo);
+            gl::BindVertexArray(text_renderer.vao);
+            gl::BindBuffer(gl::ARRAY_BUFFER, text_renderer.vbo);
+            let float_size = std::mem::size_of::<f32>() as isize;
+            let stride = FLOATS_PER_VERTEX as i32 * float_size as i32;
+            // Positions go into attrib array = 0
+            gl::EnableVertexAttribArray(0);
+            gl::VertexAttribPointer(0, 2, gl::FLOAT, gl::FALSE, stride, std::ptr::null());
+            // Texture coords go into attrib array = 1
+            let tex_coord_offset = (std::ptr::null() as *const GLvoid).offset(2 * float_size);
+            gl::EnableVertexAttribArray(1);
+            gl::VertexAttribPointer(1, 2, gl::FLOAT, gl::FALSE, stride, tex_coord_offset);
+            gl::BindVertexArray(0);
+        }
+        text_renderer.shader.activate();
+        text_renderer.shader.set_i32("atlas", 0);
+        Ok(text_renderer)
+    }
+
+    pub fn atlas(&self) -> &FontAtlas {
+        &self.atlas
+    }
+
+    // Draws text with its first line's top at (x, y), over whatever is in the bound framebuffer.
+    pub fn draw(&self,
+                text: &str,
+                x: f32,
+                y: f32,
+                pixel_height: f32,
+                alignment: Alignment,
+                color: &glm::Vec4,
+                screen_size: (u32, u32)) {
+        let quads = self.atlas.layout(text, x, y, pixel_height, alignment);
+        if quads.is_empty() {
+            return;
+        }
+        let mut vertices: Vec<f32> = Vec::with_capacity(quads.len() * 6 * FLOATS_PER_VERTEX);
+        for quad in quads.iter() {
+            let top_left = [quad.left, quad.top, quad.uv_left, quad.uv_top];
+            let top_right = [quad.right, quad.top, quad.uv_right, quad.uv_top];
+            let bottom_left = [quad.left, quad.bottom, quad.uv_left, quad.uv_bottom];
+            let bottom_right = [quad.right, quad.bottom, quad.uv_right, quad.uv_bottom];
+            for corner in [top_left, bottom_left, bottom_right, top_left, bottom_right, top_right].iter() {
+                vertices.extend_from_slice(corner);
+            }
+        }
+
+        self.shader.activate();
+        self.shader.set_vec2("screen_size", &glm::vec2(screen_size.0 as f32, screen_size.1 as f32));
+        self.shader.set_vec4("color", color);
+        unsafe {
+            gl::Disable(gl::DEPTH_TEST);
+            gl::Enable(gl::BLEND);
+            gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+            gl::ActiveTexture(gl::TEXTURE0);
+            gl::BindTexture(gl::TEXTURE_2D, self.texture.texture_id);
+
+            gl::BindVertexArray(self.vao);
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo);
+            let vertex_array_byte_size = (vertices.len() * std::mem::size_of::<f32>()) as isize;
+            gl::BufferData(gl::ARRAY_BUFFER, vertex_array_byte_size, vertices.as_ptr() as *const GLvoid, gl::STREAM_DRAW);
+            gl::DrawArrays(gl::TRIANGLES, 0, (vertices.len() / FLOATS_PER_VERTEX) as i32);
+            gl::BindVertexArray(0);
+
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+            gl::Disable(gl::BLEND);
+            gl::Enable(gl::DEPTH_TEST);
+        }
+    }
+}
+
+impl Drop for TextRenderer {
+    fn drop(&mut self) {
+        unsafe {
+            if self.vao != 0 {
+                gl::DeleteVertexArrays(1, &self.vao);
+            }
+            if self.vbo != 0 {
+                gl::DeleteBuffers(1, &self.vbo);
+            }
+        }
+    }
+}
@@ -0,0 +1,38 @@
+use app::StatusOr;
+use assets::AssetManager;
+use entity::PhysicalWorld;
+use glm;
+use render::{
+    HexGridRenderer,
+    Sprite3D,
+};
+
+// Owns the GPU resources needed to draw a PhysicalWorld, which itself stays GPU-free.
+pub struct WorldRenderer {
+    athlete_sprite: Sprite3D,
+    ball_sprite: Sprite3D,
+    hex_grid: HexGridRenderer,
+}
+
+impl WorldRenderer {
+    pub fn new(world: &PhysicalWorld, assets: &mut AssetManager) -> StatusOr<WorldRenderer> {
+        let mut athlete_sprite = Sprite3D::new(world.athlete().sprite_sheet_info(), assets)?;
+        athlete_sprite.object_center = world.athlete().object_center();
+        let mut ball_sprite = Sprite3D::new(world.ball().sprite_sheet_info(), assets)?;
+        ball_sprite.object_center = world.ball().object_center();
+        Ok(WorldRenderer {
+            athlete_sprite,
+            ball_sprite,
+            hex_grid: HexGridRenderer::new(world.hex_grid(), assets)?,
+        })
+    }
+
+    // alpha is how far between the last two physics steps to draw entities.
+    pub fn draw(&self, world: &PhysicalWorld, projection_view: &glm::Mat4, alpha: f32) {
+        let athlete = world.athlete();
+        self.athlete_sprite.draw(projection_view, &athlete.model(alpha), athlete.animation.current_mesh_index());
+        let ball = world.ball();
+        self.ball_sprite.draw(projection_view, &ball.model(alpha), ball.animation.current_mesh_index());
+        self.hex_grid.draw(projection_view);
+    }
+}
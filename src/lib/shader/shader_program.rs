@@ -1,4 +1,7 @@
-use app::StatusOr;
+use app::{
+    Error,
+    StatusOr,
+};
 use file;
 use gl::{
     self,
@@ -7,15 +10,33 @@ use gl::{
 use glm;
 use std::{
     ffi::CString,
+    path::{
+        Path,
+        PathBuf,
+    },
     self,
 };
 
-fn compile_shader(path: &str, shader_type: GLenum) -> StatusOr<GLuint> {
+fn read_info_log(info_log_len: GLint, get_info_log: &dyn Fn(GLsizei, *mut GLchar)) -> String {
+    if info_log_len <= 0 {
+        return String::new();
+    }
+    let mut info_log: Vec<u8> = vec![0; info_log_len as usize];
+    get_info_log(info_log_len, info_log.as_mut_ptr() as *mut GLchar);
+    // Drop the null terminator.
+    info_log.pop();
+    String::from_utf8_lossy(&info_log).into_owned()
+}
+
+fn compile_shader(path: &Path, shader_type: GLenum) -> StatusOr<GLuint> {
     let slurped_shader_code = file::util::slurp_file(path)
-        .map_err(|err| format!("Error reading shader ({}), code: {}", path, err))?;
+        .map_err(|err| Error::io(path.to_path_buf(), err))?;
     let shader_c_str =
         CString::new(slurped_shader_code.as_str())
-            .map_err(|err| format!("Couldn't turn shader {} into a C string. Reason: {}", path, err))?;
+            .map_err(|err| Error::ShaderCompile {
+                path: path.to_path_buf(),
+                info_log: format!("Couldn't turn shader into a C string. Reason: {}", err),
+            })?;
     unsafe {
         let shader_id = gl::CreateShader(shader_type);
         gl::ShaderSource(shader_id, 1, &shader_c_str.as_ptr(), std::ptr::null());
@@ -28,18 +49,17 @@ fn compile_shader(path: &str, shader_type: GLenum) -> StatusOr<GLuint> {
         } else {
             let mut info_log_len = 0;
             gl::GetShaderiv(shader_id, gl::INFO_LOG_LENGTH, &mut info_log_len);
-            let mut info_log = Vec::with_capacity(info_log_len as usize);
-            info_log.set_len((info_log_len as usize) - 1);
-            gl::GetShaderInfoLog(shader_id, info_log_len, std::ptr::null_mut(), info_log.as_mut_ptr() as *mut GLchar);
-            let err_string = String::from_utf8(info_log)
-                    .map_err(|_err|
-                        format!("Shader failed to compile. Explanation was invalid UTF-8. Shader: {}", path))?;
-            Err(err_string)
+            let info_log = read_info_log(info_log_len, &|len, buffer| {
+                gl::GetShaderInfoLog(shader_id, len, std::ptr::null_mut(), buffer);
+            });
+            gl::DeleteShader(shader_id);
+            Err(Error::ShaderCompile { path: path.to_path_buf(), info_log })
         }
     }
 }
 
-fn compile_program(shaders: &[GLuint]) -> StatusOr<GLuint> {
+// paths are only used to describe link errors.
+fn compile_program(shaders: &[GLuint], paths: &[&Path]) -> StatusOr<GLuint> {
     unsafe {
         let program_id = gl::CreateProgram();
         for shader in shaders.iter() {
@@ -52,14 +72,14 @@ fn compile_program(shaders: &[GLuint]) -> StatusOr<GLuint> {
         if success as GLboolean == gl::TRUE {
             Ok(program_id)
         } else {
-            let mut error_log: Vec<u8> = Vec::with_capacity(512);
-            let error_log_ptr = error_log.as_mut_slice().as_mut_ptr() as *mut i8;
-            gl::GetProgramInfoLog(program_id, error_log.capacity() as i32, std::ptr::null_mut(), error_log_ptr);
-            let err_string =
-                String::from_utf8(error_log)
-                    .map_err(|_err|
-                        String::from("Program failed to compile. Could not retrieve reason."))?;
-            Err(err_string)
+            let mut info_log_len = 0;
+            gl::GetProgramiv(program_id, gl::INFO_LOG_LENGTH, &mut info_log_len);
+            let info_log = read_info_log(info_log_len, &|len, buffer| {
+                gl::GetProgramInfoLog(program_id, len, std::ptr::null_mut(), buffer);
+            });
+            gl::DeleteProgram(program_id);
+            let paths: Vec<PathBuf> = paths.iter().map(|path| path.to_path_buf()).collect();
+            Err(Error::ShaderLink { paths, info_log })
         }
     }
 }
@@ -69,11 +89,11 @@ pub struct ShaderProgram {
 }
 
 impl ShaderProgram {
-    pub fn from_short_pipeline(vertex_filepath: &str, fragment_filepath: &str) -> StatusOr<ShaderProgram> {
+    pub fn from_short_pipeline(vertex_filepath: &Path, fragment_filepath: &Path) -> StatusOr<ShaderProgram> {
         let vertex = compile_shader(vertex_filepath, gl::VERTEX_SHADER)?;
         let fragment = compile_shader(fragment_filepath, gl::FRAGMENT_SHADER)?;
         let shader_program = ShaderProgram {
-            program: compile_program(&[vertex, fragment])?
+            program: compile_program(&[vertex, fragment], &[vertex_filepath, fragment_filepath])?
         };
         unsafe {
             gl::DeleteShader(vertex);
@@ -82,14 +102,15 @@ impl ShaderProgram {
         Ok(shader_program)
     }
 
-    pub fn from_long_pipeline(vertex_filepath: &str,
-                              geometry_filepath: &str,
-                              fragment_filepath: &str) -> StatusOr<ShaderProgram> {
+    pub fn from_long_pipeline(vertex_filepath: &Path,
+                              geometry_filepath: &Path,
+                              fragment_filepath: &Path) -> StatusOr<ShaderProgram> {
         let vertex = compile_shader(vertex_filepath, gl::VERTEX_SHADER)?;
         let geometry = compile_shader(geometry_filepath, gl::GEOMETRY_SHADER)?;
         let fragment = compile_shader(fragment_filepath, gl::FRAGMENT_SHADER)?;
         let shader_program = ShaderProgram {
-            program: compile_program(&[vertex, geometry, fragment])?
+            program: compile_program(&[vertex, geometry, fragment],
+                                     &[vertex_filepath, geometry_filepath, fragment_filepath])?
         };
         unsafe {
             gl::DeleteShader(vertex);
@@ -169,6 +190,19 @@ impl ShaderProgram {
         }
     }
 
+    // Points the named uniform block at binding, where a buffer is attached with glBindBufferBase.
+    pub fn set_uniform_block_binding(&self, name: &'static str, binding: GLuint) {
+        unsafe {
+            let c_str = CString::new(name)
+                .map_err(|err| format!("Couldn't uniform block name {} into a C string. Reason: {}", name, err)).unwrap();
+            let index = gl::GetUniformBlockIndex(self.program, c_str.as_ptr() as *const GLchar);
+            if index == gl::INVALID_INDEX {
+                panic!("Uniform block: {}", name);
+            }
+            gl::UniformBlockBinding(self.program, index, binding);
+        }
+    }
+
     pub fn activate(&self) {
         unsafe {
             gl::UseProgram(self.program);
@@ -1,5 +1,12 @@
 #version 330 core
 
+#define MAX_LIGHTS 32
+#define POINT_LIGHT 0
+#define SPOT_LIGHT 1
+#define DIRECTIONAL_LIGHT 2
+#define MAX_CASCADES 4
+#define MAX_SPOT_SHADOWS 4
+
 out vec4 frag_color;
   
 in vec2 texture_coords;
@@ -7,39 +14,117 @@ in vec2 texture_coords;
 uniform sampler2D position_tex;
 uniform sampler2D normal_tex;
 uniform sampler2D color_tex;
+uniform vec3 view_position;
+uniform mat4 view;
 
-void main()
+// Filled from render/shadow_maps.rs.
+uniform sampler2DArrayShadow cascade_shadows;
+uniform sampler2DArrayShadow spot_shadows;
+uniform mat4 cascade_matrices[MAX_CASCADES];
+uniform float cascade_far_depths[MAX_CASCADES];
+uniform int num_cascades;
+uniform mat4 spot_shadow_matrices[MAX_SPOT_SHADOWS];
+uniform float shadow_depth_bias;
+uniform int pcf_radius;
+
+// Filled from render/lights.rs, which must match this layout.
+struct Light {
+	vec4 position_type; // xyz = position, w = type
+	vec4 direction;     // xyz = normalized direction the light points in
+	vec4 color;         // rgb = color * intensity
+	vec4 attenuation;   // x = constant, y = linear, z = quadratic
+	vec4 cone;          // x = cos(inner cone), y = cos(outer cone), z = shadow layer or -1
+};
+
+layout (std140) uniform Lights {
+	vec4 ambient_count; // rgb = ambient, w = number of lights
+	Light lights[MAX_LIGHTS];
+};
+
+// Fraction of a (2 * pcf_radius + 1)^2 kernel around position's texel that is lit.
+float pcf(sampler2DArrayShadow shadow_map, float layer, mat4 light_projection_view, vec3 position)
 {
-    vec3 position = texture(position_tex, texture_coords).rgb;
-    vec3 normal = texture(normal_tex, texture_coords).rgb;
-	vec4 diffuse_specular = texture(color_tex, texture_coords);
-	vec3 diffuse_color = diffuse_specular.rgb;
-	float specular_color = diffuse_specular.a;
+	vec4 light_clip = light_projection_view * vec4(position, 1.0);
+	vec3 coords = (light_clip.xyz / light_clip.w) * 0.5 + 0.5;
+	if (coords.z > 1.0) {
+		return 1.0;
+	}
+	vec2 texel = 1.0 / vec2(textureSize(shadow_map, 0).xy);
+	float lit = 0.0;
+	for (int x = -pcf_radius; x <= pcf_radius; ++x) {
+		for (int y = -pcf_radius; y <= pcf_radius; ++y) {
+			lit += texture(shadow_map, vec4(coords.xy + vec2(x, y) * texel, layer, coords.z - shadow_depth_bias));
+		}
+	}
+	float width = float(2 * pcf_radius + 1);
+	return lit / (width * width);
+}
 
-	vec3 light_color = vec3(1.0);
-	vec3 light_position = vec3(1.0, 22.5, 130.0);
-	float light_constant = 1.0;
-	float light_linear = 0.05;
-	float light_quadratic = 0.0004;
+float shadow_factor(Light light, int type, vec3 position)
+{
+	int layer = int(light.cone.z + 0.5);
+	if (light.cone.z < 0.0) {
+		return 1.0;
+	}
+	if (type == SPOT_LIGHT) {
+		return pcf(spot_shadows, float(layer), spot_shadow_matrices[layer], position);
+	}
+	// The main light: pick the first cascade that reaches this fragment.
+	float depth = -(view * vec4(position, 1.0)).z;
+	for (int cascade = 0; cascade < num_cascades; ++cascade) {
+		if (depth <= cascade_far_depths[cascade]) {
+			return pcf(cascade_shadows, float(cascade), cascade_matrices[cascade], position);
+		}
+	}
+	return 1.0;
+}
 
-	// Ambient
-	float amb = 0.9f;
-	vec3 ambient_light = amb * light_color;
+vec3 light_contribution(Light light, vec3 position, vec3 normal, vec3 view_dir, float specular_strength)
+{
+	int type = int(light.position_type.w + 0.5);
+	vec3 light_dir;
+	float attenuation = 1.0;
+	if (type == DIRECTIONAL_LIGHT) {
+		light_dir = -light.direction.xyz;
+	} else {
+		vec3 to_light = light.position_type.xyz - position;
+		float distance = length(to_light);
+		light_dir = to_light / max(distance, 0.0001);
+		attenuation = 1.0 / (light.attenuation.x + light.attenuation.y * distance + light.attenuation.z * (distance * distance));
+		if (type == SPOT_LIGHT) {
+			float cos_angle = dot(-light_dir, light.direction.xyz);
+			attenuation *= smoothstep(light.cone.y, light.cone.x, cos_angle);
+		}
+	}
 
 	// Diffuse
-	// vec3 light_dir = normalize(-vec3(0.0, 0.0, 1.0));
-	vec3 light_dir = normalize(light_position - position);
 	float diffuse_intensity = max(dot(normal, light_dir), 0.0);
-	vec3 diffuse_light = diffuse_intensity * light_color;
 
-	// Point light attenuation
-	float distance = length(light_position - position);
-	float attenuation = 1.0 / (light_constant + light_linear * distance + light_quadratic * (distance * distance)); 
+	// Specular (Blinn-Phong)
+	vec3 halfway = normalize(light_dir + view_dir);
+	float specular_intensity = specular_strength * pow(max(dot(normal, halfway), 0.0), 32.0);
+
+	float shadow = type == POINT_LIGHT ? 1.0 : shadow_factor(light, type, position);
+	return (diffuse_intensity + specular_intensity) * light.color.rgb * attenuation * shadow;
+}
+
+void main()
+{
+    vec3 position = texture(position_tex, texture_coords).rgb;
+    vec3 normal = texture(normal_tex, texture_coords).rgb;
+	vec4 diffuse_specular = texture(color_tex, texture_coords);
+	vec3 diffuse_color = diffuse_specular.rgb;
+	float specular_strength = diffuse_specular.a;
+	vec3 view_dir = normalize(view_position - position);
 
-	// Merge
-	vec3 color = (ambient_light + diffuse_light) * diffuse_color * attenuation;
+	vec3 light = ambient_count.rgb;
+	int num_lights = min(int(ambient_count.w + 0.5), MAX_LIGHTS);
+	for (int i = 0; i < num_lights; ++i) {
+		light += light_contribution(lights[i], position, normal, view_dir, specular_strength);
+	}
+	vec3 color = light * diffuse_color;
 
 	// Gamma correct
 	color = pow(color, vec3(1.0/2.2));
     frag_color = vec4(color, 1.0);
-}
\ No newline at end of file
+}
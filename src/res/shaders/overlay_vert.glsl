@@ -0,0 +1,11 @@
+#version 330 core
+layout (location = 0) in vec2 position;
+layout (location = 1) in vec4 vertex_color;
+
+out vec4 color;
+
+void main()
+{
+    color = vertex_color;
+    gl_Position = vec4(position, 0.0, 1.0);
+}
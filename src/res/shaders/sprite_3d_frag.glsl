@@ -4,7 +4,7 @@ layout (location = 0) out vec3 position;
 layout (location = 1) out vec3 normal;
 layout (location = 2) out vec4 color; // rgb = diffuse, a = specular
 
-in GS_OUT {
+in VS_OUT {
 	vec2 texture_coords;
 	vec3 normal;
 	vec3 world_space_position;
@@ -1,25 +1,24 @@
 #version 330 core
 layout (location = 0) in vec3 position;
+layout (location = 1) in vec3 normal;
+layout (location = 2) in vec2 uv;
 
 out VS_OUT {
 	vec2 texture_coords;
+	vec3 normal;
 	vec3 world_space_position;
 } vs_out;
 
 uniform vec3 center;
-uniform vec2 scale;
 uniform mat4 model;
 uniform mat4 projection_view;
-uniform float window_x_offset;
 
 void main() {
 	vec4 world_space_position = model * vec4(position - center, 1.0);
     gl_Position = projection_view *  world_space_position;
 
-	vec2 t = position.xy + vec2(window_x_offset, 0.0);
-	t /= scale;
-	t.y = 1.0 - t.y;
-
-    vs_out.texture_coords = t;
+    vs_out.texture_coords = uv;
+	// The inverse transpose keeps normals perpendicular under non-uniform scale.
+	vs_out.normal = normalize(mat3(transpose(inverse(model))) * normal);
 	vs_out.world_space_position = vec3(world_space_position);
-} 
\ No newline at end of file
+}
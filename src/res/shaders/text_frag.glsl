@@ -0,0 +1,13 @@
+#version 330 core
+
+in vec2 tex_coords;
+
+uniform sampler2D atlas;
+uniform vec4 color;
+
+out vec4 frag_color;
+
+void main()
+{
+    frag_color = vec4(color.rgb, color.a * texture(atlas, tex_coords).a);
+}
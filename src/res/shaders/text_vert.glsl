@@ -0,0 +1,15 @@
+#version 330 core
+layout (location = 0) in vec2 position;
+layout (location = 1) in vec2 vertex_tex_coords;
+
+// In pixels.
+uniform vec2 screen_size;
+
+out vec2 tex_coords;
+
+void main()
+{
+    tex_coords = vertex_tex_coords;
+    vec2 ndc = vec2(2.0 * position.x / screen_size.x - 1.0, 1.0 - 2.0 * position.y / screen_size.y);
+    gl_Position = vec4(ndc, 0.0, 1.0);
+}